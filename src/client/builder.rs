@@ -0,0 +1,307 @@
+/*
+ * Copyright (c) 2021. Aberic - All Rights Reserved.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::time::Duration;
+
+use crate::{HttpClient, Method, Request, Response, WebSocket};
+use crate::client::client::default_request;
+use crate::client::cookie_jar::CookieJar;
+use crate::client::pool::ClientPool;
+use crate::client::proxy::Proxy;
+use crate::client::redirect::RedirectPolicy;
+use crate::client::retry::RetryPolicy;
+use crate::client::timeout::Timeouts;
+use crate::http::header::ContentType;
+use crate::utils::errors::StarryResult;
+
+/// 请求构建器，通过[`HttpClient::request`]、[`HttpClient::post`]等方法创建
+///
+/// ```no_run
+/// use starry::HttpClient;
+///
+/// let resp = HttpClient::post("http://localhost:7878/path")
+///     .header("X-Token", "abc")
+///     .json(r#"{"name":"starry"}"#)
+///     .send();
+/// ```
+///
+/// 地址解析失败等构建过程中的错误会在[`send`]时返回
+///
+/// [`HttpClient::request`]: crate::HttpClient::request
+/// [`HttpClient::post`]: crate::HttpClient::post
+/// [`send`]: crate::RequestBuilder::send
+pub struct RequestBuilder {
+    request: StarryResult<Request>,
+    /// 是否启用http压缩，如gzip、deflate等
+    compress: bool,
+    /// 启用http压缩时是否自动解压回复数据体
+    decompress: bool,
+    /// 自定义信任的CA证书集合，PEM格式
+    ca_pem: Option<Vec<u8>>,
+    /// 是否跳过https证书校验
+    insecure: bool,
+    /// 本次请求的超时配置
+    timeouts: Timeouts,
+    /// 保持连接时所使用的复用池，未设置时使用默认复用池
+    pool: Option<ClientPool>,
+    /// 代理配置，未设置时不使用代理
+    proxy: Option<Proxy>,
+    /// 重定向策略，未设置时使用默认策略
+    redirect: Option<RedirectPolicy>,
+    /// 重试策略，未设置时不重试
+    retry: Option<RetryPolicy>,
+    /// cookie存储
+    cookie_jar: Option<CookieJar>,
+}
+
+impl RequestBuilder {
+    pub(crate) fn new(method: Method, url: &str) -> Self {
+        RequestBuilder {
+            request: default_request(method, url),
+            compress: false,
+            decompress: true,
+            ca_pem: None,
+            insecure: false,
+            timeouts: Timeouts::default(),
+            pool: None,
+            proxy: None,
+            redirect: None,
+            retry: None,
+            cookie_jar: None,
+        }
+    }
+
+    fn map<F: FnOnce(&mut Request)>(mut self, f: F) -> Self {
+        if let Ok(request) = self.request.as_mut() {
+            f(request)
+        }
+        self
+    }
+
+    /// 设置请求头，如果已存在，则覆盖
+    pub fn header(self, k: &str, v: &str) -> Self {
+        self.map(|request| request.header.set_str(k, v))
+    }
+
+    /// 保持连接，请求结束后连接会放回复用池
+    pub fn keep_alive(self) -> Self {
+        self.map(|request| {
+            request.close = false;
+            request.header.set_connection()
+        })
+    }
+
+    /// 设置数据体
+    ///
+    /// * body 数据体
+    /// * content_type 数据类型
+    pub fn body(self, body: Vec<u8>, content_type: ContentType) -> Self {
+        self.map(|request| request.write_body(body, content_type))
+    }
+
+    /// 设置JSON格式的数据体
+    pub fn json<B: Into<Vec<u8>>>(self, body: B) -> Self {
+        self.body(body.into(), ContentType::APPLICATION_JSON)
+    }
+
+    /// 设置表单参数，发送时编码为"application/x-www-form-urlencoded"，存在附件时编码为"multipart/form-data"
+    ///
+    /// 已通过[`body`]或[`json`]设置数据体时，表单参数不会被发送
+    ///
+    /// [`body`]: crate::RequestBuilder::body
+    /// [`json`]: crate::RequestBuilder::json
+    pub fn form(self, k: &str, v: &str) -> Self {
+        self.map(|request| request.form_set(k.to_string(), v.to_string()))
+    }
+
+    /// 添加附件，发送时编码为"multipart/form-data"
+    ///
+    /// * name 表单字段名
+    /// * filename 文件名
+    /// * content 文件内容
+    /// * content_type 文件数据类型
+    pub fn multipart(self, name: &str, filename: &str, content: Vec<u8>, content_type: ContentType) -> Self {
+        self.map(|request| request.multipart_form_insert(name.to_string(), filename.to_string(), content, content_type))
+    }
+
+    /// 添加磁盘文件附件，发送时编码为"multipart/form-data"
+    ///
+    /// 文件名取自路径，数据类型根据扩展名推断，文件内容在发送时才从磁盘读取，适合上传大文件
+    ///
+    /// * name 表单字段名
+    /// * path 文件路径
+    pub fn multipart_file(mut self, name: &str, path: &str) -> Self {
+        self.request = match self.request {
+            Ok(mut request) => match request.multipart_form_file(name.to_string(), path) {
+                Ok(()) => Ok(request),
+                Err(err) => Err(err)
+            },
+            Err(err) => Err(err)
+        };
+        self
+    }
+
+    /// 启用http压缩，如gzip、deflate等，回复数据体会被自动解压
+    pub fn compress(mut self) -> Self {
+        self.compress = true;
+        self
+    }
+
+    /// 设置启用http压缩时是否自动解压回复数据体，关闭后返回原始的压缩数据
+    pub fn decompress(mut self, decompress: bool) -> Self {
+        self.decompress = decompress;
+        self
+    }
+
+    /// 设置https请求所信任的CA证书集合
+    ///
+    /// * ca_pem PEM格式的CA证书，可以包含多个证书
+    pub fn ca_pem(mut self, ca_pem: Vec<u8>) -> Self {
+        self.ca_pem = Some(ca_pem);
+        self
+    }
+
+    /// 跳过https证书及主机名校验，仅建议在测试环境中使用
+    pub fn insecure(mut self) -> Self {
+        self.insecure = true;
+        self
+    }
+
+    /// 设置建立tcp连接的超时时间
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.timeouts.connect = Some(timeout);
+        self
+    }
+
+    /// 设置单次读取的超时时间，即等待对端数据的最大间隔
+    pub fn read_timeout(mut self, timeout: Duration) -> Self {
+        self.timeouts.read = Some(timeout);
+        self
+    }
+
+    /// 设置单次写入的超时时间
+    pub fn write_timeout(mut self, timeout: Duration) -> Self {
+        self.timeouts.write = Some(timeout);
+        self
+    }
+
+    /// 设置请求总时限，包括建立连接、tls握手、发送请求及读取完整返回
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeouts.total = Some(timeout);
+        self
+    }
+
+    /// 设置保持连接时所使用的复用池
+    pub fn pool(mut self, pool: &ClientPool) -> Self {
+        self.pool = Some(pool.clone());
+        self
+    }
+
+    /// 设置代理
+    pub fn proxy(mut self, proxy: Proxy) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// 设置重定向策略
+    pub fn redirect(mut self, policy: RedirectPolicy) -> Self {
+        self.redirect = Some(policy);
+        self
+    }
+
+    /// 设置重试策略
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = Some(policy);
+        self
+    }
+
+    /// 设置cookie存储，回复设置的cookie会保存其中，并在后续请求中按规则发送
+    pub fn cookie_jar(mut self, jar: &CookieJar) -> Self {
+        self.cookie_jar = Some(jar.clone());
+        self
+    }
+
+    /// 构建请求，表单参数及附件会在此时编码为数据体
+    pub fn build(self) -> StarryResult<Request> {
+        let mut request = self.request?;
+        request.encode_form()?;
+        Ok(request)
+    }
+
+    /// 发送请求
+    pub fn send(self) -> StarryResult<Response> {
+        self.client()?.send()
+    }
+
+    /// 以当前配置完成websocket握手，返回客户端websocket连接，地址支持"ws://"及"wss://"
+    ///
+    /// 设置的请求头、cookie、证书、代理及超时均作用于握手请求，握手完成后读写超时继续作用于连接
+    ///
+    /// ```no_run
+    /// use starry::{HttpClient, Message, Method};
+    ///
+    /// let mut ws = HttpClient::request(Method::GET, "wss://localhost:7878/ws/echo")
+    ///     .header("X-Token", "abc")
+    ///     .websocket()
+    ///     .unwrap();
+    /// ws.send_text("hello").unwrap();
+    /// match ws.read().unwrap() {
+    ///     Message::Text(src) => println!("{}", src),
+    ///     _ => {}
+    /// }
+    /// ws.close(1000, "bye").unwrap();
+    /// ```
+    pub fn websocket(self) -> StarryResult<WebSocket> {
+        self.client()?.upgrade_websocket()
+    }
+
+    /// 根据构建器配置创建客户端
+    fn client(self) -> StarryResult<HttpClient> {
+        let compress = self.compress;
+        let decompress = self.decompress;
+        let ca_pem = self.ca_pem.clone();
+        let insecure = self.insecure;
+        let timeouts = self.timeouts;
+        let pool = self.pool.clone();
+        let proxy = self.proxy.clone();
+        let redirect = self.redirect;
+        let retry = self.retry.clone();
+        let cookie_jar = self.cookie_jar.clone();
+        let mut client = HttpClient::create(compress, self.build()?);
+        client.set_decompress(decompress);
+        client.set_timeouts(timeouts);
+        if let Some(src) = pool {
+            client.set_pool(&src)
+        }
+        if let Some(src) = proxy {
+            client.set_proxy(src)
+        }
+        if let Some(src) = redirect {
+            client.set_redirect(src)
+        }
+        if let Some(src) = retry {
+            client.set_retry(src)
+        }
+        if let Some(src) = cookie_jar {
+            client.set_cookie_jar(&src)
+        }
+        if let Some(src) = ca_pem {
+            client.set_ca_pem(src)
+        }
+        if insecure {
+            client.set_insecure()
+        }
+        Ok(client)
+    }
+}
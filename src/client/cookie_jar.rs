@@ -0,0 +1,413 @@
+/*
+ * Copyright (c) 2021. Aberic - All Rights Reserved.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::cmp::Reverse;
+use std::fmt::{Debug, Formatter};
+use std::fs;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{Request, Response, URL};
+use crate::http::header::Cookie;
+use crate::utils::errors::{Errs, StarryResult};
+
+/// 保存到文件时，仅HttpOnly的cookie所在行的前缀
+const HTTP_ONLY_PREFIX: &str = "#HttpOnly_";
+
+/// 客户端cookie存储
+///
+/// 保存回复中"Set-Cookie"设置的cookie，并在后续请求中按Domain、Path、Secure及过期时间规则
+/// 通过"Cookie"请求头发送，参考[`RFC6265`]
+///
+/// cookie存储可以被克隆，克隆后的实例共享同一组cookie
+///
+/// Domain属性为单级域名（如"com"）或ip地址时，仅在与请求主机相同时被接受。
+/// 不检查公共后缀列表（Public Suffix List），"co.uk"等公共后缀仍可被设置为Domain
+///
+/// ```no_run
+/// use starry::{CookieJar, HttpClient};
+///
+/// let jar = CookieJar::load("cookies.txt").unwrap_or_default();
+/// let resp = HttpClient::post("http://localhost:7878/login")
+///     .form("name", "starry")
+///     .cookie_jar(&jar)
+///     .send();
+/// jar.save("cookies.txt").unwrap();
+/// ```
+///
+/// [`RFC6265`]: https://datatracker.ietf.org/doc/html/rfc6265
+#[derive(Clone)]
+pub struct CookieJar {
+    entries: Arc<Mutex<Vec<Entry>>>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Entry {
+    name: String,
+    value: String,
+    /// 小写且不包含前导"."的域名
+    domain: String,
+    /// 未设置Domain属性时，仅发送给设置该cookie的主机
+    host_only: bool,
+    path: String,
+    secure: bool,
+    httponly: bool,
+    /// 过期时间，单位秒，为None时表示会话cookie
+    expires: Option<i64>,
+}
+
+impl CookieJar {
+    pub fn new() -> Self {
+        CookieJar { entries: Arc::new(Mutex::new(vec![])) }
+    }
+
+    /// 获取发送至`url`时会携带的cookie
+    pub fn cookies(&self, url: &str) -> StarryResult<Vec<Cookie>> {
+        let url = URL::from(url)?;
+        Ok(self.matches(&url).into_iter().map(|entry| Cookie::new(entry.name, entry.value)).collect())
+    }
+
+    /// 当前保存的cookie数量，包括已过期但尚未清理的cookie
+    pub fn len(&self) -> usize {
+        self.entries().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries().is_empty()
+    }
+
+    /// 清空所有cookie
+    pub fn clear(&self) {
+        self.entries().clear()
+    }
+
+    /// 保存至文件，格式与curl等工具使用的Netscape cookie文件一致，会话cookie的过期时间记为0
+    pub fn save(&self, filepath: &str) -> StarryResult<()> {
+        let now = now();
+        let mut content = String::from("# Netscape HTTP Cookie File\n");
+        for entry in self.entries().iter() {
+            if expired(entry, now) {
+                continue;
+            }
+            content.push_str(&format!("{}{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                                      if entry.httponly { HTTP_ONLY_PREFIX } else { "" },
+                                      if entry.host_only { entry.domain.clone() } else { format!(".{}", entry.domain) },
+                                      bool_str(!entry.host_only), entry.path, bool_str(entry.secure),
+                                      entry.expires.unwrap_or(0), entry.name, entry.value))
+        }
+        match fs::write(filepath, content) {
+            Ok(()) => Ok(()),
+            Err(err) => Err(Errs::strings(format!("cookie jar save to {} failed!", filepath), err))
+        }
+    }
+
+    /// 从[`save`]保存的文件中加载，已过期的cookie会被忽略
+    ///
+    /// [`save`]: crate::CookieJar::save
+    pub fn load(filepath: &str) -> StarryResult<CookieJar> {
+        let content = match fs::read_to_string(filepath) {
+            Ok(src) => src,
+            Err(err) => return Err(Errs::strings(format!("cookie jar load from {} failed!", filepath), err))
+        };
+        let now = now();
+        let mut entries = vec![];
+        for line in content.lines() {
+            let (line, httponly) = match line.strip_prefix(HTTP_ONLY_PREFIX) {
+                Some(src) => (src, true),
+                None => (line, false)
+            };
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.splitn(7, '\t').collect();
+            if fields.len() != 7 {
+                return Err(Errs::string(format!("cookie jar line {} invalid!", line)));
+            }
+            let expires = match fields[4].parse::<i64>() {
+                Ok(0) => None,
+                Ok(src) => Some(src),
+                Err(err) => return Err(Errs::strings(format!("cookie jar expires {} invalid!", fields[4]), err))
+            };
+            let entry = Entry {
+                name: fields[5].to_string(),
+                value: fields[6].to_string(),
+                domain: fields[0].trim_start_matches('.').to_lowercase(),
+                host_only: fields[1] != "TRUE",
+                path: fields[2].to_string(),
+                secure: fields[3] == "TRUE",
+                httponly,
+                expires,
+            };
+            if !expired(&entry, now) {
+                entries.push(entry)
+            }
+        }
+        Ok(CookieJar { entries: Arc::new(Mutex::new(entries)) })
+    }
+
+    /// 保存回复中"Set-Cookie"设置的cookie，单个无法解析或不符合规则的cookie会被忽略
+    ///
+    /// * url 本次请求地址
+    /// * response 本次请求的回复
+    pub(crate) fn store(&self, url: &URL, response: &Response) {
+        for (key, values) in response.header.map() {
+            if !key.eq_ignore_ascii_case("Set-Cookie") {
+                continue;
+            }
+            for value in values {
+                match Cookie::read_set_cookies(Some(vec![value.clone()])) {
+                    Ok(cookies) => for cookie in cookies {
+                        self.insert(url, cookie)
+                    },
+                    Err(err) => log::debug!("cookie jar ignore set-cookie {}! {}", value, err)
+                }
+            }
+        }
+    }
+
+    fn insert(&self, url: &URL, cookie: Cookie) {
+        if cookie.name.is_empty() {
+            return;
+        }
+        let host = url.authority.addr.host().to_lowercase();
+        let (domain, host_only) = match cookie.domain {
+            Some(domain) if !domain.trim().is_empty() => {
+                let domain = domain.trim().trim_start_matches('.').to_lowercase();
+                // 不能为其它域名及单级域名、ip地址等过宽的域名设置cookie
+                if !domain_allowed(&host, &domain) {
+                    log::debug!("cookie jar ignore cookie {} for domain {} from {}", cookie.name, domain, host);
+                    return;
+                }
+                (domain, false)
+            }
+            _ => (host, true)
+        };
+        let path = match cookie.path {
+            Some(path) if path.starts_with('/') => path,
+            _ => default_path(&url.location.path())
+        };
+        let now = now();
+        // Max-Age优先于Expires
+        let expires = match cookie.max_age {
+            Some(max_age) => Some(now + max_age as i64),
+            None => cookie.expires.map(|time| time.num_seconds())
+        };
+        let entry = Entry {
+            name: cookie.name,
+            value: cookie.value,
+            domain,
+            host_only,
+            path,
+            secure: cookie.secure,
+            httponly: cookie.httponly,
+            expires,
+        };
+        let mut entries = self.entries();
+        entries.retain(|src| !(src.name == entry.name && src.domain == entry.domain && src.path == entry.path));
+        // 已过期的cookie即删除同名cookie
+        if !expired(&entry, now) {
+            entries.push(entry)
+        }
+    }
+
+    /// 为请求添加"Cookie"请求头，请求已携带"Cookie"时追加在其后
+    pub(crate) fn apply(&self, request: &mut Request) {
+        let cookies: Vec<String> = self.matches(&request.url).into_iter()
+            .map(|entry| format!("{}={}", entry.name, entry.value))
+            .collect();
+        if cookies.is_empty() {
+            return;
+        }
+        let cookie = cookies.join("; ");
+        match request.header.get("Cookie") {
+            Some(src) if !src.trim().is_empty() => request.header.set_str("Cookie", &format!("{}; {}", src, cookie)),
+            _ => request.header.set_str("Cookie", &cookie)
+        }
+    }
+
+    /// 匹配发送至`url`时会携带的cookie，路径更长的cookie排在前面，同时清理已过期的cookie
+    fn matches(&self, url: &URL) -> Vec<Entry> {
+        let host = url.authority.addr.host().to_lowercase();
+        let path = match url.location.path() {
+            src if src.is_empty() => String::from("/"),
+            src => src
+        };
+        let secure = url.scheme.is_tls();
+        let now = now();
+        let mut entries = self.entries();
+        entries.retain(|entry| !expired(entry, now));
+        let mut res: Vec<Entry> = entries.iter().filter(|entry| {
+            let domain = if entry.host_only { host == entry.domain } else { domain_match(&host, &entry.domain) };
+            domain && path_match(&path, &entry.path) && (secure || !entry.secure)
+        }).cloned().collect();
+        res.sort_by_key(|entry| Reverse(entry.path.len()));
+        res
+    }
+
+    fn entries(&self) -> MutexGuard<'_, Vec<Entry>> {
+        match self.entries.lock() {
+            Ok(guard) => guard,
+            Err(err) => err.into_inner(),
+        }
+    }
+}
+
+impl Default for CookieJar {
+    fn default() -> Self {
+        CookieJar::new()
+    }
+}
+
+impl Debug for CookieJar {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CookieJar").field("len", &self.len()).finish()
+    }
+}
+
+fn now() -> i64 {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(src) => src.as_secs() as i64,
+        Err(_) => 0
+    }
+}
+
+fn expired(entry: &Entry, now: i64) -> bool {
+    match entry.expires {
+        Some(expires) => expires <= now,
+        None => false
+    }
+}
+
+fn bool_str(src: bool) -> &'static str {
+    if src { "TRUE" } else { "FALSE" }
+}
+
+/// Domain属性是否可由该主机设置，单级域名及ip地址仅允许与主机相同，不检查公共后缀
+fn domain_allowed(host: &str, domain: &str) -> bool {
+    if host == domain {
+        return true;
+    }
+    domain.contains('.') && domain.parse::<IpAddr>().is_err() && domain_match(host, domain)
+}
+
+/// 主机与域名匹配，即相等或为其子域名，ip地址仅支持相等
+fn domain_match(host: &str, domain: &str) -> bool {
+    if host == domain {
+        return true;
+    }
+    host.parse::<IpAddr>().is_err() && host.ends_with(domain) && host[0..host.len() - domain.len()].ends_with('.')
+}
+
+/// 请求路径与cookie路径匹配，即相等或以cookie路径为目录前缀
+fn path_match(path: &str, cookie_path: &str) -> bool {
+    if path == cookie_path {
+        return true;
+    }
+    path.starts_with(cookie_path) && (cookie_path.ends_with('/') || path[cookie_path.len()..].starts_with('/'))
+}
+
+/// 未设置Path属性时，默认为请求路径最后一个"/"之前的部分
+fn default_path(path: &str) -> String {
+    match path.rfind('/') {
+        Some(0) | None => String::from("/"),
+        Some(pos) => path[0..pos].to_string()
+    }
+}
+
+#[cfg(test)]
+mod cookie_jar_test {
+    use std::fs;
+
+    use crate::{Response, URL};
+    use crate::client::client::default_request;
+    use crate::client::cookie_jar::{CookieJar, default_path, domain_allowed, domain_match, path_match};
+    use crate::Method;
+
+    fn store(jar: &CookieJar, url: &str, set_cookies: Vec<&str>) {
+        let mut response = Response::success();
+        for set_cookie in set_cookies {
+            response.add_header_str("Set-Cookie", set_cookie);
+        }
+        jar.store(&URL::from(url).unwrap(), &response);
+    }
+
+    fn names(jar: &CookieJar, url: &str) -> Vec<String> {
+        jar.cookies(url).unwrap().into_iter().map(|cookie| format!("{}={}", cookie.name, cookie.value)).collect()
+    }
+
+    #[test]
+    fn match_rules() {
+        assert!(domain_match("a.example.com", "example.com"));
+        assert!(!domain_match("aexample.com", "example.com"));
+        assert!(!domain_match("127.0.0.1", "0.0.1"));
+        assert!(domain_allowed("a.example.com", "example.com"));
+        assert!(domain_allowed("localhost", "localhost"));
+        assert!(domain_allowed("127.0.0.1", "127.0.0.1"));
+        assert!(!domain_allowed("example.com", "com"));
+        assert!(!domain_allowed("a.1.2.3.4", "1.2.3.4"));
+        assert!(path_match("/a/b", "/a"));
+        assert!(path_match("/a/b", "/a/"));
+        assert!(!path_match("/ab", "/a"));
+        assert_eq!(default_path("/a/b/c"), "/a/b");
+        assert_eq!(default_path("/a"), "/");
+    }
+
+    #[test]
+    fn store_and_match() {
+        let jar = CookieJar::new();
+        store(&jar, "http://a.example.com/login/form", vec![
+            "sid=abc==; Path=/; HttpOnly",
+            "local=1",
+            "shared=2; Domain=.example.com; Path=/api",
+            "secure=3; Path=/; Secure",
+            "other=4; Domain=other.com",
+            "gone=5; Max-Age=0",
+        ]);
+        assert_eq!(jar.len(), 4);
+        assert_eq!(names(&jar, "http://a.example.com/login/x"), vec!["local=1", "sid=abc=="]);
+        assert_eq!(names(&jar, "http://b.example.com/api/users"), vec!["shared=2"]);
+        assert_eq!(names(&jar, "https://a.example.com/"), vec!["sid=abc==", "secure=3"]);
+        assert!(names(&jar, "http://other.com/").is_empty());
+
+        // 单级域名不能作为Domain，避免cookie被发送给同一顶级域名下的所有主机
+        store(&jar, "http://a.example.com/", vec!["tld=6; Domain=com"]);
+        assert_eq!(jar.len(), 4);
+        assert!(names(&jar, "http://evil.com/").is_empty());
+
+        // 过期时间早于当前时间的cookie会删除已有的同名cookie
+        store(&jar, "http://a.example.com/", vec!["sid=; Path=/; Expires=Thu, 01 Jan 1970 00:00:00 GMT"]);
+        assert_eq!(names(&jar, "http://a.example.com/"), Vec::<String>::new());
+
+        let mut request = default_request(Method::GET, "http://a.example.com/login/x").unwrap();
+        request.header.set_str("Cookie", "user=1");
+        jar.apply(&mut request);
+        assert_eq!(request.header.get("Cookie").unwrap(), "user=1; local=1");
+    }
+
+    #[test]
+    fn save_and_load() {
+        let jar = CookieJar::new();
+        store(&jar, "http://localhost/", vec!["sid=abc; HttpOnly", "token=1; Domain=localhost; Max-Age=3600; Secure"]);
+        let filepath = std::env::temp_dir().join(format!("starry_cookie_jar_{}.txt", std::process::id()));
+        let filepath = filepath.to_str().unwrap();
+        jar.save(filepath).unwrap();
+
+        let loaded = CookieJar::load(filepath).unwrap();
+        assert_eq!(*loaded.entries(), *jar.entries());
+        assert_eq!(names(&loaded, "https://localhost/"), vec!["sid=abc", "token=1"]);
+        fs::remove_file(filepath).unwrap();
+        assert!(CookieJar::load(filepath).is_err());
+    }
+}
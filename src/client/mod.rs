@@ -12,6 +12,19 @@
  * limitations under the License.
  */
 
+pub use builder::RequestBuilder;
 pub use client::HttpClient;
+pub use cookie_jar::CookieJar;
+pub use pool::{ClientPool, PoolStats};
+pub use proxy::Proxy;
+pub use redirect::RedirectPolicy;
+pub use retry::RetryPolicy;
 
-mod client;
\ No newline at end of file
+mod builder;
+mod client;
+mod cookie_jar;
+mod pool;
+mod proxy;
+mod redirect;
+mod retry;
+mod timeout;
\ No newline at end of file
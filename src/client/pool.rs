@@ -0,0 +1,401 @@
+/*
+ * Copyright (c) 2021. Aberic - All Rights Reserved.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::net::Shutdown;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::{Method, RequestBuilder};
+use crate::http::stream::HttpStream;
+
+/// 每个主机默认最多保留的空闲连接数
+const MAX_IDLE_PER_HOST: usize = 10;
+
+/// 默认空闲超时时间
+const IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 客户端连接复用池
+///
+/// 仅保持连接（"Connection: keep-alive"）的请求会使用复用池。
+/// 连接在取出前会依次检查空闲超时、最大存活时间以及对端是否已关闭，不可用的连接会被直接关闭。
+/// 每次取出或放回连接时，所有主机下已过期的空闲连接都会被关闭
+///
+/// 复用池可以被克隆，克隆后的实例共享同一组连接及配置
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// use starry::{ClientPool, Method};
+///
+/// let mut pool = ClientPool::new();
+/// pool.set_max_idle_per_host(4);
+/// pool.set_idle_timeout(Duration::from_secs(10));
+/// let resp = pool.request(Method::GET, "http://localhost:7878/path").send();
+/// println!("stats = {:?}", pool.stats());
+/// ```
+#[derive(Clone)]
+pub struct ClientPool {
+    shared: Arc<Shared>,
+}
+
+/// 复用池统计信息
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PoolStats {
+    /// 当前空闲连接数
+    pub idle: usize,
+    /// 当前正在使用中的连接数
+    pub active: usize,
+    /// 累计新建连接数
+    pub created: usize,
+    /// 累计复用连接数
+    pub reused: usize,
+}
+
+struct Shared {
+    inner: Mutex<Inner>,
+    active: AtomicUsize,
+    created: AtomicUsize,
+    reused: AtomicUsize,
+}
+
+struct Inner {
+    /// 每个主机最多保留的空闲连接数
+    max_idle_per_host: usize,
+    /// 空闲超时时间，连接放回复用池后超过该时间未被取出即关闭
+    idle_timeout: Duration,
+    /// 连接最大存活时间，自建立起超过该时间即不再复用
+    max_lifetime: Option<Duration>,
+    /// 以"scheme://host"为键的空闲连接集合，后放回的连接排在末尾，取出时优先使用
+    idles: HashMap<String, Vec<Idle>>,
+}
+
+struct Idle {
+    stream: HttpStream,
+    /// 连接建立时刻
+    created: Instant,
+    /// 放回复用池时刻
+    since: Instant,
+}
+
+impl ClientPool {
+    pub fn new() -> Self {
+        ClientPool {
+            shared: Arc::new(Shared {
+                inner: Mutex::new(Inner {
+                    max_idle_per_host: MAX_IDLE_PER_HOST,
+                    idle_timeout: IDLE_TIMEOUT,
+                    max_lifetime: None,
+                    idles: HashMap::new(),
+                }),
+                active: AtomicUsize::new(0),
+                created: AtomicUsize::new(0),
+                reused: AtomicUsize::new(0),
+            }),
+        }
+    }
+
+    /// 设置每个主机最多保留的空闲连接数，默认10，为0时不复用连接
+    pub fn set_max_idle_per_host(&mut self, max: usize) {
+        self.inner().max_idle_per_host = max
+    }
+
+    /// 设置空闲超时时间，默认30秒
+    pub fn set_idle_timeout(&mut self, timeout: Duration) {
+        self.inner().idle_timeout = timeout
+    }
+
+    /// 设置连接最大存活时间，默认不限制
+    pub fn set_max_lifetime(&mut self, lifetime: Duration) {
+        self.inner().max_lifetime = Some(lifetime)
+    }
+
+    /// 创建使用当前复用池的请求构建器
+    pub fn request(&self, method: Method, url: &str) -> RequestBuilder {
+        RequestBuilder::new(method, url).pool(self)
+    }
+
+    /// 当前统计信息
+    pub fn stats(&self) -> PoolStats {
+        let idle = self.inner().idles.values().map(|idles| idles.len()).sum();
+        PoolStats {
+            idle,
+            active: self.shared.active.load(Ordering::Acquire),
+            created: self.shared.created.load(Ordering::Acquire),
+            reused: self.shared.reused.load(Ordering::Acquire),
+        }
+    }
+
+    /// 关闭并清空所有空闲连接，使用中的连接不受影响
+    pub fn clear(&self) {
+        for (_, idles) in self.inner().idles.drain() {
+            for idle in idles {
+                close(idle.stream)
+            }
+        }
+    }
+
+    /// 取出可用的空闲连接，同时清理所有主机下已过期的空闲连接，已被对端关闭的连接会被直接关闭
+    pub(crate) fn checkout(&self, key: &str) -> Option<Pooled> {
+        self.inner().sweep(Instant::now());
+        loop {
+            let idle = match self.inner().idles.get_mut(key) {
+                Some(idles) => idles.pop(),
+                None => None
+            };
+            match idle {
+                Some(idle) => {
+                    if !idle.stream.is_alive() {
+                        log::trace!("client pool {} drop stale stream", key);
+                        close(idle.stream);
+                        continue;
+                    }
+                    self.shared.reused.fetch_add(1, Ordering::AcqRel);
+                    return Some(self.pooled(key, idle.stream, idle.created));
+                }
+                None => return None
+            }
+        }
+    }
+
+    /// 将新建的连接纳入复用池管理
+    pub(crate) fn register(&self, key: &str, stream: HttpStream) -> Pooled {
+        self.shared.created.fetch_add(1, Ordering::AcqRel);
+        self.pooled(key, stream, Instant::now())
+    }
+
+    fn pooled(&self, key: &str, stream: HttpStream, created: Instant) -> Pooled {
+        self.shared.active.fetch_add(1, Ordering::AcqRel);
+        Pooled { pool: self.clone(), key: key.to_string(), stream: Some(stream), created }
+    }
+
+    /// 放回连接，同时清理所有主机下已过期的空闲连接
+    fn checkin(&self, key: &str, stream: HttpStream, created: Instant) {
+        let now = Instant::now();
+        let idle = Idle { stream, created, since: now };
+        let mut inner = self.inner();
+        inner.sweep(now);
+        if expired(&idle, now, inner.idle_timeout, inner.max_lifetime) {
+            return close(idle.stream);
+        }
+        let max = inner.max_idle_per_host;
+        let idles = inner.idles.entry(key.to_string()).or_default();
+        if idles.len() < max {
+            idles.push(idle)
+        } else {
+            close(idle.stream)
+        }
+    }
+
+    fn inner(&self) -> MutexGuard<'_, Inner> {
+        match self.shared.inner.lock() {
+            Ok(guard) => guard,
+            Err(err) => err.into_inner(),
+        }
+    }
+}
+
+impl Inner {
+    /// 关闭所有主机下已过期的空闲连接，并移除不再有空闲连接的主机
+    fn sweep(&mut self, now: Instant) {
+        let (idle_timeout, max_lifetime) = (self.idle_timeout, self.max_lifetime);
+        self.idles.retain(|key, idles| {
+            let mut index = 0;
+            while index < idles.len() {
+                if expired(&idles[index], now, idle_timeout, max_lifetime) {
+                    log::trace!("client pool {} drop expired stream", key);
+                    close(idles.remove(index).stream);
+                } else {
+                    index += 1
+                }
+            }
+            !idles.is_empty()
+        })
+    }
+}
+
+impl Default for ClientPool {
+    fn default() -> Self {
+        ClientPool::new()
+    }
+}
+
+impl Debug for ClientPool {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let (max_idle_per_host, idle_timeout, max_lifetime) = {
+            let inner = self.inner();
+            (inner.max_idle_per_host, inner.idle_timeout, inner.max_lifetime)
+        };
+        f.debug_struct("ClientPool")
+            .field("max_idle_per_host", &max_idle_per_host)
+            .field("idle_timeout", &idle_timeout)
+            .field("max_lifetime", &max_lifetime)
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+/// 从复用池中取出的连接
+///
+/// 通过[`release`]放回复用池，未放回即被释放时关闭连接
+///
+/// [`release`]: crate::client::pool::Pooled::release
+pub(crate) struct Pooled {
+    pool: ClientPool,
+    key: String,
+    stream: Option<HttpStream>,
+    created: Instant,
+}
+
+impl Pooled {
+    pub(crate) fn stream(&self) -> &HttpStream {
+        self.stream.as_ref().unwrap()
+    }
+
+    /// 请求完成且连接可继续使用时，放回复用池
+    pub(crate) fn release(mut self) {
+        if let Some(stream) = self.stream.take() {
+            self.pool.checkin(&self.key, stream, self.created)
+        }
+    }
+}
+
+impl Drop for Pooled {
+    fn drop(&mut self) {
+        self.pool.shared.active.fetch_sub(1, Ordering::AcqRel);
+        if let Some(stream) = self.stream.take() {
+            close(stream)
+        }
+    }
+}
+
+/// 空闲超时或超过最大存活时间
+fn expired(idle: &Idle, now: Instant, idle_timeout: Duration, max_lifetime: Option<Duration>) -> bool {
+    if now.duration_since(idle.since) >= idle_timeout {
+        return true;
+    }
+    match max_lifetime {
+        Some(lifetime) => now.duration_since(idle.created) >= lifetime,
+        None => false
+    }
+}
+
+fn close(stream: HttpStream) {
+    match stream.shutdown(Shutdown::Both) {
+        Ok(_) => {}
+        Err(err) => log::trace!("client pool stream shutdown failed! {}", err)
+    }
+}
+
+#[cfg(test)]
+mod pool_test {
+    use std::io::Read;
+    use std::net::{TcpListener, TcpStream};
+    use std::time::Duration;
+
+    use crate::client::pool::{ClientPool, PoolStats};
+    use crate::http::stream::HttpStream;
+    use crate::utils::concurrent::Thread;
+
+    fn connect(listener: &TcpListener) -> (HttpStream, TcpStream) {
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        (HttpStream::Tcp(client), server)
+    }
+
+    #[test]
+    fn checkout_checkin() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut pool = ClientPool::new();
+        pool.set_max_idle_per_host(1);
+        assert!(pool.checkout("http://a").is_none());
+
+        let (stream1, _server1) = connect(&listener);
+        let (stream2, _server2) = connect(&listener);
+        let pooled1 = pool.register("http://a", stream1);
+        let pooled2 = pool.register("http://a", stream2);
+        assert_eq!(pool.stats(), PoolStats { idle: 0, active: 2, created: 2, reused: 0 });
+        pooled1.release();
+        // 超出空闲连接上限，直接关闭
+        pooled2.release();
+        assert_eq!(pool.stats(), PoolStats { idle: 1, active: 0, created: 2, reused: 0 });
+
+        let pooled = pool.checkout("http://a").unwrap();
+        assert!(pool.checkout("http://b").is_none());
+        assert_eq!(pool.stats(), PoolStats { idle: 0, active: 1, created: 2, reused: 1 });
+        // 未放回的连接在释放时关闭
+        drop(pooled);
+        assert_eq!(pool.stats(), PoolStats { idle: 0, active: 0, created: 2, reused: 1 });
+    }
+
+    #[test]
+    fn checkout_stale() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let pool = ClientPool::new();
+
+        // 对端已关闭的连接不会被复用
+        let (stream, server) = connect(&listener);
+        pool.register("http://a", stream).release();
+        drop(server);
+        Thread::sleep(Duration::from_millis(100));
+        assert!(pool.checkout("http://a").is_none());
+        assert_eq!(pool.stats().idle, 0);
+
+        // 空闲超时及超过最大存活时间的连接不会被复用
+        let mut pool = ClientPool::new();
+        pool.set_idle_timeout(Duration::from_millis(100));
+        let (stream, mut server) = connect(&listener);
+        pool.register("http://a", stream).release();
+        Thread::sleep(Duration::from_millis(200));
+        assert!(pool.checkout("http://a").is_none());
+        let mut buf = [0; 1];
+        assert_eq!(server.read(&mut buf).unwrap(), 0);
+
+        let mut pool = ClientPool::new();
+        pool.set_max_lifetime(Duration::from_millis(100));
+        let (stream, _server) = connect(&listener);
+        let pooled = pool.register("http://a", stream);
+        Thread::sleep(Duration::from_millis(200));
+        pooled.release();
+        assert_eq!(pool.stats().idle, 0);
+    }
+
+    #[test]
+    fn sweep_and_shared_config() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut pool = ClientPool::new();
+        // 克隆后修改的配置同样作用于克隆的实例
+        let cloned = pool.clone();
+        pool.set_idle_timeout(Duration::from_millis(100));
+
+        // 不再访问的主机下过期的空闲连接，在其它主机取出或放回连接时被关闭
+        let (stream, mut server) = connect(&listener);
+        cloned.register("http://a", stream).release();
+        Thread::sleep(Duration::from_millis(200));
+        assert!(cloned.checkout("http://b").is_none());
+        assert_eq!(pool.stats().idle, 0);
+        let mut buf = [0; 1];
+        assert_eq!(server.read(&mut buf).unwrap(), 0);
+
+        let (stream, mut server) = connect(&listener);
+        cloned.register("http://a", stream).release();
+        Thread::sleep(Duration::from_millis(200));
+        let (stream, _server) = connect(&listener);
+        pool.register("http://b", stream).release();
+        assert_eq!(server.read(&mut buf).unwrap(), 0);
+        assert_eq!(cloned.stats().idle, 1);
+    }
+}
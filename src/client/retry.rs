@@ -0,0 +1,267 @@
+/*
+ * Copyright (c) 2021. Aberic - All Rights Reserved.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::{Method, Response};
+use crate::utils::errors::Error;
+use crate::utils::Time;
+
+/// 默认首次重试前的等待时间
+const BASE_DELAY: Duration = Duration::from_millis(100);
+
+/// 默认单次重试前的最长等待时间
+const MAX_DELAY: Duration = Duration::from_secs(10);
+
+/// 默认接受的"Retry-After"最长等待时间
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// "Retry-After"回复头中的时间格式
+const HTTP_DATE: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+/// 重试策略
+///
+/// 请求因连接异常（连接被拒绝、被重置、复用的连接已被对端关闭等）、超时，或返回指定状态码（默认502、503、504）时重试。
+/// 默认仅重试安全及幂等的请求方法，即GET、HEAD、OPTIONS、TRACE、PUT、DELETE
+///
+/// 重试前的等待时间按指数退避计算，并在`[退避时间/2, 退避时间]`之间随机取值，避免多个客户端同时重试；
+/// 回复包含"Retry-After"时以其为准，但其超出上限（默认60秒）时不再重试，直接返回该回复。
+/// 设置了请求总时限时，等待后会超出总时限的重试不再进行
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// use starry::{HttpClient, Method, RetryPolicy};
+///
+/// let mut policy = RetryPolicy::new(3);
+/// policy.set_backoff(Duration::from_millis(200), Duration::from_secs(5));
+/// let resp = HttpClient::request(Method::GET, "http://localhost:7878/path").retry(policy).send();
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// 最多尝试次数，包括首次请求，为1时不重试
+    max_attempts: usize,
+    /// 首次重试前的等待时间，此后每次翻倍
+    base_delay: Duration,
+    /// 单次重试前的最长等待时间，不限制"Retry-After"
+    max_delay: Duration,
+    /// 接受的"Retry-After"最长等待时间，超出时不再重试
+    max_retry_after: Duration,
+    /// 需要重试的回复状态码
+    statuses: Vec<u16>,
+    /// 是否重试所有请求方法，包括POST、PATCH等非幂等方法
+    all_methods: bool,
+}
+
+impl RetryPolicy {
+    /// 不重试
+    pub fn none() -> Self {
+        RetryPolicy::new(1)
+    }
+
+    /// 最多尝试`max_attempts`次，包括首次请求
+    pub fn new(max_attempts: usize) -> Self {
+        RetryPolicy {
+            max_attempts,
+            base_delay: BASE_DELAY,
+            max_delay: MAX_DELAY,
+            max_retry_after: MAX_RETRY_AFTER,
+            statuses: vec![502, 503, 504],
+            all_methods: false,
+        }
+    }
+
+    /// 设置退避时间，默认首次等待100毫秒，此后每次翻倍，最长10秒
+    ///
+    /// * base 首次重试前的等待时间
+    /// * max 单次重试前的最长等待时间
+    pub fn set_backoff(&mut self, base: Duration, max: Duration) {
+        self.base_delay = base;
+        self.max_delay = max
+    }
+
+    /// 设置接受的"Retry-After"最长等待时间，默认60秒
+    ///
+    /// 回复要求的等待时间超出该值时不再重试，直接返回该回复，避免因对端要求而长时间阻塞
+    pub fn set_max_retry_after(&mut self, max: Duration) {
+        self.max_retry_after = max
+    }
+
+    /// 设置需要重试的回复状态码，默认502、503、504
+    pub fn set_statuses(&mut self, codes: &[u16]) {
+        self.statuses = codes.to_vec()
+    }
+
+    /// 重试所有请求方法，包括POST、PATCH等非幂等方法
+    ///
+    /// 非幂等请求在对端已处理但回复丢失时被重试，可能产生重复的副作用
+    pub fn set_all_methods(&mut self) {
+        self.all_methods = true
+    }
+
+    /// 请求失败后是否重试，需要重试时返回等待时间
+    ///
+    /// * attempts 已尝试的次数
+    pub(crate) fn on_error(&self, method: &Method, err: &Error, attempts: usize) -> Option<Duration> {
+        if !self.retryable(method, attempts) || !(err.is_connection() || err.is_timeout()) {
+            return None;
+        }
+        Some(self.backoff(attempts))
+    }
+
+    /// 收到回复后是否重试，需要重试时返回等待时间
+    ///
+    /// 回复包含"Retry-After"时以其为准，超出接受的最长等待时间时不再重试
+    ///
+    /// * attempts 已尝试的次数
+    pub(crate) fn on_response(&self, method: &Method, response: &Response, attempts: usize) -> Option<Duration> {
+        if !self.retryable(method, attempts) || !self.statuses.contains(&response.status.code()) {
+            return None;
+        }
+        match retry_after(response) {
+            Some(src) if src > self.max_retry_after => {
+                log::debug!("retry after {:?} exceeds {:?}, stop retrying!", src, self.max_retry_after);
+                None
+            }
+            Some(src) => Some(src),
+            None => Some(self.backoff(attempts))
+        }
+    }
+
+    fn retryable(&self, method: &Method, attempts: usize) -> bool {
+        attempts < self.max_attempts && (self.all_methods || method.is_safe() || method.is_idempotent())
+    }
+
+    /// 第`attempts`次尝试失败后的等待时间
+    fn backoff(&self, attempts: usize) -> Duration {
+        let mut delay = self.base_delay;
+        for _ in 1..attempts {
+            if delay >= self.max_delay {
+                break;
+            }
+            delay *= 2
+        }
+        let delay = delay.min(self.max_delay);
+        delay / 2 + delay.mul_f64(jitter() / 2.0)
+    }
+}
+
+impl Default for RetryPolicy {
+    /// 默认不重试
+    fn default() -> Self {
+        RetryPolicy::none()
+    }
+}
+
+/// 解析"Retry-After"回复头，支持秒数及http日期两种格式
+fn retry_after(response: &Response) -> Option<Duration> {
+    let src = response.header.get("Retry-After")?;
+    match src.trim().parse::<u64>() {
+        Ok(secs) => Some(Duration::from_secs(secs)),
+        Err(_) => match Time::parse_from_str(src.trim(), HTTP_DATE) {
+            Ok(time) => {
+                let now = match SystemTime::now().duration_since(UNIX_EPOCH) {
+                    Ok(src) => src.as_secs() as i64,
+                    Err(_) => 0
+                };
+                Some(Duration::from_secs((time.num_seconds() - now).max(0) as u64))
+            }
+            Err(_) => None
+        }
+    }
+}
+
+/// `[0, 1)`之间的随机数，每个`RandomState`实例使用不同的随机密钥
+fn jitter() -> f64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u32(SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |src| src.subsec_nanos()));
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod retry_test {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use crate::{Method, Response, Status};
+    use crate::client::retry::{HTTP_DATE, RetryPolicy};
+    use crate::utils::errors::Errs;
+    use crate::utils::Time;
+
+    #[test]
+    fn backoff() {
+        let mut policy = RetryPolicy::new(10);
+        policy.set_backoff(Duration::from_millis(100), Duration::from_millis(500));
+        for (attempts, delay) in vec![(1, 100), (2, 200), (3, 400), (4, 500), (9, 500)] {
+            let src = policy.backoff(attempts);
+            assert!(src >= Duration::from_millis(delay / 2) && src <= Duration::from_millis(delay), "{} {:?}", attempts, src);
+        }
+    }
+
+    #[test]
+    fn on_error() {
+        let policy = RetryPolicy::new(2);
+        let reset = Errs::connection("reset");
+        assert!(policy.on_error(&Method::GET, &reset, 1).is_some());
+        assert!(policy.on_error(&Method::PUT, &Errs::timeout("timeout"), 1).is_some());
+        // 超出次数、非幂等方法或其它错误不重试
+        assert!(policy.on_error(&Method::GET, &reset, 2).is_none());
+        assert!(policy.on_error(&Method::POST, &reset, 1).is_none());
+        assert!(policy.on_error(&Method::GET, &Errs::str("parse failed"), 1).is_none());
+        assert!(RetryPolicy::none().on_error(&Method::GET, &reset, 1).is_none());
+
+        let mut policy = RetryPolicy::new(2);
+        policy.set_all_methods();
+        assert!(policy.on_error(&Method::POST, &reset, 1).is_some());
+    }
+
+    #[test]
+    fn on_response() {
+        let mut policy = RetryPolicy::new(3);
+        assert!(policy.on_response(&Method::GET, &Response::custom(Status::SERVICE_UNAVAILABLE), 1).is_some());
+        assert!(policy.on_response(&Method::GET, &Response::custom(Status::INTERNAL_SERVER_ERROR), 1).is_none());
+        assert!(policy.on_response(&Method::POST, &Response::custom(Status::BAD_GATEWAY), 1).is_none());
+
+        let mut response = Response::custom(Status::TOO_MANY_REQUESTS);
+        response.set_header_str("Retry-After", "3");
+        assert!(policy.on_response(&Method::GET, &response, 1).is_none());
+        policy.set_statuses(&[429]);
+        assert_eq!(policy.on_response(&Method::GET, &response, 1).unwrap(), Duration::from_secs(3));
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
+        response.set_header_str("Retry-After", &Time::from_secs(now + 60).format_string(HTTP_DATE));
+        let delay = policy.on_response(&Method::GET, &response, 1).unwrap();
+        assert!(delay > Duration::from_secs(50) && delay <= Duration::from_secs(60));
+    }
+
+    #[test]
+    fn on_response_retry_after_limit() {
+        let mut policy = RetryPolicy::new(3);
+        policy.set_statuses(&[503]);
+        let mut response = Response::custom(Status::SERVICE_UNAVAILABLE);
+        // 超出接受的最长等待时间时不再重试
+        response.set_header_str("Retry-After", "86400");
+        assert!(policy.on_response(&Method::GET, &response, 1).is_none());
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
+        response.set_header_str("Retry-After", &Time::from_secs(now + 90 * 86400).format_string(HTTP_DATE));
+        assert!(policy.on_response(&Method::GET, &response, 1).is_none());
+
+        policy.set_max_retry_after(Duration::from_secs(2));
+        response.set_header_str("Retry-After", "3");
+        assert!(policy.on_response(&Method::GET, &response, 1).is_none());
+        response.set_header_str("Retry-After", "2");
+        assert_eq!(policy.on_response(&Method::GET, &response, 1).unwrap(), Duration::from_secs(2));
+    }
+}
@@ -0,0 +1,122 @@
+/*
+ * Copyright (c) 2021. Aberic - All Rights Reserved.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::io::{Error, ErrorKind, Read, Result, Write};
+use std::time::{Duration, Instant};
+
+use crate::http::stream::HttpStream;
+
+/// 客户端超时配置，未设置的项表示不限制
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Timeouts {
+    /// 建立tcp连接的超时时间
+    pub(crate) connect: Option<Duration>,
+    /// 单次读取的超时时间，即两次收到数据之间的最大间隔
+    pub(crate) read: Option<Duration>,
+    /// 单次写入的超时时间
+    pub(crate) write: Option<Duration>,
+    /// 请求总时限，包括建立连接、tls握手、发送请求及读取完整返回
+    pub(crate) total: Option<Duration>,
+}
+
+impl Timeouts {
+    /// 根据总时限计算请求截止时刻
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        self.total.map(|total| Instant::now() + total)
+    }
+}
+
+/// 取单项超时时间与截止时刻剩余时间中较小的一项
+///
+/// 已超过截止时刻时返回超时错误
+pub(crate) fn remaining(timeout: Option<Duration>, deadline: Option<Instant>) -> Result<Option<Duration>> {
+    match deadline {
+        Some(deadline) => {
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(Error::new(ErrorKind::TimedOut, "request deadline exceeded"));
+            }
+            let left = deadline - now;
+            match timeout {
+                Some(timeout) => Ok(Some(timeout.min(left))),
+                None => Ok(Some(left))
+            }
+        }
+        None => Ok(timeout)
+    }
+}
+
+/// 带超时控制的连接流
+///
+/// 每次读写前根据单次读写超时及请求截止时刻重新设置底层socket超时，
+/// 因此复用池中的连接不会沿用上一次请求的超时设置
+#[derive(Debug)]
+pub(crate) struct TimeoutStream {
+    inner: HttpStream,
+    read: Option<Duration>,
+    write: Option<Duration>,
+    deadline: Option<Instant>,
+}
+
+impl TimeoutStream {
+    pub(crate) fn new(inner: HttpStream, timeouts: &Timeouts, deadline: Option<Instant>) -> Self {
+        TimeoutStream { inner, read: timeouts.read, write: timeouts.write, deadline }
+    }
+}
+
+impl Read for TimeoutStream {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        self.inner.set_read_timeout(remaining(self.read, self.deadline)?)?;
+        self.inner.read(buf).map_err(timed_out)
+    }
+}
+
+impl Write for TimeoutStream {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.inner.set_write_timeout(remaining(self.write, self.deadline)?)?;
+        self.inner.write(buf).map_err(timed_out)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.inner.set_write_timeout(remaining(self.write, self.deadline)?)?;
+        self.inner.flush().map_err(timed_out)
+    }
+}
+
+/// socket超时在不同平台上分别表现为`WouldBlock`或`TimedOut`，统一为`TimedOut`
+fn timed_out(err: Error) -> Error {
+    match err.kind() {
+        ErrorKind::WouldBlock => Error::new(ErrorKind::TimedOut, err),
+        _ => err
+    }
+}
+
+#[cfg(test)]
+mod timeout_test {
+    use std::io::ErrorKind;
+    use std::time::{Duration, Instant};
+
+    use crate::client::timeout::remaining;
+
+    #[test]
+    fn remaining_test() {
+        assert_eq!(remaining(None, None).unwrap(), None);
+        assert_eq!(remaining(Some(Duration::from_secs(1)), None).unwrap(), Some(Duration::from_secs(1)));
+        let deadline = Instant::now() + Duration::from_secs(60);
+        assert_eq!(remaining(Some(Duration::from_secs(1)), Some(deadline)).unwrap(), Some(Duration::from_secs(1)));
+        assert!(remaining(None, Some(deadline)).unwrap().unwrap() <= Duration::from_secs(60));
+        let err = remaining(None, Some(Instant::now())).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TimedOut);
+    }
+}
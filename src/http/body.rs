@@ -12,6 +12,9 @@
  * limitations under the License.
  */
 
+use std::fmt::{Debug, Formatter};
+use std::io::{Read, Result};
+
 use bytes::{Buf, Bytes, BytesMut};
 
 #[derive(Debug, Clone)]
@@ -78,3 +81,49 @@ impl ToString for Body {
         String::from_utf8_lossy(self.reader.as_ref()).to_string()
     }
 }
+
+/// 流式数据体，回复时边读取边写入，无需事先将数据全部读入内存
+pub(crate) struct BodyStream {
+    pub(crate) reader: Box<dyn Read + Send>,
+    /// 数据体长度，已知时通过"Content-Length"声明，否则使用chunked编码
+    pub(crate) content_length: Option<u64>,
+}
+
+impl Debug for BodyStream {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BodyStream").field("content_length", &self.content_length).finish()
+    }
+}
+
+/// 将迭代器转换为数据源，每次读取最多返回迭代器产生的一项数据，不会跨项拼接
+///
+/// 配合chunked编码时，迭代器产生的每一项数据都会被及时写出
+pub(crate) struct IterReader<I: Iterator<Item=Vec<u8>>> {
+    iter: I,
+    data: Vec<u8>,
+    pos: usize,
+}
+
+impl<I: Iterator<Item=Vec<u8>>> IterReader<I> {
+    pub(crate) fn new(iter: I) -> Self {
+        IterReader { iter, data: vec![], pos: 0 }
+    }
+}
+
+impl<I: Iterator<Item=Vec<u8>>> Read for IterReader<I> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        while self.pos >= self.data.len() {
+            match self.iter.next() {
+                Some(src) => {
+                    self.data = src;
+                    self.pos = 0
+                }
+                None => return Ok(0)
+            }
+        }
+        let size = buf.len().min(self.data.len() - self.pos);
+        buf[0..size].copy_from_slice(&self.data[self.pos..self.pos + size]);
+        self.pos += size;
+        Ok(size)
+    }
+}
@@ -0,0 +1,297 @@
+/*
+ * Copyright (c) 2021. Aberic - All Rights Reserved.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::io::{Error, ErrorKind, Read, Result};
+
+use crate::utils::errors::{Errs, StarryResult};
+
+/// chunked编码结束块，即长度为0的块，且不携带trailer
+pub(crate) const LAST_CHUNK: &[u8] = b"0\r\n\r\n";
+
+/// 单行（块长度行及trailer行）允许的最大长度
+const MAX_LINE_LEN: usize = 8192;
+
+/// 允许的最大trailer行数
+const MAX_TRAILERS: usize = 64;
+
+/// 全部trailer行允许的最大总长度
+const MAX_TRAILERS_LEN: usize = 16 * 1024;
+
+/// 不允许通过trailer携带的字段，详见[`RFC9110，第6.5.1节`]
+///
+/// 包括消息分帧、路由、请求修饰、认证、cookie、回复控制及数据体处理相关字段，
+/// 这些字段在报头之后才出现时会被接收方错误地信任，因此直接丢弃
+///
+/// [`RFC9110，第6.5.1节`]: https://datatracker.ietf.org/doc/html/rfc9110#section-6.5.1
+const FORBIDDEN_TRAILERS: [&str; 37] = [
+    "transfer-encoding", "content-length", "trailer", "te", "connection", "keep-alive", "upgrade",
+    "proxy-connection", "host", "cache-control", "expect", "max-forwards", "pragma", "range", "if-match",
+    "if-none-match", "if-modified-since", "if-unmodified-since", "if-range", "authorization",
+    "proxy-authorization", "www-authenticate", "proxy-authenticate", "authentication-info",
+    "proxy-authentication-info", "cookie", "set-cookie", "age", "expires", "date", "location", "retry-after",
+    "vary", "warning", "content-encoding", "content-type", "content-range",
+];
+
+/// 结束块后携带的trailer键值对
+pub(crate) type Trailers = Vec<(String, String)>;
+
+/// 是否为不允许通过trailer携带的字段，字段名忽略大小写
+pub(crate) fn forbidden_trailer(name: &str) -> bool {
+    FORBIDDEN_TRAILERS.iter().any(|src| src.eq_ignore_ascii_case(name))
+}
+
+/// 将数据编码为单个chunk块，空数据会被编码为结束块，调用方应避免写入空数据
+///
+/// ```chunk
+/// chunk-size(hex) CRLF
+/// chunk-data CRLF
+/// ```
+pub(crate) fn encode_chunk(data: &[u8]) -> Vec<u8> {
+    let mut res = Vec::with_capacity(data.len() + 12);
+    res.extend_from_slice(format!("{:x}\r\n", data.len()).as_bytes());
+    res.extend_from_slice(data);
+    res.extend_from_slice(b"\r\n");
+    res
+}
+
+/// 将完整数据编码为chunked数据体，包括结束块
+pub(crate) fn encode(body: &[u8]) -> Vec<u8> {
+    let mut res = vec![];
+    if !body.is_empty() {
+        res.append(&mut encode_chunk(body));
+    }
+    res.extend_from_slice(LAST_CHUNK);
+    res
+}
+
+/// 解析chunked编码的数据体
+///
+/// 返回解码后的数据，以及结束块后携带的trailer键值对
+///
+/// * pre 解析报头时已从流中读出的数据体部分
+/// * reader 数据体剩余部分所在的流，只会读取到结束块及trailer为止
+pub(crate) fn decode<R: Read>(pre: &[u8], reader: &mut R) -> StarryResult<(Vec<u8>, Trailers)> {
+    let mut decoder = ChunkedDecoder::new(pre.to_vec());
+    let mut body = vec![];
+    let mut buffer = [0; 1024];
+    loop {
+        match decoder.read(reader, &mut buffer) {
+            Ok(0) => break,
+            Ok(size) => body.extend_from_slice(&buffer[0..size]),
+            Err(err) => return Err(Errs::ios("chunked body decode failed!", err))
+        }
+    }
+    Ok((body, decoder.trailers))
+}
+
+/// chunked数据体增量解码器
+///
+/// 解码器本身不持有流，每次读取时由调用方传入，以便与连接的其它读写操作共存
+#[derive(Debug)]
+pub(crate) struct ChunkedDecoder {
+    /// 已从流中读出但尚未解析的数据
+    buf: Vec<u8>,
+    /// 已解析位置
+    pos: usize,
+    /// 当前块剩余未读取的数据长度
+    remaining: usize,
+    /// 当前块数据已读取完毕，待读取块尾CRLF
+    chunk_end: bool,
+    /// 是否已读取到结束块及trailer
+    done: bool,
+    /// 结束块后携带的trailer键值对
+    pub(crate) trailers: Trailers,
+    /// 已读取的trailer总长度，包括行尾CRLF
+    pub(crate) trailers_len: usize,
+}
+
+impl ChunkedDecoder {
+    /// * pre 解析报头时已从流中读出的数据体部分
+    pub(crate) fn new(pre: Vec<u8>) -> Self {
+        ChunkedDecoder { buf: pre, pos: 0, remaining: 0, chunk_end: false, done: false, trailers: vec![], trailers_len: 0 }
+    }
+
+    /// 读取解码后的数据，返回0表示数据体已结束
+    pub(crate) fn read<R: Read>(&mut self, reader: &mut R, out: &mut [u8]) -> Result<usize> {
+        if self.done || out.is_empty() {
+            return Ok(0);
+        }
+        if self.remaining == 0 {
+            if self.chunk_end {
+                if !self.read_line(reader)?.is_empty() {
+                    return Err(invalid("chunk data not end with CRLF!"));
+                }
+                self.chunk_end = false;
+            }
+            self.remaining = self.read_size(reader)?;
+            if self.remaining == 0 {
+                self.read_trailers(reader)?;
+                self.done = true;
+                return Ok(0);
+            }
+        }
+        let max = out.len().min(self.remaining);
+        let size = if self.pos < self.buf.len() {
+            let size = max.min(self.buf.len() - self.pos);
+            out[0..size].copy_from_slice(&self.buf[self.pos..self.pos + size]);
+            self.pos += size;
+            size
+        } else {
+            match reader.read(&mut out[0..max])? {
+                0 => return Err(unexpected_eof()),
+                size => size
+            }
+        };
+        self.remaining -= size;
+        if self.remaining == 0 {
+            self.chunk_end = true;
+        }
+        Ok(size)
+    }
+
+    /// 读取块长度行，块长度后可能携带扩展信息，如`1a;name=value`，扩展信息直接忽略
+    fn read_size<R: Read>(&mut self, reader: &mut R) -> Result<usize> {
+        let line = self.read_line(reader)?;
+        let size_str = match line.iter().position(|b| *b == b';') {
+            Some(pos) => String::from_utf8_lossy(&line[0..pos]).to_string(),
+            None => String::from_utf8_lossy(&line).to_string(),
+        };
+        match usize::from_str_radix(size_str.trim(), 16) {
+            Ok(src) => Ok(src),
+            Err(err) => Err(invalid(&format!("chunk size {} parse failed! {}", size_str, err)))
+        }
+    }
+
+    /// 读取结束块后的trailer，行数及总长度超出限制时返回错误
+    fn read_trailers<R: Read>(&mut self, reader: &mut R) -> Result<()> {
+        loop {
+            let line = self.read_line(reader)?;
+            self.trailers_len += line.len() + 2;
+            if line.is_empty() {
+                return Ok(());
+            }
+            if self.trailers.len() >= MAX_TRAILERS || self.trailers_len > MAX_TRAILERS_LEN {
+                return Err(invalid("chunk trailers too large!"));
+            }
+            let line = String::from_utf8_lossy(&line).to_string();
+            match line.find(':') {
+                Some(pos) => self.trailers.push((line[0..pos].trim().to_string(), line[pos + 1..].trim().to_string())),
+                None => return Err(invalid(&format!("chunk trailer {} invalid!", line)))
+            }
+        }
+    }
+
+    /// 读取一行数据，不包括行尾CRLF
+    fn read_line<R: Read>(&mut self, reader: &mut R) -> Result<Vec<u8>> {
+        let mut from = self.pos;
+        loop {
+            match self.buf[from..].windows(2).position(|w| w == b"\r\n") {
+                Some(pos) => {
+                    let line = self.buf[self.pos..from + pos].to_vec();
+                    self.pos = from + pos + 2;
+                    // 已解析的数据不再保留
+                    self.buf.drain(0..self.pos);
+                    self.pos = 0;
+                    return Ok(line);
+                }
+                None => {
+                    if self.buf.len() - self.pos > MAX_LINE_LEN {
+                        return Err(invalid("chunk line too long!"));
+                    }
+                    // 回退一个字节，避免CRLF恰好被拆分在两次读取之间
+                    from = if self.buf.len() > self.pos { self.buf.len() - 1 } else { self.pos };
+                    let mut buffer = [0; 1024];
+                    match reader.read(&mut buffer)? {
+                        0 => return Err(unexpected_eof()),
+                        size => self.buf.extend_from_slice(&buffer[0..size])
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn invalid(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg.to_string())
+}
+
+fn unexpected_eof() -> Error {
+    Error::new(ErrorKind::UnexpectedEof, "chunked body unexpected eof!")
+}
+
+#[cfg(test)]
+mod chunked_test {
+    use crate::http::chunked::{decode, encode, encode_chunk, forbidden_trailer};
+
+    #[test]
+    fn decode_test() {
+        let pre = b"5;name=value\r\nhello\r\n8\r\n, starr";
+        let mut rest: &[u8] = b"y\r\n0\r\nExpires: never\r\nX-Sum:  abc \r\n\r\nGET / HTTP/1.1";
+        let (body, trailers) = decode(pre, &mut rest).unwrap();
+        assert_eq!(body, b"hello, starry".to_vec());
+        assert_eq!(trailers, vec![
+            ("Expires".to_string(), "never".to_string()),
+            ("X-Sum".to_string(), "abc".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn decode_trailers_limit_test() {
+        let mut trailers = String::new();
+        for i in 0..65 {
+            trailers.push_str(&format!("X-{}: {}\r\n", i, i));
+        }
+        let mut rest = format!("{}\r\n", trailers).into_bytes();
+        assert!(decode(b"3\r\nabc\r\n0\r\n", &mut rest.as_slice()).is_err());
+        rest = format!("X-Long: {}\r\nX-Long: {}\r\nX-Long: {}\r\n\r\n", "a".repeat(8000), "a".repeat(8000), "a".repeat(8000)).into_bytes();
+        assert!(decode(b"3\r\nabc\r\n0\r\n", &mut rest.as_slice()).is_err());
+    }
+
+    #[test]
+    fn forbidden_trailer_test() {
+        assert!(forbidden_trailer("Content-Length"));
+        assert!(forbidden_trailer("transfer-encoding"));
+        assert!(forbidden_trailer("AUTHORIZATION"));
+        assert!(forbidden_trailer("Set-Cookie"));
+        assert!(!forbidden_trailer("X-Checksum"));
+        assert!(!forbidden_trailer("Server-Timing"));
+    }
+
+    #[test]
+    fn decode_split_crlf_test() {
+        let pre = b"3\r";
+        let mut rest: &[u8] = b"\nabc\r\n0\r\n\r\n";
+        let (body, trailers) = decode(pre, &mut rest).unwrap();
+        assert_eq!(body, b"abc".to_vec());
+        assert!(trailers.is_empty());
+    }
+
+    #[test]
+    fn decode_failed_test() {
+        let mut rest: &[u8] = b"";
+        assert!(decode(b"zz\r\nabc\r\n0\r\n\r\n", &mut rest).is_err());
+        assert!(decode(b"3\r\nabcd\r\n0\r\n\r\n", &mut rest).is_err());
+        assert!(decode(b"3\r\nabc\r\n", &mut rest).is_err());
+    }
+
+    #[test]
+    fn encode_test() {
+        assert_eq!(encode_chunk(b"hello, starry"), b"d\r\nhello, starry\r\n".to_vec());
+        assert_eq!(encode(b"abc"), b"3\r\nabc\r\n0\r\n\r\n".to_vec());
+        assert_eq!(encode(b""), b"0\r\n\r\n".to_vec());
+        let mut rest: &[u8] = b"";
+        let (body, _) = decode(encode(b"abc").as_slice(), &mut rest).unwrap();
+        assert_eq!(body, b"abc".to_vec());
+    }
+}
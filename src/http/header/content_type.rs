@@ -174,6 +174,44 @@ impl ContentType {
         }
     }
 
+    /// 根据文件扩展名推测数据类型，扩展名不区分大小写，无法识别时返回"application/octet-stream"
+    ///
+    /// * ext 文件扩展名，不包括"."，如`html`
+    pub fn from_extension(ext: &str) -> ContentType {
+        match ext.to_lowercase().as_str() {
+            "html" | "htm" => ContentType::TEXT_HTML,
+            "txt" => ContentType::TEXT_PLAIN,
+            "xml" => ContentType::TEXT_XML,
+            "gif" => ContentType::IMAGE_GIF,
+            "jpg" | "jpeg" => ContentType::IMAGE_JPEG,
+            "png" => ContentType::IMAGE_PNG,
+            "xhtml" => ContentType::APPLICATION_XHTML_XML,
+            "atom" => ContentType::APPLICATION_ATOM_XML,
+            "json" => ContentType::APPLICATION_JSON,
+            "pdf" => ContentType::APPLICATION_PDF,
+            "doc" => ContentType::APPLICATION_MS_WORD,
+            "css" => ContentType::custom_str("text/css"),
+            "csv" => ContentType::custom_str("text/csv"),
+            "md" => ContentType::custom_str("text/markdown"),
+            "js" | "mjs" => ContentType::custom_str("application/javascript"),
+            "wasm" => ContentType::custom_str("application/wasm"),
+            "zip" => ContentType::custom_str("application/zip"),
+            "gz" => ContentType::custom_str("application/gzip"),
+            "svg" => ContentType::custom_str("image/svg+xml"),
+            "ico" => ContentType::custom_str("image/x-icon"),
+            "webp" => ContentType::custom_str("image/webp"),
+            "bmp" => ContentType::custom_str("image/bmp"),
+            "woff" => ContentType::custom_str("font/woff"),
+            "woff2" => ContentType::custom_str("font/woff2"),
+            "ttf" => ContentType::custom_str("font/ttf"),
+            "mp3" => ContentType::custom_str("audio/mpeg"),
+            "wav" => ContentType::custom_str("audio/wav"),
+            "mp4" => ContentType::custom_str("video/mp4"),
+            "webm" => ContentType::custom_str("video/webm"),
+            _ => ContentType::default()
+        }
+    }
+
     pub fn inner(&self) -> Inner {
         self.0.clone()
     }
@@ -244,4 +282,12 @@ mod content_type_test {
         assert_eq!("text/html", ct1.as_str());
         assert_eq!("abc/xyz", ct2.as_str());
     }
+
+    #[test]
+    fn from_extension() {
+        assert_eq!(ContentType::from_extension("HTML"), ContentType::TEXT_HTML);
+        assert_eq!(ContentType::from_extension("jpg"), ContentType::IMAGE_JPEG);
+        assert_eq!("text/css", ContentType::from_extension("css").as_str());
+        assert_eq!(ContentType::from_extension("unknown"), ContentType::APPLICATION_OCTET_STREAM);
+    }
 }
\ No newline at end of file
@@ -22,7 +22,7 @@ pub struct Cookie {
     /// cookie的值
     pub(crate) value: String,
     /// 可以访问此cookie的页面路径
-    path: Option<String>,
+    pub(crate) path: Option<String>,
     /// 可以访问此cookie的域名
     ///
     /// domain属性可以使多个web服务器共享cookie。
@@ -44,7 +44,7 @@ pub struct Cookie {
     /// 所以要想cookie在多个二级域名中共享，需要设置domain为顶级域名，这样就可以在所有二级域名里面或者到这个cookie的值了。
     ///
     /// 顶级域名只能获取到domain设置为顶级域名的cookie，其他domain设置为二级域名的无法获取。
-    domain: Option<String>,
+    pub(crate) domain: Option<String>,
     /// 此cookie超时时间
     ///
     /// 指定了cookie的生存期，默认情况下cookie是暂时存在的，他们存储的值只在浏览器会话期间存在，当用户退出浏览器
@@ -52,7 +52,7 @@ pub struct Cookie {
     /// 时间点，expires默认为设置的expires的当前时间。
     ///
     /// 现在已经被max-age属性所取代，max-age用秒来设置cookie的生存期。
-    expires: Option<Time>,
+    pub(crate) expires: Option<Time>,
     /// 设置cookie的生存期
     ///
     /// max-age属性为正数，则表示该cookie会在max-age秒之后自动失效。
@@ -68,15 +68,15 @@ pub struct Cookie {
     /// 失效的Cookie会被浏览器从cookie文件或者内存中删除。
     ///
     /// 如果不设置expires或者max-age这个cookie默认是Session的，也就是关闭浏览器该cookie就消失了。
-    max_age: Option<isize>,
+    pub(crate) max_age: Option<isize>,
     /// 设置是否只能通过https来传递此条cookie
     ///
     /// 它是一个布尔值，指定在网络上如何传输cookie，默认是不安全的，通过一个普通的http连接传输。
-    secure: bool,
+    pub(crate) secure: bool,
     /// 若此属性为true，则只有在http请求头中会带有此cookie的信息，而不能通过document.cookie来访问此cookie。
     ///
     /// 这意味着，浏览器脚本，比如javascript中，是不允许访问操作此cookie的。
-    httponly: bool,
+    pub(crate) httponly: bool,
     /// SameSite属性用于限制第三方Cookie，从而降低安全风险，减少`CSRF`攻击
     same_site: Option<SameSite>,
 }
@@ -120,13 +120,19 @@ impl Cookie {
                 for s in src {
                     let mut cookie = Cookie::default();
                     let res = s.trim().split(";");
-                    for v in res {
-                        let parts: Vec<&str> = v.trim().split("=").collect();
+                    for (i, v) in res.enumerate() {
+                        // cookie的值中可能包含"="，如base64编码后的值，仅以第一个"="分隔
+                        let parts: Vec<&str> = v.trim().splitn(2, "=").collect();
                         let parts_len = parts.len();
-                        if parts_len > 2 {
-                            return Err(Errs::string(format!("cookie's value invalid! can not support {}", v)));
+                        let key = parts[0].trim().to_string();
+                        // 首段固定为cookie的名称和值，即使名称与属性名相同
+                        if i == 0 {
+                            if parts_len == 2 {
+                                cookie.name = key;
+                                cookie.value = parts[1].trim().to_string();
+                            }
+                            continue;
                         }
-                        let key = parts[0].to_string();
                         match key.to_lowercase().as_str() {
                             "path" => cookie.path = if parts_len == 2 {
                                 Some(parts[1].to_string())
@@ -166,12 +172,8 @@ impl Cookie {
                             } else {
                                 None
                             },
-                            _ => if parts_len == 2 {
-                                cookie.name = key;
-                                cookie.value = parts[1].to_string();
-                            } else {
-                                continue;
-                            }
+                            // 忽略无法识别的属性
+                            _ => continue
                         }
                     }
                     cookies.push(cookie)
@@ -41,10 +41,11 @@ impl AcceptEncoding {
         res
     }
 
-    // pub(crate) fn support_str() -> &'static str {
-    //     "gzip, deflate, zlib"
-    // }
-    //
+    /// 客户端可解压的编码方式，用于"Accept-Encoding"请求头
+    pub(crate) fn support_str() -> &'static str {
+        "gzip, deflate, zlib"
+    }
+
     // pub(crate) fn support() -> String {
     //     AcceptEncoding::support_str().to_string()
     // }
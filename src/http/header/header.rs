@@ -47,6 +47,30 @@ impl Header {
         self.del("Content-Length")
     }
 
+    /// 数据体是否使用chunked传输编码，即`Transfer-Encoding`的最后一个编码为chunked
+    ///
+    /// 上游服务返回的键名大小写不一，因此忽略大小写进行匹配
+    pub(crate) fn is_chunked(&self) -> bool {
+        for (key, values) in self.map() {
+            if key.eq_ignore_ascii_case("Transfer-Encoding") {
+                return match values.last() {
+                    Some(src) => match src.rsplit(',').next() {
+                        Some(last) => last.trim().eq_ignore_ascii_case("chunked"),
+                        None => false
+                    },
+                    None => false
+                };
+            }
+        }
+        false
+    }
+
+    /// 设置数据体使用chunked传输编码，与"Content-Length"互斥
+    pub(crate) fn set_chunked(&mut self) {
+        self.del_content_length();
+        self.set_str("Transfer-Encoding", "chunked")
+    }
+
     pub(crate) fn get_userinfo(&self) -> StarryResult<Option<Userinfo>> {
         match self.get("Authorization") {
             Some(src) => match Userinfo::from_basic(src.to_string()) {
@@ -77,9 +101,9 @@ impl Header {
         self.del("Content-Type")
     }
 
-    pub(crate) fn set_accept_encoding(&mut self, encode_type: AcceptEncoding) {
+    pub(crate) fn set_content_encoding(&mut self, encode_type: AcceptEncoding) {
         if encode_type.ne("") && encode_type.ne("br") {
-            self.set("Accept-Encoding".to_string(), encode_type.to_string())
+            self.set("Content-Encoding".to_string(), encode_type.to_string())
         }
     }
 
@@ -90,6 +114,44 @@ impl Header {
         }
     }
 
+    /// 声明客户端可解压的编码方式，已设置时不覆盖
+    pub(crate) fn set_accept_encoding(&mut self) {
+        if self.map().keys().all(|k| !k.eq_ignore_ascii_case("Accept-Encoding")) {
+            self.set_str("Accept-Encoding", AcceptEncoding::support_str())
+        }
+    }
+
+    /// 数据体的编码方式，未设置或无法识别时返回None
+    pub(crate) fn get_content_encoding(&self) -> Option<AcceptEncoding> {
+        for (k, v) in self.map() {
+            if k.eq_ignore_ascii_case("Content-Encoding") {
+                return match v.first().map(|src| AcceptEncoding::from(src.trim().to_ascii_lowercase())) {
+                    Some(AcceptEncoding::None) | None => None,
+                    src => src
+                };
+            }
+        }
+        None
+    }
+
+    /// 忽略键名大小写获取第一个值
+    pub(crate) fn get_ignore_case(&self, key: &str) -> Option<String> {
+        for (k, v) in self.map() {
+            if k.eq_ignore_ascii_case(key) {
+                return v.first().cloned();
+            }
+        }
+        None
+    }
+
+    /// 以逗号分隔的值中是否包含指定标记，键名及标记均忽略大小写，如"Connection: keep-alive, Upgrade"包含"upgrade"
+    pub(crate) fn contains_token(&self, key: &str, token: &str) -> bool {
+        self.map().iter()
+            .filter(|(k, _)| k.eq_ignore_ascii_case(key))
+            .flat_map(|(_, v)| v.iter())
+            .any(|src| src.split(',').any(|item| item.trim().eq_ignore_ascii_case(token)))
+    }
+
     // pub(crate) fn del_accept_encoding(&mut self) -> Option<String> {
     //     self.del("Accept-Encoding")
     // }
@@ -22,4 +22,7 @@ pub mod status;
 pub mod body;
 pub mod header;
 pub mod requester;
-pub mod responser;
\ No newline at end of file
+pub mod responser;
+pub(crate) mod stream;
+pub(crate) mod chunked;
+pub(crate) mod multipart;
\ No newline at end of file
@@ -0,0 +1,161 @@
+/*
+ * Copyright (c) 2021. Aberic - All Rights Reserved.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use crate::http::values::MultipartValues;
+use crate::Values;
+
+/// 待发送的"multipart/form-data"数据体
+///
+/// 由内存数据及磁盘文件依次组成，磁盘文件在发送时才打开并边读取边写入，因此请求被重试或重定向时可以再次发送
+#[derive(Clone, Debug, Default)]
+pub(crate) struct MultipartBody {
+    segments: Vec<Segment>,
+}
+
+#[derive(Clone, Debug)]
+enum Segment {
+    Data(Vec<u8>),
+    /// 文件路径及编码时的文件大小
+    File(PathBuf, u64),
+}
+
+impl MultipartBody {
+    /// 将表单参数及附件编码为数据体，表单参数在前，附件在后
+    ///
+    /// * boundary 分隔符
+    pub(crate) fn encode(boundary: &str, form: &Values, files: &MultipartValues) -> MultipartBody {
+        let mut body = MultipartBody::default();
+        for (key, values) in form.map() {
+            for value in values {
+                body.data(format!("--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n", boundary, quote(&key)).as_bytes());
+                body.data(value.as_bytes());
+                body.data(b"\r\n");
+            }
+        }
+        for (name, file) in files.map() {
+            body.data(format!("--{}\r\nContent-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\nContent-Type: {}\r\n\r\n",
+                              boundary, quote(&name), quote(&file.filename()), file.content_type().as_str()).as_bytes());
+            match file.path() {
+                Some(path) => body.segments.push(Segment::File(path, file.size() as u64)),
+                None => body.data(file.content().as_slice())
+            }
+            body.data(b"\r\n");
+        }
+        body.data(format!("--{}--\r\n", boundary).as_bytes());
+        body
+    }
+
+    /// 追加内存数据，与前一段内存数据合并
+    fn data(&mut self, src: &[u8]) {
+        match self.segments.last_mut() {
+            Some(Segment::Data(data)) => data.extend_from_slice(src),
+            _ => self.segments.push(Segment::Data(src.to_vec()))
+        }
+    }
+
+    /// 数据体总长度
+    pub(crate) fn len(&self) -> u64 {
+        self.segments.iter().map(|segment| match segment {
+            Segment::Data(data) => data.len() as u64,
+            Segment::File(_, size) => *size
+        }).sum()
+    }
+
+    /// 不包含磁盘文件时，返回完整的内存数据
+    pub(crate) fn into_bytes(self) -> Result<Vec<u8>, MultipartBody> {
+        if self.segments.iter().all(|segment| match segment {
+            Segment::Data(_) => true,
+            Segment::File(_, _) => false
+        }) {
+            Ok(self.segments.into_iter().flat_map(|segment| match segment {
+                Segment::Data(data) => data,
+                Segment::File(_, _) => vec![]
+            }).collect())
+        } else {
+            Err(self)
+        }
+    }
+
+    /// 依次写入各段数据，文件大小与编码时不一致时返回错误，避免与已声明的"Content-Length"不符
+    pub(crate) fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        for segment in &self.segments {
+            match segment {
+                Segment::Data(data) => writer.write_all(data)?,
+                Segment::File(path, size) => {
+                    let file = File::open(path)?;
+                    let copied = io::copy(&mut file.take(*size + 1), writer)?;
+                    if copied != *size {
+                        return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                                  format!("file {} size changed from {} to {}", path.display(), size, copied)));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 转义字段名及文件名中的引号及换行，防止破坏"Content-Disposition"结构
+fn quote(src: &str) -> String {
+    src.replace('"', "%22").replace('\r', "%0D").replace('\n', "%0A")
+}
+
+#[cfg(test)]
+mod multipart_test {
+    use std::env;
+    use std::fs;
+
+    use crate::{MultipartValues, Values};
+    use crate::header::ContentType;
+    use crate::http::multipart::MultipartBody;
+
+    #[test]
+    fn encode() {
+        let mut form = Values::new();
+        form.set("a\"b".to_string(), "1".to_string());
+        let mut files = MultipartValues::new();
+        files.insert_obj("f".to_string(), "a\r\n.txt".to_string(), b"abc".to_vec(), ContentType::TEXT_PLAIN);
+        let body = MultipartBody::encode("xyz", &form, &files);
+        let len = body.len();
+        let data = body.into_bytes().unwrap();
+        assert_eq!(len, data.len() as u64);
+        assert_eq!(String::from_utf8(data).unwrap(), "--xyz\r\nContent-Disposition: form-data; name=\"a%22b\"\r\n\r\n1\r\n\
+        --xyz\r\nContent-Disposition: form-data; name=\"f\"; filename=\"a%0D%0A.txt\"\r\nContent-Type: text/plain\r\n\r\nabc\r\n--xyz--\r\n");
+    }
+
+    #[test]
+    fn encode_file() {
+        let path = env::temp_dir().join("starry_multipart_encode_file.txt");
+        fs::write(&path, b"hello file").unwrap();
+        let mut files = MultipartValues::new();
+        files.insert_path("f".to_string(), path.to_str().unwrap()).unwrap();
+        let body = MultipartBody::encode("xyz", &Values::new(), &files);
+        let body = body.into_bytes().unwrap_err();
+        let mut data = vec![];
+        body.write_to(&mut data).unwrap();
+        assert_eq!(body.len(), data.len() as u64);
+        assert_eq!(String::from_utf8(data).unwrap(), "--xyz\r\nContent-Disposition: form-data; name=\"f\"; \
+        filename=\"starry_multipart_encode_file.txt\"\r\nContent-Type: text/plain\r\n\r\nhello file\r\n--xyz--\r\n");
+
+        // 文件大小发生变化时写入失败
+        fs::write(&path, b"hello").unwrap();
+        assert!(body.write_to(&mut vec![]).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
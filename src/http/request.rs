@@ -18,17 +18,22 @@ use std::hash::Hash;
 use std::net::SocketAddr;
 
 use bytes::{Bytes, BytesMut};
+use openssl::error::ErrorStack;
+use openssl::rand::rand_bytes;
 
 use crate::{Method, URL, Values, Version};
 use crate::header::AcceptEncoding;
 use crate::http::body::Body;
 use crate::http::header::{ContentType, Cookie};
 use crate::http::header::Header;
+use crate::http::multipart::MultipartBody;
 use crate::http::url::{Authority, Location, Scheme};
 use crate::http::url::authority::{Addr, Userinfo};
 use crate::http::values::MultipartValues;
 use crate::http::version::Protocol;
-use crate::utils::errors::StarryResult;
+use crate::utils::cryptos::Hex;
+use crate::utils::cryptos::hex::HexEncoder;
+use crate::utils::errors::{Errs, StarryResult};
 
 // use crate::values::{MultipartValues, RequestValues};
 
@@ -127,6 +132,12 @@ pub struct Request {
     pub(crate) multipart_form: MultipartValues,
     pub(crate) cookies: Vec<Cookie>,
     pub(crate) client: Addr,
+    /// 客户端请求行是否使用绝对形式（absolute-form），即"GET http://host:port/path HTTP/1.1"，经http代理转发时使用
+    pub(crate) absolute_form: bool,
+    /// 包含磁盘文件的"multipart/form-data"数据体，发送时边读取文件边写入，此时[`body`]为空
+    ///
+    /// [`body`]: crate::http::request::Request::body
+    pub(crate) multipart_body: Option<MultipartBody>,
 }
 
 impl Request {
@@ -152,6 +163,8 @@ impl Request {
             multipart_form: MultipartValues::new(),
             cookies: vec![],
             client: Default::default(),
+            absolute_form: false,
+            multipart_body: None,
         })
     }
 
@@ -260,6 +273,12 @@ impl Request {
         self.multipart_form.insert_obj(name, filename, content, content_type);
     }
 
+    /// 添加磁盘文件附件，文件内容在发送时才从磁盘读取
+    pub fn multipart_form_file(&mut self, name: String, path: &str) -> StarryResult<()> {
+        self.multipart_form.insert_path(name, path)?;
+        Ok(())
+    }
+
     /// 返回对应于URI请求参数中定义参数值的引用。
     pub fn param_value<K: ?Sized>(&self, k: &K) -> Option<String> where
         K: Borrow<K>,
@@ -345,6 +364,69 @@ impl Request {
     pub(crate) fn get_write_content(&mut self) -> Bytes {
         self.body.get_write_content()
     }
+
+    /// 写入即将发送的数据体，并设置数据类型
+    pub(crate) fn write_body(&mut self, body: Vec<u8>, content_type: ContentType) {
+        self.header.set_str("Content-Type", content_type.as_str());
+        self.content_type = Some(content_type);
+        self.body.write(body)
+    }
+
+    /// 将表单数据编码为即将发送的数据体，已写入数据体或没有表单数据时不做处理
+    ///
+    /// 存在附件时编码为"multipart/form-data"，表单参数作为普通字段写入，否则编码为"application/x-www-form-urlencoded"
+    ///
+    /// 附件包含磁盘文件时，数据体在发送时才边读取文件边写入，此处仅根据文件大小声明"Content-Length"
+    pub(crate) fn encode_form(&mut self) -> StarryResult<()> {
+        if !self.body.writer.is_empty() || self.multipart_body.is_some() {
+            return Ok(());
+        }
+        if self.multipart_form.len() > 0 {
+            let boundary = match boundary() {
+                Ok(src) => src,
+                Err(err) => return Err(Errs::strs("request multipart boundary generate failed!", err))
+            };
+            let body = MultipartBody::encode(&boundary, &self.form, &self.multipart_form);
+            self.header.set("Content-Type".to_string(), format!("multipart/form-data; boundary={}", boundary));
+            self.content_type = Some(ContentType::multipart_form_data(boundary));
+            match body.into_bytes() {
+                Ok(src) => self.body.write(src),
+                Err(src) => {
+                    self.header.set("Content-Length".to_string(), src.len().to_string());
+                    self.multipart_body = Some(src)
+                }
+            }
+        } else if self.form.len() > 0 {
+            let mut pairs = vec![];
+            for (key, values) in self.form.map() {
+                for value in values {
+                    pairs.push(format!("{}={}", url_encode(&key), url_encode(&value)))
+                }
+            }
+            self.write_body(pairs.join("&").into_bytes(), ContentType::APPLICATION_X_WWW_FORM_URL_ENCODED)
+        }
+        Ok(())
+    }
+}
+
+/// 生成multipart/form-data的分隔符
+fn boundary() -> Result<String, ErrorStack> {
+    let mut buf = [0; 12];
+    rand_bytes(&mut buf)?;
+    Ok(format!("------------------------{}", Hex::encode(&buf[..])))
+}
+
+/// 按照"application/x-www-form-urlencoded"规则对字符串进行百分号编码
+fn url_encode(src: &str) -> String {
+    let mut res = String::with_capacity(src.len());
+    for b in src.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'*' => res.push(b as char),
+            b' ' => res.push('+'),
+            _ => res.push_str(&format!("%{:02X}", b))
+        }
+    }
+    res
 }
 
 impl Default for Request {
@@ -365,6 +447,8 @@ impl Default for Request {
             multipart_form: MultipartValues::new(),
             cookies: vec![],
             client: Default::default(),
+            absolute_form: false,
+            multipart_body: None,
         }
     }
 }
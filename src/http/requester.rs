@@ -16,14 +16,16 @@ use std::borrow::{Borrow, BorrowMut};
 use std::collections::HashMap;
 use std::fmt::Debug;
 use std::hash::Hash;
-use std::io::{Read, Write};
+use std::io::{ErrorKind, Read, Write};
 use std::slice::Iter;
 use std::sync::{Arc, RwLock};
 
-use bytes::{BufMut, BytesMut};
-use bytes::buf::Writer;
+use bytes::BytesMut;
 
-use crate::{Header, Inner, Method, MultipartValues, Request, Response, URL, Values, Version};
+use crate::{Header, Inner, Method, MultipartValues, Request, Response, Status, URL, Values, Version};
+use crate::http::body::BodyStream;
+use crate::http::chunked;
+use crate::http::chunked::ChunkedDecoder;
 use crate::http::header::{AcceptEncoding, ContentType, Cookie};
 use crate::http::url::{Authority, Location, Scheme};
 use crate::http::url::authority::{Addr, Userinfo};
@@ -41,20 +43,55 @@ pub(crate) const SERVER_TCP_STREAM_HAD_NO_DATA: &str = "server request tcp strea
 pub struct Requester<Stream: Read + Write + Debug> {
     pub(crate) request: Request,
     pub(crate) stream: Stream,
+    /// 请求正文读取状态，正文在被使用时才从流中读取
+    body_state: BodyState,
+    /// 请求正文允许的最大长度，0表示不限制
+    body_limit: u64,
+    /// 已读取的请求正文长度
+    body_read: u64,
+    /// 解析报头时已读取但不属于请求正文的数据，如客户端随websocket握手一同发送的帧
+    pub(crate) surplus: Vec<u8>,
 }
 
+/// 请求正文读取状态
+#[derive(Debug)]
+enum BodyState {
+    /// 请求没有正文，或正文已读取完毕
+    None,
+    /// 通过"Content-Length"声明长度的正文
+    ///
+    /// * pre 解析报头时已读取的正文部分
+    /// * pos pre中已读取位置
+    /// * remaining 剩余待读取长度
+    Length { pre: Vec<u8>, pos: usize, remaining: u64 },
+    /// chunked编码的正文
+    Chunked(ChunkedDecoder),
+}
+
+/// 单次回复结束后，未被读取的请求正文超过该长度时不再读取丢弃，而是直接关闭连接
+const BODY_DRAIN_MAX: u64 = 64 * 1024;
+
 impl<Stream: Read + Write + Debug> Requester<Stream> {
     /// 通过写入流获取来自客户端的Request
     ///
+    /// 该过程只解析请求行及报头，请求正文在被使用时才从流中读取
+    ///
     /// * root 资源树根结点
+    /// * scheme 当前连接所使用的协议
     /// * peer 客户端地址信息
     /// * local 本机地址信息
-    pub(crate) fn from(stream: Stream, root: Arc<RwLock<Root>>, peer: Addr, local: Addr) -> StarryResult<(Self, Node, HashMap<String, String>)> {
+    /// * body_limit 请求正文允许的最大长度，0表示不限制
+    pub(crate) fn from(stream: Stream, root: Arc<RwLock<Root>>, scheme: Scheme, peer: Addr, local: Addr, body_limit: u64)
+                       -> StarryResult<(Self, Node, HashMap<String, String>)> {
         let mut req = Requester {
             request: Default::default(),
             stream,
+            body_state: BodyState::None,
+            body_limit,
+            body_read: 0,
+            surplus: vec![],
         };
-        let (node, fields) = req.parse(root, peer, local)?;
+        let (node, fields) = req.parse(root, scheme, peer, local)?;
         Ok((req, node, fields))
     }
 }
@@ -144,10 +181,32 @@ impl<Stream: Read + Write + Debug> Requester<Stream> {
         None
     }
 
+    /// 获取请求正文，尚未读取的正文会先从流中全部读出
+    ///
+    /// 读取失败时返回已读取的部分，如需获取错误信息，请使用[`body_all`]
+    ///
+    /// [`body_all`]: crate::Requester::body_all
     pub fn body(&mut self) -> Vec<u8> {
+        match self.load_body() {
+            Ok(()) => {}
+            Err(err) => log::error!("request body load failed! {}", err)
+        }
         self.request.body()
     }
 
+    /// 获取请求正文，尚未读取的正文会先从流中全部读出
+    pub fn body_all(&mut self) -> StarryResult<Vec<u8>> {
+        self.load_body()?;
+        Ok(self.request.body())
+    }
+
+    /// 请求正文读取器，读取时才从流中获取数据，适用于大数据上传等无需将正文全部读入内存的场景
+    ///
+    /// 读取过程中检查正文长度限制，超出时返回错误，且本次请求结束后关闭连接
+    pub fn body_reader(&mut self) -> BodyReader<'_, Stream> {
+        BodyReader { requester: self }
+    }
+
     pub fn form(&mut self) -> StarryResult<Values> {
         if !self.request.body.is_parse {
             self.parse_body()?;
@@ -237,13 +296,13 @@ impl<Stream: Read + Write + Debug> Requester<Stream> {
 
 impl<Stream: Read + Write + Debug> Requester<Stream> {
     /// 解析请求行信息
-    fn parse(&mut self, root: Arc<RwLock<Root>>, peer: Addr, local: Addr) -> StarryResult<(Node, HashMap<String, String>)> {
+    fn parse(&mut self, root: Arc<RwLock<Root>>, scheme: Scheme, peer: Addr, local: Addr) -> StarryResult<(Node, HashMap<String, String>)> {
         let mut buffer = [0; 1024];
         let mut iter;
         // 当前读取总长度
         let mut count = 0;
-        // 剩余待读取数据的总长度
-        let mut size;
+        // 已读取数据的总长度
+        let size;
         match self.stream.read(&mut buffer) {
             Ok(src) => {
                 log::trace!("request stream read size = {}", src);
@@ -284,19 +343,15 @@ impl<Stream: Read + Write + Debug> Requester<Stream> {
         count += len;
 
         // 根据已知结果解析请求关联参数
-        self.parse_others(location, peer, local)?;
+        self.parse_others(location, scheme, peer, local)?;
 
-        if size < count {
-            return Ok((node, fields))
-        }
-        size -= count;
+        // 报头之后已读取的部分属于请求正文
+        let pre = if size < count { &[] } else { &iter.as_ref()[0..size - count] };
 
-        // 读取请求正文
-        // 当请求方法为 POST/PUT/PATCH 时需要读取到body中，其它方法没有实体，即便有，也会被丢弃掉
-        // 该方法不会解析body内容，body内容解析根据实际情况进行
-        // 当用户使用到form数据等情况时，会解析，解析后，body内数据会被清空
+        // 初始化请求正文读取状态，请求正文在被使用时才从流中读取
+        // 当用户使用到form数据等情况时，会读取并解析，解析后，body内数据会被清空
         // 用户也可以主动使用body数据，但用户使用后，解析不会再自动进行
-        self.fill_body(iter.borrow_mut(), size)?;
+        self.init_body(pre)?;
 
         // log::trace!("body = {}", self.body.to_string());
         // self.parse_body()?;
@@ -331,9 +386,10 @@ impl<Stream: Read + Write + Debug> Requester<Stream> {
                                 }
                             }
                             2 => { // /path/test/test1/hello/world?key=value&key2=value2\r
-                                data.push(b'\r');
-                                log::trace!("location = {}", String::from_utf8_lossy(data.as_slice()));
-                                location = Location::from_bytes(data.clone());
+                                let mut target = origin_form(data.as_slice());
+                                target.push(b'\r');
+                                log::trace!("location = {}", String::from_utf8_lossy(target.as_slice()));
+                                location = Location::from_bytes(target);
                                 for (key, value) in location.query().map() {
                                     self.request.set_form_param(key, value[0].clone());
                                 }
@@ -426,7 +482,7 @@ impl<Stream: Read + Write + Debug> Requester<Stream> {
     }
 
     /// 根据已知结果解析请求关联参数
-    pub(crate) fn parse_others(&mut self, location: Location, peer: Addr, local: Addr) -> StarryResult<()> {
+    pub(crate) fn parse_others(&mut self, location: Location, scheme: Scheme, peer: Addr, local: Addr) -> StarryResult<()> {
         let userinfo;
         match self.request.header.get_userinfo() {
             Ok(src) => userinfo = src,
@@ -435,7 +491,7 @@ impl<Stream: Read + Write + Debug> Requester<Stream> {
                 Errs::strs("parse request failed, userinfo parse error!", err)))
         }
         self.request.set_client(peer);
-        self.request.set_url(URL::new(Scheme::HTTP, Authority::new(userinfo, local), location));
+        self.request.set_url(URL::new(scheme, Authority::new(userinfo, local), location));
         self.request.close = self.request.header.check_close(&self.request.version, false);
         match self.request.version() {
             Version::HTTP_10 | Version::HTTP_11 => match self.request.header.get_host() {
@@ -469,92 +525,165 @@ impl<Stream: Read + Write + Debug> Requester<Stream> {
         Ok(())
     }
 
-    fn fill_body(&mut self, iter: &mut Iter<u8>, mut size: usize) -> StarryResult<()> {
-        match self.method() {
-            Method::PATCH | Method::PUT | Method::POST => {
-                let mut bytes = vec![];
-                loop {
-                    match iter.next() {
-                        Some(b) => {
-                            match b {
-                                b'\r' | b'\n' => size -= 1,
-                                _ => {
-                                    bytes.push(*b);
-                                    size -= 1;
-                                    break;
-                                }
-                            }
+    /// 根据报头初始化请求正文读取状态
+    ///
+    /// 支持chunked编码及"Content-Length"，两者都不存在时视为没有正文，
+    /// 此时以及超出"Content-Length"的已读取数据保留在`surplus`中
+    ///
+    /// * pre 解析报头时已读取的正文部分
+    pub(crate) fn init_body(&mut self, pre: &[u8]) -> StarryResult<()> {
+        if self.request.header.is_chunked() {
+            self.body_state = BodyState::Chunked(ChunkedDecoder::new(pre.to_vec()));
+            return Ok(());
+        }
+        match self.request.header.get_content_length() {
+            Some(content_len) => match content_len.trim().parse::<u64>() {
+                Ok(len) => {
+                    if self.body_limit > 0 && len > self.body_limit {
+                        return Err(self.interrupt(
+                            Response::custom(Status::PAYLOAD_TOO_LARGE),
+                            Errs::string(format!("content len {} exceeds body limit {}!", len, self.body_limit))));
+                    }
+                    self.request.set_content_length(len as isize);
+                    let (pre, surplus) = pre.split_at(pre.len().min(len as usize));
+                    self.surplus = surplus.to_vec();
+                    if len > 0 {
+                        self.body_state = BodyState::Length { pre: pre.to_vec(), pos: 0, remaining: len };
+                    }
+                    Ok(())
+                }
+                Err(err) => Err(self.interrupt(
+                    Response::length_required(),
+                    Errs::strings(format!("content len {} parse usize from header failed!", content_len), err)))
+            },
+            None => {
+                self.request.set_content_length(0);
+                self.surplus = pre.to_vec();
+                Ok(())
+            }
+        }
+    }
+
+    /// 从流中读取请求正文，正文读取完毕时返回0
+    ///
+    /// 读取失败或超出正文长度限制时，本次请求结束后关闭连接
+    fn read_body(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let res = match &mut self.body_state {
+            BodyState::None => return Ok(0),
+            BodyState::Length { pre, pos, remaining } => {
+                let max = (buf.len() as u64).min(*remaining) as usize;
+                if max == 0 {
+                    Ok(0)
+                } else if *pos < pre.len() {
+                    let size = max.min(pre.len() - *pos);
+                    buf[0..size].copy_from_slice(&pre[*pos..*pos + size]);
+                    *pos += size;
+                    *remaining -= size as u64;
+                    Ok(size)
+                } else {
+                    match self.stream.read(&mut buf[0..max]) {
+                        Ok(0) => Err(std::io::Error::new(ErrorKind::UnexpectedEof, "request body unexpected eof!")),
+                        Ok(size) => {
+                            *remaining -= size as u64;
+                            Ok(size)
                         }
-                        None => return Ok(())
+                        Err(err) => Err(err)
                     }
                 }
-                // iter.next(); // 请求，过滤换行符
-                // size -= 1;
-
-                match self.request.header().get_content_length() {
-                    Some(content_len) => match content_len.parse::<isize>() {
-                        Ok(mut len) => {
-                            if len > 0 { // 读取到"Content-Length"大于0
-                                // 因为前面内容多读取了一个字节，所以len = len - 1
-                                len = len - 1;
-                                // Body数据存在，新建写流，将body数据进行填充
-                                let mut bw = BytesMut::new().writer();
-                                self.write_bytes(bw.borrow_mut(), bytes.as_slice())?;
-                                self.write_bytes(bw.borrow_mut(), &iter.as_ref()[0..size])?;
-                                let mut buffer = [0; 1024];
-                                let mut count = size;
-                                loop { // 将stream中数据全数读出到 self.body
-                                    if count as isize == len || size == 0 {
-                                        break;
-                                    }
-                                    size = self.reread_stream(&mut buffer)?;
-                                    self.write_bytes(bw.borrow_mut(), &buffer[0..size])?;
-                                    count += size
-                                }
-                                self.request.set_body(bw.into_inner());
-                            } else if len == 0 {  // 读取到"Content-Length"等于0
-                                return Ok(());
-                            } else if len == -1 {  // 读取到"Content-Length"小于0
-                                // Body数据存在，长度未知，新建写流，将body数据进行填充
-                                let mut bw = BytesMut::new().writer();
-                                self.write_bytes(bw.borrow_mut(), bytes.as_slice())?;
-                                self.write_bytes(bw.borrow_mut(), &iter.as_ref()[0..size])?;
-                                let mut buf_all = Vec::new();
-                                match self.stream.read_to_end(&mut buf_all) {
-                                    Ok(_) => {
-                                        // bw.write(buf_all.as_slice());
-                                        self.write_bytes(bw.borrow_mut(), buf_all.as_slice())?;
-                                        self.request.set_body(bw.into_inner());
-                                    }
-                                    Err(err) => return Err(self.interrupt(
-                                        Response::length_required(),
-                                        Errs::strs("parse request body while read_to_end failed!", err)))
-                                }
-                            } else {
-                                return Err(self.interrupt(
-                                    Response::length_required(),
-                                    Errs::string(format!("content len {} invalid from header failed!", len))));
-                            }
-                            self.request.set_content_length(len);
-                            Ok(())
+            }
+            BodyState::Chunked(decoder) => decoder.read(&mut self.stream, buf),
+        };
+        match res {
+            Ok(0) => {
+                if let BodyState::Chunked(decoder) = &mut self.body_state {
+                    // trailer同样计入正文长度限制
+                    self.body_read += decoder.trailers_len as u64;
+                    if self.body_limit > 0 && self.body_read > self.body_limit {
+                        self.body_state = BodyState::None;
+                        self.request.close = true;
+                        return Err(std::io::Error::new(ErrorKind::InvalidData,
+                                                       format!("request body exceeds limit {}!", self.body_limit)));
+                    }
+                    // 分帧、路由、认证等字段不允许通过trailer设置，直接丢弃
+                    for (key, value) in decoder.trailers.drain(..) {
+                        if chunked::forbidden_trailer(&key) {
+                            log::debug!("request chunked trailer {} is forbidden, discard!", key);
+                        } else {
+                            self.request.header.add(key, value)
                         }
-                        Err(err) => return Err(self.interrupt(
-                            Response::length_required(),
-                            Errs::strings(format!("content len {} parse usize from header failed!", content_len), err)))
-                    },
-                    None => {
-                        self.request.set_content_length(0);
-                        return Ok(());
                     }
                 }
+                self.body_state = BodyState::None;
+                Ok(0)
+            }
+            Ok(size) => {
+                self.body_read += size as u64;
+                if self.body_limit > 0 && self.body_read > self.body_limit {
+                    self.body_state = BodyState::None;
+                    self.request.close = true;
+                    return Err(std::io::Error::new(ErrorKind::InvalidData,
+                                                   format!("request body exceeds limit {}!", self.body_limit)));
+                }
+                Ok(size)
+            }
+            Err(err) => {
+                self.body_state = BodyState::None;
+                self.request.close = true;
+                Err(err)
+            }
+        }
+    }
+
+    /// 将尚未读取的请求正文全部读出，供body及表单解析使用
+    fn load_body(&mut self) -> StarryResult<()> {
+        if let BodyState::None = self.body_state {
+            return Ok(());
+        }
+        let mut body = self.request.body();
+        let mut buffer = [0; 8192];
+        loop {
+            match self.read_body(&mut buffer) {
+                Ok(0) => break,
+                Ok(size) => body.extend_from_slice(&buffer[0..size]),
+                Err(err) => return Err(Errs::strs("read request body failed!", err))
+            }
+        }
+        self.request.set_body(BytesMut::from(body.as_slice()));
+        Ok(())
+    }
+
+    /// 丢弃本次请求中未被读取的正文，以便连接可以继续处理下一个请求
+    ///
+    /// 未读取的正文过长或读取失败时，本次请求结束后关闭连接
+    pub(crate) fn drain_body(&mut self) {
+        let mut buffer = [0; 8192];
+        let mut count: u64 = 0;
+        loop {
+            if let BodyState::None = self.body_state {
+                return;
+            }
+            if count > BODY_DRAIN_MAX {
+                self.body_state = BodyState::None;
+                self.request.close = true;
+                return;
+            }
+            match self.read_body(&mut buffer) {
+                Ok(size) => count += size as u64,
+                Err(err) => {
+                    log::debug!("drain request body failed! {}", err);
+                    return;
+                }
             }
-            _ => Ok(())
         }
     }
 
     /// 如果存在body，则需要根据content-type对body数据进行解析使用
     fn parse_body(&mut self) -> StarryResult<()> {
-        let body = self.body();
+        match self.load_body() {
+            Ok(()) => {}
+            Err(err) => return Err(self.interrupt(Response::bad_request(), err))
+        }
+        let body = self.request.body();
         log::trace!("body len = {}", body.len());
         let content_type;
         match self.request.content_type() {
@@ -714,57 +843,92 @@ impl<Stream: Read + Write + Debug> Requester<Stream> {
         // }
         Ok(())
     }
-
-    fn write_bytes(&mut self, bw: &mut Writer<BytesMut>, src: &[u8]) -> StarryResult<usize> {
-        match bw.write(src) {
-            Ok(src) => Ok(src),
-            Err(err) => return Err(self.interrupt(
-                Response::bad_request(),
-                Errs::strs("write data into body failed!", err)))
-        }
-    }
-
-    fn reread_stream(&mut self, buf: &mut [u8]) -> StarryResult<usize> {
-        match self.stream.read(buf) {
-            Ok(src) => {
-                Ok(src)
-            }
-            Err(err) => return Err(self.interrupt(
-                Response::bad_request(),
-                Errs::strs("reread failed while parse request with failed!", err)))
-        }
-    }
 }
 
 impl<Stream: Read + Write + Debug> Requester<Stream> {
     /// 执行回复操作
+    ///
+    /// 回复内容先整体组装再一次性写入，避免tls等连接将回复拆分成多个片段发送
     pub(crate) fn response(&mut self, mut response: Response) -> StarryResult<()> {
         log::debug!("response: {:#?}", response);
 
         // let mut tmp = vec![];
         // let _ = self.stream.read_to_end(&mut tmp).unwrap_or(0);
+        // 1xx、204及304回复不允许携带数据体
+        if !response.status.has_body() {
+            response.header.del("Content-Length");
+            response.header.del("Transfer-Encoding");
+            let data = response_head(&response);
+            self.write(data.as_slice())?;
+            return self.flush();
+        }
+        // 长度未知的回复使用chunked编码，此时不应再携带"Content-Length"
+        let chunked_body = response.header.is_chunked();
+        if chunked_body {
+            response.header.set_chunked();
+        }
+        let mut data = response_head(&response);
+        // HEAD请求只回复状态行及头部块，"Content-Length"保留为对应GET请求的数据体长度
+        if self.method() == Method::HEAD {
+            self.write(data.as_slice())?;
+            return self.flush();
+        }
+        // 数据块
+        if chunked_body {
+            data.append(&mut chunked::encode(response.get_write_content().as_ref()));
+        } else {
+            data.extend_from_slice(response.get_write_content().as_ref());
+        }
+        self.write(data.as_slice())?;
+        self.flush()
+    }
 
-        // 状态行
-        self.write(response.version.as_slice())?;
-        self.write(b" ")?;
-        self.write(response.status.code().to_string().as_bytes())?;
-        self.write(b" ")?;
-        self.write(response.status.phrase_as_slice())?;
-        self.write(b"\r\n")?;
-
-        // 头部块
-        for (key, values) in response.header.map() {
-            for value in values {
-                self.write(key.as_bytes())?;
-                self.write(b": ")?;
-                self.write(value.as_bytes())?;
-                self.write(b"\r\n")?;
+    /// 执行流式回复操作
+    ///
+    /// 先写入状态行及头部块，再边读取边写入数据体。数据体长度已知时按长度写入，否则使用chunked编码逐块写入
+    pub(crate) fn response_stream(&mut self, response: Response, mut body: BodyStream) -> StarryResult<()> {
+        log::debug!("response stream: {:#?}", response);
+        self.write(response_head(&response).as_slice())?;
+        if self.method() == Method::HEAD {
+            return self.flush();
+        }
+        let mut buffer = vec![0; 8192];
+        let mut count: u64 = 0;
+        loop {
+            let size = match body.reader.read(&mut buffer) {
+                Ok(src) => src,
+                Err(err) => return Err(Errs::strs("response stream read body failed!", err))
+            };
+            match body.content_length {
+                Some(len) => {
+                    if size == 0 {
+                        if count < len {
+                            return Err(Errs::string(format!("response stream body expect {} bytes but only {}!", len, count)));
+                        }
+                        break;
+                    }
+                    // 超出声明长度的数据直接丢弃
+                    let size = (len - count).min(size as u64) as usize;
+                    self.write(&buffer[0..size])?;
+                    count += size as u64;
+                    if count >= len {
+                        break;
+                    }
+                }
+                None => {
+                    if size == 0 {
+                        self.write(chunked::LAST_CHUNK)?;
+                        break;
+                    }
+                    self.write(chunked::encode_chunk(&buffer[0..size]).as_slice())?;
+                    self.flush()?;
+                }
             }
         }
-        self.write(b"\r\n")?;
-        // 数据块
-        self.write(response.get_write_content().as_ref())?;
+        self.flush()
+    }
 
+    fn flush(&mut self) -> StarryResult<()> {
         match self.stream.flush() {
             Ok(()) => Ok(()),
             Err(err) => Err(Errs::strs("stream flush failed!", err)),
@@ -790,33 +954,125 @@ impl<Stream: Read + Write + Debug> Requester<Stream> {
     }
 }
 
+/// 请求正文读取器，读取时才从连接中获取数据
+///
+/// 通过[`Requester::body_reader`]或[`Context::req_body_reader`]获取
+///
+/// [`Requester::body_reader`]: crate::Requester::body_reader
+/// [`Context::req_body_reader`]: crate::Context::req_body_reader
+pub struct BodyReader<'a, Stream: Read + Write + Debug> {
+    requester: &'a mut Requester<Stream>,
+}
+
+impl<'a, Stream: Read + Write + Debug> Read for BodyReader<'a, Stream> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.requester.read_body(buf)
+    }
+}
+
+/// 请求目标为绝对形式（absolute-form）时，如经代理转发的"http://host:port/path?query"，去除协议及主机部分
+fn origin_form(target: &[u8]) -> Vec<u8> {
+    let lower = target.to_ascii_lowercase();
+    let from = if lower.starts_with(b"http://") {
+        7
+    } else if lower.starts_with(b"https://") {
+        8
+    } else {
+        return target.to_vec();
+    };
+    match target[from..].iter().position(|b| *b == b'/' || *b == b'?') {
+        Some(pos) if target[from + pos] == b'/' => target[from + pos..].to_vec(),
+        Some(pos) => [b"/", &target[from + pos..]].concat(),
+        None => b"/".to_vec()
+    }
+}
+
+/// 组装回复的状态行及头部块
+fn response_head(response: &Response) -> Vec<u8> {
+    let mut data: Vec<u8> = vec![];
+
+    // 状态行
+    data.extend_from_slice(response.version.as_slice());
+    data.extend_from_slice(b" ");
+    data.extend_from_slice(response.status.code().to_string().as_bytes());
+    data.extend_from_slice(b" ");
+    data.extend_from_slice(response.status.phrase_as_slice());
+    data.extend_from_slice(b"\r\n");
+
+    // 头部块
+    for (key, values) in response.header.map() {
+        for value in values {
+            data.extend_from_slice(key.as_bytes());
+            data.extend_from_slice(b": ");
+            data.extend_from_slice(value.as_bytes());
+            data.extend_from_slice(b"\r\n");
+        }
+    }
+    data.extend_from_slice(b"\r\n");
+    data
+}
+
 #[cfg(test)]
 mod requester_test {
     use std::borrow::BorrowMut;
-    use std::fs::File;
-    use std::io::Read;
+    use std::fs;
+    use std::io::{Cursor, Read};
 
+    use crate::http::requester::BodyState;
     use crate::http::url::authority::Addr;
+    use crate::http::url::Scheme;
     use crate::Requester;
 
-    impl Requester<File> {
-        fn new_mock(file: File) -> Self {
+    impl Requester<Cursor<Vec<u8>>> {
+        fn new_mock(data: Vec<u8>, body_limit: u64) -> Self {
             Requester {
                 request: Default::default(),
-                stream: file,
+                stream: Cursor::new(data),
+                body_state: BodyState::None,
+                body_limit,
+                body_read: 0,
+                surplus: vec![],
             }
         }
     }
 
+    /// 测试请求文件使用LF换行，"Content-Length"与正文实际长度不一致，修正为实际长度
+    fn request_test_data() -> Vec<u8> {
+        let data = fs::read("examples/request_test").unwrap();
+        let pos = data.windows(2).position(|w| w == b"\n\n").unwrap();
+        let body = &data[pos + 2..];
+        let head = String::from_utf8_lossy(&data[0..pos]).replace("Content-Length: 728", &format!("Content-Length: {}", body.len()));
+        let mut res = head.into_bytes();
+        res.extend_from_slice(b"\n\n");
+        res.extend_from_slice(body);
+        res
+    }
+
+    #[test]
+    fn parse_absolute_form() {
+        for (line, path) in vec![
+            ("GET http://localhost:7878/a/b?key=value HTTP/1.1\r\n", "/a/b"),
+            ("GET HTTP://localhost:7878?key=value HTTP/1.1\r\n", "/"),
+            ("GET https://localhost HTTP/1.1\r\n", "/"),
+        ] {
+            let mut req = Requester::new_mock(vec![], 0);
+            let (location, _) = req.parse_request_line(line.as_bytes().iter().borrow_mut()).unwrap();
+            assert_eq!(location.path(), path, "{}", line);
+        }
+        let mut req = Requester::new_mock(vec![], 0);
+        let (location, _) = req.parse_request_line(b"GET http://localhost/a?key=value HTTP/1.1\r\n".iter().borrow_mut()).unwrap();
+        assert_eq!(location.query().get("key").unwrap(), "value");
+    }
+
     #[test]
     fn parse_test() {
-        let mut file = File::open("examples/request_test").unwrap();
+        let mut file = Cursor::new(request_test_data());
         let mut buffer = [0; 1024];
         let mut iter;
         // 剩余待读取数据的总长度
         let mut size = file.read(&mut buffer).unwrap();
         iter = buffer.iter();
-        let mut req = Requester::new_mock(file.try_clone().unwrap());
+        let mut req = Requester::new_mock(file.get_ref()[size..].to_vec(), 0);
         // 解析请求行信息 POST /path/data?key=value&key2=value2 HTTP/1.1
         let (location, count) = req.parse_request_line(iter.borrow_mut()).unwrap();
         size -= count;
@@ -832,7 +1088,7 @@ mod requester_test {
         assert_eq!(req.request.header.get("Host").unwrap(), "localhost:7878");
         let peer = Addr::new("127.0.0.1".to_string());
         let local = Addr::new("127.0.0.2".to_string());
-        req.parse_others(location, peer, local).unwrap();
+        req.parse_others(location, Scheme::HTTP, peer, local).unwrap();
         assert_eq!("127.0.0.1:80", req.request.client.to_string());
         assert_eq!("http", req.request.url.scheme.as_str());
         assert_eq!("user", req.request.url.authority.userinfo().unwrap().username());
@@ -845,7 +1101,7 @@ mod requester_test {
         assert_eq!(req.request.cookies.get(0).unwrap().value, "value3");
         assert_eq!(req.request.cookies.get(1).unwrap().name, "Cookie_4");
         assert_eq!(req.request.cookies.get(1).unwrap().value, "value4");
-        req.fill_body(iter.borrow_mut(), size).unwrap();
+        req.init_body(&iter.as_ref()[0..size]).unwrap();
         req.parse_body().unwrap();
         assert_eq!(req.param_value("key").unwrap(), "value");
         assert_eq!(req.param_value("key2").unwrap(), "value2");
@@ -864,6 +1120,67 @@ test5
 ".as_bytes().to_vec());
     }
 
+    #[test]
+    fn body_reader_test() {
+        let mut req = Requester::new_mock(b"lo, starryGET / HTTP/1.1".to_vec(), 0);
+        req.request.header.set_str("Content-Length", "13");
+        req.init_body(b"hel").unwrap();
+        let mut body = vec![];
+        req.body_reader().read_to_end(&mut body).unwrap();
+        assert_eq!(body, b"hello, starry".to_vec());
+        // 正文之后的数据不会被读取
+        assert_eq!(req.stream.position(), 10);
+    }
+
+    #[test]
+    fn body_reader_chunked_test() {
+        let mut req = Requester::new_mock(b"llo\r\n0\r\nX-Sum: 5\r\n\r\n".to_vec(), 0);
+        req.request.header.set_str("Transfer-Encoding", "chunked");
+        req.init_body(b"5\r\nhe").unwrap();
+        assert_eq!(req.body_all().unwrap(), b"hello".to_vec());
+        assert_eq!(req.request.header.get("X-Sum").unwrap(), "5");
+    }
+
+    #[test]
+    fn body_trailers_test() {
+        // 分帧、路由、认证及cookie相关字段不允许通过trailer设置
+        let mut req = Requester::new_mock(b"llo\r\n0\r\nContent-Length: 100\r\nhost: evil\r\nAuthorization: Basic YTpi\r\n\
+        Cookie: a=b\r\nX-Sum: 5\r\n\r\n".to_vec(), 0);
+        req.request.header.set_str("Transfer-Encoding", "chunked");
+        req.request.header.set_str("Host", "localhost");
+        req.init_body(b"5\r\nhe").unwrap();
+        assert_eq!(req.body_all().unwrap(), b"hello".to_vec());
+        assert_eq!(req.request.header.get("X-Sum").unwrap(), "5");
+        assert_eq!(req.request.header.get("Host").unwrap(), "localhost");
+        assert!(req.request.header.get("Content-Length").is_none());
+        assert!(req.request.header.get("Authorization").is_none());
+        assert!(req.request.header.get("Cookie").is_none());
+
+        // trailer计入正文长度限制
+        let mut req = Requester::new_mock(b"llo\r\n0\r\nX-Sum: 5\r\n\r\n".to_vec(), 8);
+        req.request.header.set_str("Transfer-Encoding", "chunked");
+        req.init_body(b"5\r\nhe").unwrap();
+        assert!(req.body_all().is_err());
+        assert!(req.request.close);
+        assert!(req.request.header.get("X-Sum").is_none());
+    }
+
+    #[test]
+    fn body_limit_test() {
+        // 声明长度超出限制
+        let mut req = Requester::new_mock(vec![], 4);
+        req.request.header.set_str("Content-Length", "5");
+        assert!(req.init_body(b"hello").is_err());
+
+        // 读取过程中超出限制
+        let mut req = Requester::new_mock(b"llo\r\n0\r\n\r\n".to_vec(), 4);
+        req.request.header.set_str("Transfer-Encoding", "chunked");
+        req.init_body(b"5\r\nhe").unwrap();
+        let mut body = vec![];
+        assert!(req.body_reader().read_to_end(&mut body).is_err());
+        assert!(req.request.close);
+    }
+
     // fn parse_bench_init(peer: Addr, local: Addr) {
     //     let mut file = File::open("examples/request_test").unwrap();
     //     let mut buffer = [0; 1024];
@@ -12,17 +12,22 @@
  * limitations under the License.
  */
 
+use std::io::Read;
+
 use bytes::{Bytes, BytesMut};
 use flate2::Compression;
 
-use crate::{Status, Version};
+use crate::{Status, URL, Version};
 use crate::header::AcceptEncoding;
-use crate::http::body::Body;
+use crate::http::body::{Body, BodyStream};
 use crate::http::header::{ContentType, Cookie};
 use crate::http::header::Header;
 use crate::utils::Compress;
 use crate::utils::errors::StarryResult;
 
+/// 解压接收到的数据体时允许的最大长度，超出时视为压缩炸弹并返回错误
+const MAX_DECOMPRESSED_LEN: u64 = 64 * 1024 * 1024;
+
 /// Response 表示由服务器响应客户端发送的HTTP请求。
 ///
 /// 响应报文格式如下所示：
@@ -53,6 +58,8 @@ pub struct Response {
     pub(crate) close: bool,
     /// 是否启用http压缩，如gzip、deflate等
     compress: bool,
+    /// 客户端跟随重定向时依次经过的地址，不包括最终返回该回复的地址
+    pub(crate) redirects: Vec<URL>,
 }
 
 /// 组合方法集
@@ -86,10 +93,15 @@ impl Response {
         self.header.read_set_cookies()
     }
 
+    /// 使用chunked编码传输数据体，适用于数据体长度无法预先确定的回复，设置后将不再携带"Content-Length"
+    pub fn set_chunked(&mut self) {
+        self.header.set_chunked()
+    }
+
     fn set_this_encode(&mut self, content_length: usize, content_type: ContentType, accept_encoding: AcceptEncoding) {
         self.header.set_content_length(content_length);
         self.header.set_content_type(content_type);
-        self.header.set_accept_encoding(accept_encoding)
+        self.header.set_content_encoding(accept_encoding)
     }
 
     fn set_this(&mut self, content_length: usize, content_type: ContentType) {
@@ -105,6 +117,48 @@ impl Response {
         self.body.vec()
     }
 
+    /// 按"Content-Encoding"解压接收到的数据体，解压后移除该回复头并更新"Content-Length"
+    ///
+    /// "deflate"编码优先按zlib格式解压，失败时再按原始deflate格式解压，以兼容两种常见实现。
+    /// 解压后的数据超过[`MAX_DECOMPRESSED_LEN`]时返回错误
+    pub(crate) fn decompress(&mut self) -> StarryResult<()> {
+        self.decompress_limit(MAX_DECOMPRESSED_LEN)
+    }
+
+    fn decompress_limit(&mut self, limit: u64) -> StarryResult<()> {
+        let encoding = match self.header.get_content_encoding() {
+            Some(src) => src,
+            None => return Ok(())
+        };
+        if self.body.reader.is_empty() {
+            return Ok(());
+        }
+        let data = self.body.reader.as_ref();
+        let src = match encoding {
+            AcceptEncoding::GZip => Compress::un_gzip_limit(data, limit)?,
+            AcceptEncoding::Deflate => match Compress::un_zlib_limit(data, limit) {
+                Ok(src) => src,
+                Err(_) => Compress::un_deflate_limit(data, limit)?
+            },
+            AcceptEncoding::ZLib => Compress::un_zlib_limit(data, limit)?,
+            _ => return Ok(())
+        };
+        for k in self.header.map().keys() {
+            if k.eq_ignore_ascii_case("Content-Encoding") {
+                self.header.del(k);
+            } else if k.eq_ignore_ascii_case("Content-Length") {
+                self.header.set(k.to_string(), src.len().to_string());
+            }
+        }
+        self.body.reader = Bytes::from(src);
+        Ok(())
+    }
+
+    /// 客户端跟随重定向时依次经过的地址，即各次返回3xx的请求地址，未发生重定向时为空
+    pub fn redirects(&self) -> Vec<URL> {
+        self.redirects.clone()
+    }
+
     pub fn write_type(&mut self, body: Vec<u8>, content_type: ContentType, accept_encoding: AcceptEncoding) {
         if !self.compress {
             self.set_this(body.len(), content_type);
@@ -170,6 +224,42 @@ impl Response {
         self.body.write(data)
     }
 
+    /// 组装流式数据体，数据类型沿用当前设置
+    ///
+    /// 启用http压缩且客户端支持时，对数据流边读取边压缩，压缩后长度未知，因此改用chunked编码
+    ///
+    /// * content_length 数据体长度，未知时使用chunked编码
+    pub(crate) fn write_stream(&mut self, reader: Box<dyn Read + Send>, content_length: Option<u64>,
+                               accept_encoding: AcceptEncoding) -> BodyStream {
+        self.body.write(vec![]);
+        if self.compress {
+            let level = Compression::default();
+            match accept_encoding {
+                AcceptEncoding::GZip => return self.stream_encode(Box::new(Compress::gzip_reader(reader, level)), accept_encoding),
+                AcceptEncoding::Deflate => return self.stream_encode(Box::new(Compress::deflate_reader(reader, level)), accept_encoding),
+                AcceptEncoding::ZLib => return self.stream_encode(Box::new(Compress::zlib_reader(reader, level)), accept_encoding),
+                _ => {}
+            }
+        }
+        self.stream_this(reader, content_length)
+    }
+
+    fn stream_encode(&mut self, reader: Box<dyn Read + Send>, accept_encoding: AcceptEncoding) -> BodyStream {
+        self.header.set_content_encoding(accept_encoding);
+        self.stream_this(reader, None)
+    }
+
+    fn stream_this(&mut self, reader: Box<dyn Read + Send>, content_length: Option<u64>) -> BodyStream {
+        match content_length {
+            Some(src) => {
+                self.header.del("Transfer-Encoding");
+                self.header.set("Content-Length".to_string(), src.to_string())
+            }
+            None => self.header.set_chunked()
+        }
+        BodyStream { reader, content_length }
+    }
+
     pub fn write(&mut self, body: Vec<u8>, accept_encoding: AcceptEncoding) {
         self.write_type(body, ContentType::TEXT_PLAIN, accept_encoding)
     }
@@ -241,6 +331,7 @@ impl Response {
             body: Default::default(),
             close: true,
             compress,
+            redirects: vec![],
         };
         resp.write(vec![], AcceptEncoding::None);
         if connection {
@@ -345,6 +436,7 @@ fn fill(status: Status) -> Response {
         body: Default::default(),
         close: true,
         compress: false,
+        redirects: vec![],
     }
 }
 
@@ -358,6 +450,7 @@ impl Default for Response {
             body: Default::default(),
             close: true,
             compress: false,
+            redirects: vec![],
         }
     }
 }
@@ -366,7 +459,12 @@ impl Default for Response {
 mod response_test {
     use std::ops::Add;
 
-    use crate::Response;
+    use bytes::BytesMut;
+    use flate2::Compression;
+
+    use crate::{Response, Version};
+    use crate::header::AcceptEncoding;
+    use crate::utils::Compress;
 
     impl Response {
         fn string(&mut self) -> String {
@@ -410,5 +508,28 @@ mod response_test {
         let mut r2 = Response::success();
         assert_eq!(r2.string(), "HTTP/1.1 200 OK\r\n\r\n");
     }
-}
 
+    #[test]
+    fn write_compress() {
+        let mut resp = Response::new(Version::HTTP_11, false, true);
+        resp.write("starry".repeat(100).into_bytes(), AcceptEncoding::GZip);
+        // 压缩后的数据体通过"Content-Encoding"声明编码方式，"Accept-Encoding"仅用于请求
+        assert_eq!(resp.header.get("Content-Encoding").unwrap(), "gzip");
+        assert!(resp.header.get("Accept-Encoding").is_none());
+    }
+
+    #[test]
+    fn decompress_limit() {
+        let data = vec![0u8; 1024 * 1024];
+        let gz = Compress::gzip(&data, Compression::best()).unwrap();
+        let mut resp = Response::new(Version::HTTP_11, false, true);
+        resp.header.set_content_encoding(AcceptEncoding::GZip);
+        resp.set_body(BytesMut::from(gz.as_slice()));
+        assert!(resp.decompress_limit(1024).is_err());
+
+        resp.set_body(BytesMut::from(gz.as_slice()));
+        resp.decompress().unwrap();
+        assert_eq!(resp.body(), data);
+        assert!(resp.header.get("Content-Encoding").is_none());
+    }
+}
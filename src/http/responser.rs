@@ -17,10 +17,10 @@ use std::fmt::Debug;
 use std::io::{Read, Write};
 use std::slice::Iter;
 
-use bytes::{BufMut, BytesMut};
-use bytes::buf::Writer;
+use bytes::BytesMut;
 
-use crate::{Request, Response, Status, Version};
+use crate::{Method, Request, Response, Status, Version};
+use crate::http::chunked;
 use crate::http::url::Scheme;
 use crate::utils::errors::{Errs, StarryResult};
 use crate::utils::Strings;
@@ -34,6 +34,8 @@ pub(crate) const CLIENT_TCP_STREAM_HAD_NO_DATA: &str = "client response tcp stre
 pub struct Responser<Stream: Read + Write + Debug> {
     pub(crate) response: Response,
     pub(crate) stream: Stream,
+    /// 协议升级时报头之后已读取的数据，属于升级后的协议
+    pub(crate) surplus: Vec<u8>,
 }
 
 impl<Stream: Read + Write + Debug> Responser<Stream> {
@@ -52,56 +54,65 @@ impl<Stream: Read + Write + Debug> Responser<Stream> {
         let mut resper = Responser {
             response: Default::default(),
             stream,
+            surplus: vec![],
         };
+        let head = request.method.eq(&Method::HEAD);
         resper.request(request)?;
-        resper.parse()?;
+        resper.parse(head)?;
         Ok(resper)
     }
 
     /// 解析返回信息
-    fn parse(&mut self) -> StarryResult<()> {
+    ///
+    /// 先读取至报头结束，再根据报头决定数据体的读取方式，避免在复用连接上阻塞等待对端关闭
+    ///
+    /// * head 是否为HEAD请求的返回，此类返回没有数据体
+    fn parse(&mut self, head: bool) -> StarryResult<()> {
         let mut data = vec![];
-        let mut iter;
-        // 当前读取总长度
-        let mut count = 0;
-        // 剩余待读取数据的总长度
-        let mut size;
-        match self.stream.read_to_end(&mut data) {
-            Ok(src) => {
-                log::trace!("response stream read size = {}", src);
-                if src == 0 { // 没有数据进入
-                    return Err(Errs::str(CLIENT_TCP_STREAM_HAD_NO_DATA));
-                }
-                size = src;
-                iter = data.iter()
+        let mut buffer = [0; 1024];
+        // 报头结束位置，即"\r\n\r\n"之后
+        let head_end;
+        loop {
+            let size = self.reread_stream(&mut buffer)?;
+            log::trace!("response stream read size = {}", size);
+            if size == 0 {
+                return if data.is_empty() { // 没有数据进入
+                    Err(Errs::connection(CLIENT_TCP_STREAM_HAD_NO_DATA))
+                } else {
+                    Err(Errs::str("parse response failed while stream closed before header end!"))
+                };
+            }
+            // 回退3个字节，避免"\r\n\r\n"恰好被拆分在两次读取之间
+            let from = if data.len() > 3 { data.len() - 3 } else { 0 };
+            data.extend_from_slice(&buffer[0..size]);
+            if let Some(pos) = data[from..].windows(4).position(|w| w == b"\r\n\r\n") {
+                head_end = from + pos + 4;
+                break;
             }
-            Err(err) => return Err(Errs::strs("read failed while parse request with error!", err))
         }
+        let mut iter = data[0..head_end].iter();
 
         // 解析返回行信息 HTTP/1.1 200 OK
         let len = self.parse_response_line(iter.borrow_mut())?;
-        log::trace!("parse_response_line size = {}, len1 = {}", size, len);
-        count += len;
+        log::trace!("parse_response_line head = {}, len1 = {}", head_end, len);
 
         // 解析消息报头
         let len = self.parse_response_header(iter.borrow_mut());
-        log::trace!("size = {}, len2 = {}", size, len);
-        count += len;
+        log::trace!("head = {}, len2 = {}", head_end, len);
 
         self.parse_others()?;
 
-        if size < count {
-            return Ok(());
-        }
-        size -= count;
-
         // 读取请求正文
         // 当用户使用到form数据等情况时，会解析，解析后，body内数据会被清空
         // 用户也可以主动使用body数据，但用户使用后，解析不会再自动进行
-        self.fill_body(iter.borrow_mut(), size)?;
-
-        // println!("response buffer = {}", String::from_utf8_lossy(buffer.as_slice()));
-        Ok(())
+        if self.response.status == Status::SWITCHING_PROTOCOLS {
+            self.surplus = data[head_end..].to_vec();
+            return Ok(());
+        }
+        if head || !self.response.status.has_body() {
+            return Ok(());
+        }
+        self.fill_body(&data[head_end..])
     }
 
     /// 解析返回行信息 HTTP/1.1 200 OK
@@ -137,6 +148,8 @@ impl<Stream: Read + Write + Debug> Responser<Stream> {
                                 }
                                 Err(err) => return Err(Errs::strs("parse response status code failed!", err))
                             }
+                            // 状态描述中可以包含空格，如"Not Found"
+                            3 => data.push(*b),
                             _ => return Err(Errs::str("parse response failed!"))
                         }
                         b'\r' | b'\n' => match step {
@@ -233,61 +246,59 @@ impl<Stream: Read + Write + Debug> Responser<Stream> {
         }
     }
 
-    fn fill_body(&mut self, iter: &mut Iter<u8>, mut size: usize) -> StarryResult<()> {
-        let mut bytes = vec![];
-        loop {
-            match iter.next() {
-                Some(b) => {
-                    match b {
-                        b'\r' | b'\n' => size -= 1,
-                        _ => {
-                            bytes.push(*b);
-                            size -= 1;
-                            break;
+    /// 读取返回正文
+    ///
+    /// 依次支持chunked编码、"Content-Length"以及以关闭连接作为结束的返回
+    ///
+    /// * pre 解析报头时已读取的正文部分
+    fn fill_body(&mut self, pre: &[u8]) -> StarryResult<()> {
+        if self.response.header.is_chunked() {
+            return match chunked::decode(pre, &mut self.stream) {
+                Ok((body, trailers)) => {
+                    // 分帧、编码、cookie及重定向等字段不允许通过trailer设置，直接丢弃
+                    for (key, value) in trailers {
+                        if chunked::forbidden_trailer(&key) {
+                            log::debug!("response chunked trailer {} is forbidden, discard!", key);
+                        } else {
+                            self.response.header.add(key, value)
                         }
                     }
+                    self.response.set_body(BytesMut::from(body.as_slice()));
+                    Ok(())
                 }
-                None => return Ok(())
-            }
+                // 超时错误原样返回，以便调用方区分
+                Err(err) if err.is_timeout() => Err(err),
+                Err(err) => Err(Errs::strs("parse response chunked body failed!", err))
+            };
         }
-        // iter.next(); // 请求，过滤换行符
-        // size -= 1;
-
+        let mut body = pre.to_vec();
+        let mut buffer = [0; 1024];
         match self.response.header.get_content_length() {
-            Some(content_len) => match content_len.parse::<isize>() {
-                Ok(mut len) => {
-                    if len > 0 { // 读取到"Content-Length"大于0
-                        // 因为前面内容多读取了一个字节，所以len = len - 1
-                        len = len - 1;
-                        // Body数据存在，新建写流，将body数据进行填充
-                        let mut bw = BytesMut::new().writer();
-                        self.write_bytes(bw.borrow_mut(), bytes.as_slice())?;
-                        self.write_bytes(bw.borrow_mut(), &iter.as_ref()[0..size])?;
-                        self.response.set_body(bw.into_inner());
-                    } else if len == 0 {  // 读取到"Content-Length"等于0
-                        return Ok(());
-                    } else if len == -1 {  // 读取到"Content-Length"小于0
-                        // Body数据存在，长度未知，新建写流，将body数据进行填充
-                        let mut bw = BytesMut::new().writer();
-                        self.write_bytes(bw.borrow_mut(), bytes.as_slice())?;
-                        self.write_bytes(bw.borrow_mut(), &iter.as_ref()[0..size])?;
-                        self.response.set_body(bw.into_inner());
-                    } else {
-                        return Err(Errs::string(format!("content len {} invalid from header failed!", len)));
+            Some(content_len) => match content_len.trim().parse::<usize>() {
+                Ok(len) => {
+                    while body.len() < len {
+                        let size = self.reread_stream(&mut buffer)?;
+                        if size == 0 {
+                            return Err(Errs::string(format!("response body expect {} bytes but stream closed at {}!", len, body.len())));
+                        }
+                        body.extend_from_slice(&buffer[0..size])
                     }
-                    Ok(())
+                    body.truncate(len);
                 }
                 Err(err) => return Err(Errs::strings(format!("content len {} parse usize from header failed!", content_len), err))
             },
-            None => return Ok(())
+            None => { // 长度未知，读取至对端关闭连接，该连接不可复用
+                match self.stream.read_to_end(&mut body) {
+                    Ok(_) => {}
+                    Err(err) => return Err(Errs::ios("parse response body while read_to_end failed!", err))
+                }
+                self.response.close = true;
+            }
         }
-    }
-
-    fn write_bytes(&mut self, bw: &mut Writer<BytesMut>, src: &[u8]) -> StarryResult<usize> {
-        match bw.write(src) {
-            Ok(src) => Ok(src),
-            Err(err) => return Err(Errs::strs("write data into body failed!", err))
+        if !body.is_empty() {
+            self.response.set_body(BytesMut::from(body.as_slice()));
         }
+        Ok(())
     }
 
     fn reread_stream(&mut self, buf: &mut [u8]) -> StarryResult<usize> {
@@ -295,47 +306,111 @@ impl<Stream: Read + Write + Debug> Responser<Stream> {
             Ok(src) => {
                 Ok(src)
             }
-            Err(err) => return Err(Errs::strs("reread failed while parse response with failed!", err))
+            Err(err) => Err(Errs::ios("reread failed while parse response with failed!", err))
         }
     }
 }
 
 impl<Stream: Read + Write + Debug> Responser<Stream> {
     /// 执行请求操作
+    ///
+    /// 请求内容先整体组装再一次性写入，避免tls等连接将请求拆分成多个片段发送
     pub(crate) fn request(&mut self, mut request: Request) -> StarryResult<()> {
         // log::debug!("request: {:#?}", request);
+        let body = request.get_write_content();
+        // 携带数据体且未声明长度时，补充"Content-Length"
+        if !body.is_empty() && request.header.get_content_length().is_none() && !request.header.is_chunked() {
+            request.header.set("Content-Length".to_string(), body.len().to_string());
+        }
+        let mut data: Vec<u8> = vec![];
 
         // 状态行
-        self.write(request.method.as_str().as_bytes())?;
-        self.write(b" ")?;
-        self.write(request.url.location.to_string().as_bytes())?;
-        self.write(b" ")?;
-        self.write(request.version.as_slice())?;
-        self.write(b"\r\n")?;
+        data.extend_from_slice(request.method.as_str().as_bytes());
+        data.extend_from_slice(b" ");
+        if request.absolute_form {
+            data.extend_from_slice(request.url.to_string().as_bytes());
+        } else {
+            data.extend_from_slice(request.url.location.to_string().as_bytes());
+        }
+        data.extend_from_slice(b" ");
+        data.extend_from_slice(request.version.as_slice());
+        data.extend_from_slice(b"\r\n");
 
         // 头部块
         for (key, values) in request.header.map() {
             for value in values {
-                self.write(key.as_bytes())?;
-                self.write(b": ")?;
-                self.write(value.as_bytes())?;
-                self.write(b"\r\n")?;
+                data.extend_from_slice(key.as_bytes());
+                data.extend_from_slice(b": ");
+                data.extend_from_slice(value.as_bytes());
+                data.extend_from_slice(b"\r\n");
             }
         }
-        self.write(b"\r\n")?;
+        data.extend_from_slice(b"\r\n");
         // 数据块
-        self.write(request.get_write_content().as_ref())?;
+        data.extend_from_slice(body.as_ref());
+        self.write(data.as_slice())?;
+        // 包含磁盘文件的数据体，边读取边写入
+        if let Some(src) = &request.multipart_body {
+            match src.write_to(&mut self.stream) {
+                Ok(()) => {}
+                Err(err) => return Err(Errs::ios("stream write multipart body to request failed!", err))
+            }
+        }
 
         match self.stream.flush() {
             Ok(()) => Ok(()),
-            Err(err) => Err(Errs::strs("stream flush failed!", err)),
+            Err(err) => Err(Errs::ios("stream flush failed!", err)),
         }
     }
 
-    fn write(&mut self, buf: &[u8]) -> StarryResult<usize> {
-        match self.stream.write(buf) {
-            Ok(src) => Ok(src),
-            Err(err) => Err(Errs::strs("stream write to request failed!", err)),
+    fn write(&mut self, buf: &[u8]) -> StarryResult<()> {
+        match self.stream.write_all(buf) {
+            Ok(_) => Ok(()),
+            Err(err) => Err(Errs::ios("stream write to request failed!", err)),
         }
     }
 }
+
+#[cfg(test)]
+mod responser_test {
+    use std::io::{Cursor, Read, Result, Write};
+
+    use crate::{Method, Request};
+    use crate::http::responser::Responser;
+
+    /// 读取预设的回复数据，写入的请求直接丢弃
+    #[derive(Debug)]
+    struct MockStream(Cursor<Vec<u8>>);
+
+    impl Read for MockStream {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            self.0.read(buf)
+        }
+    }
+
+    impl Write for MockStream {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn chunked_trailers() {
+        // 分帧、编码、cookie及重定向相关字段不允许通过trailer设置
+        let data = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\nContent-Length: 100\r\n\
+        Content-Encoding: gzip\r\nSet-Cookie: a=b\r\nLocation: http://evil/\r\nX-Sum: 5\r\n\r\n".to_vec();
+        let request = Request::new(Method::GET, "http://localhost/").unwrap();
+        let mut resper = Responser::from(MockStream(Cursor::new(data)), request).unwrap();
+        assert_eq!(resper.body(), b"hello".to_vec());
+        let header = &resper.response.header;
+        assert_eq!(header.get("X-Sum").unwrap(), "5");
+        assert!(header.get("Content-Length").is_none());
+        assert!(header.get("Content-Encoding").is_none());
+        assert!(header.get("Set-Cookie").is_none());
+        assert!(header.get("Location").is_none());
+    }
+}
@@ -449,6 +449,11 @@ impl Status {
     pub fn code(&self) -> u16 {
         self.0
     }
+
+    /// 当前状态的回复是否允许携带数据体，1xx、204及304回复没有数据体
+    pub(crate) fn has_body(&self) -> bool {
+        !matches!(self.0, 100..=199 | 204 | 304)
+    }
 }
 
 impl<'a> PartialEq<&'a Status> for Status {
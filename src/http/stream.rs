@@ -0,0 +1,143 @@
+/*
+ * Copyright (c) 2021. Aberic - All Rights Reserved.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::io::{ErrorKind, Read, Result, Write};
+use std::net::{Shutdown, TcpStream};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use openssl::ssl::SslStream;
+
+use crate::http::url::Scheme;
+
+/// 连接流，对明文tcp连接和基于tls的加密连接进行统一封装
+///
+/// tls连接无法像[`TcpStream`]一样直接`try_clone`，因此通过`Arc<Mutex<_>>`进行共享，
+/// 同时保留一份底层[`TcpStream`]，用于在不争抢锁的情况下关闭连接
+///
+/// [`TcpStream`]: std::net::TcpStream
+#[derive(Debug)]
+pub(crate) enum HttpStream {
+    /// 明文tcp连接
+    Tcp(TcpStream),
+    /// 基于tls的加密连接，以及其底层tcp连接
+    Tls(Arc<Mutex<SslStream<TcpStream>>>, TcpStream),
+}
+
+impl HttpStream {
+    pub(crate) fn tls(ssl_stream: SslStream<TcpStream>) -> Result<Self> {
+        let tcp_stream = ssl_stream.get_ref().try_clone()?;
+        Ok(HttpStream::Tls(Arc::new(Mutex::new(ssl_stream)), tcp_stream))
+    }
+
+    pub(crate) fn try_clone(&self) -> Result<Self> {
+        match self {
+            HttpStream::Tcp(src) => Ok(HttpStream::Tcp(src.try_clone()?)),
+            HttpStream::Tls(src, tcp) => Ok(HttpStream::Tls(src.clone(), tcp.try_clone()?)),
+        }
+    }
+
+    /// 当前连接所使用的协议
+    pub(crate) fn scheme(&self) -> Scheme {
+        match self {
+            HttpStream::Tcp(_) => Scheme::HTTP,
+            HttpStream::Tls(_, _) => Scheme::HTTPS,
+        }
+    }
+
+    /// 关闭连接，直接作用于底层tcp连接，不会等待正在进行的读写操作释放锁
+    pub(crate) fn shutdown(&self, how: Shutdown) -> Result<()> {
+        self.tcp().shutdown(how)
+    }
+
+    /// 设置读取超时，直接作用于底层tcp连接，tls连接同样生效
+    pub(crate) fn set_read_timeout(&self, dur: Option<Duration>) -> Result<()> {
+        self.tcp().set_read_timeout(dur)
+    }
+
+    /// 设置写入超时，直接作用于底层tcp连接，tls连接同样生效
+    pub(crate) fn set_write_timeout(&self, dur: Option<Duration>) -> Result<()> {
+        self.tcp().set_write_timeout(dur)
+    }
+
+    /// 检查空闲连接是否仍然可用
+    ///
+    /// 以非阻塞方式窥探底层tcp连接，对端已关闭（读到EOF）、出错或存在未预期的数据时均视为不可用
+    pub(crate) fn is_alive(&self) -> bool {
+        let tcp = self.tcp();
+        if tcp.set_nonblocking(true).is_err() {
+            return false;
+        }
+        let mut buf = [0; 1];
+        let alive = match tcp.peek(&mut buf) {
+            Err(err) => err.kind() == ErrorKind::WouldBlock,
+            Ok(_) => false
+        };
+        tcp.set_nonblocking(false).is_ok() && alive
+    }
+
+    /// tls连接中是否存在已解密但尚未读取的数据，此类数据无法通过底层tcp连接的可读事件感知
+    pub(crate) fn has_buffered(&self) -> bool {
+        match self {
+            HttpStream::Tcp(_) => false,
+            HttpStream::Tls(src, _) => lock(src).ssl().pending() > 0,
+        }
+    }
+
+    fn tcp(&self) -> &TcpStream {
+        match self {
+            HttpStream::Tcp(src) => src,
+            HttpStream::Tls(_, tcp) => tcp,
+        }
+    }
+}
+
+impl Read for HttpStream {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        match self {
+            HttpStream::Tcp(src) => src.read(buf),
+            HttpStream::Tls(src, _) => lock(src).read(buf),
+        }
+    }
+}
+
+impl Write for HttpStream {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        match self {
+            HttpStream::Tcp(src) => src.write(buf),
+            HttpStream::Tls(src, _) => lock(src).write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        match self {
+            HttpStream::Tcp(src) => src.flush(),
+            HttpStream::Tls(src, _) => lock(src).flush(),
+        }
+    }
+}
+
+impl AsRawFd for HttpStream {
+    fn as_raw_fd(&self) -> RawFd {
+        self.tcp().as_raw_fd()
+    }
+}
+
+fn lock(src: &Arc<Mutex<SslStream<TcpStream>>>) -> std::sync::MutexGuard<'_, SslStream<TcpStream>> {
+    match src.lock() {
+        Ok(guard) => guard,
+        Err(err) => err.into_inner(),
+    }
+}
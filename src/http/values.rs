@@ -15,9 +15,12 @@
 use std::borrow::Borrow;
 use std::collections::HashMap;
 use std::fmt::{Debug, Formatter};
+use std::fs;
 use std::hash::Hash;
+use std::path::{Path, PathBuf};
 
 use crate::http::header::ContentType;
+use crate::utils::errors::{Errs, StarryResult};
 
 // pub trait RequestValues<Value> {
 //     fn new() -> Self;
@@ -180,9 +183,39 @@ impl MultipartValues {
             size: content.len(),
             content,
             content_type,
+            path: None,
         })
     }
 
+    /// 插入磁盘文件，文件名取自路径，数据类型根据扩展名推断
+    ///
+    /// 文件内容不会被读入内存，客户端发送请求时才边读取边写入
+    pub fn insert_path(&mut self, name: String, path: &str) -> StarryResult<Option<FileHeader>> {
+        let path = Path::new(path);
+        let metadata = match fs::metadata(path) {
+            Ok(src) => src,
+            Err(err) => return Err(Errs::strings(format!("multipart file {} read metadata failed!", path.display()), err))
+        };
+        if !metadata.is_file() {
+            return Err(Errs::string(format!("multipart file {} is not a file!", path.display())));
+        }
+        let content_type = match path.extension() {
+            Some(ext) => ContentType::from_extension(&ext.to_string_lossy()),
+            None => ContentType::APPLICATION_OCTET_STREAM
+        };
+        let filename = match path.file_name() {
+            Some(src) => src.to_string_lossy().to_string(),
+            None => String::new()
+        };
+        Ok(self.insert(name, FileHeader {
+            filename,
+            size: metadata.len() as usize,
+            content: vec![],
+            content_type,
+            path: Some(path.to_path_buf()),
+        }))
+    }
+
     /// 返回对对应于键的值的引用。
     /// 键可以是映射的键类型的任何借用形式，但是借用形式上的Hash和Eq必须与键类型匹配。
     pub fn get<K: ?Sized>(&self, k: &K) -> Option<FileHeader> where
@@ -232,6 +265,8 @@ pub struct FileHeader {
     content: Vec<u8>,
     /// 指定基础数据的媒体类型
     content_type: ContentType,
+    /// 磁盘文件路径，存在时内容在发送时从该文件读取
+    path: Option<PathBuf>,
 }
 
 impl FileHeader {
@@ -244,6 +279,7 @@ impl FileHeader {
             size,
             content,
             content_type,
+            path: None,
         }
     }
 
@@ -258,6 +294,18 @@ impl FileHeader {
     pub fn content(&self) -> Vec<u8> {
         self.content.clone()
     }
+
+    pub fn content_type(&self) -> ContentType {
+        self.content_type.clone()
+    }
+
+    /// 磁盘文件路径，通过[`MultipartValues::insert_path`]插入时存在，此时[`content`]为空
+    ///
+    /// [`MultipartValues::insert_path`]: crate::MultipartValues::insert_path
+    /// [`content`]: crate::http::values::FileHeader::content
+    pub fn path(&self) -> Option<PathBuf> {
+        self.path.clone()
+    }
 }
 
 impl Debug for FileHeader {
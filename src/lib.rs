@@ -15,7 +15,14 @@
 #[macro_use]
 extern crate lazy_static;
 
+pub use client::ClientPool;
+pub use client::CookieJar;
 pub use client::HttpClient;
+pub use client::PoolStats;
+pub use client::Proxy;
+pub use client::RedirectPolicy;
+pub use client::RequestBuilder;
+pub use client::RetryPolicy;
 pub use http::header;
 pub use http::header::content_type::Inner;
 pub use http::header::Header;
@@ -24,16 +31,25 @@ pub use http::request::Request;
 pub use http::requester::Requester;
 pub use http::response::Response;
 pub use http::status::Status;
+pub use http::url::Scheme;
 pub use http::url::URL;
 pub use http::values::MultipartValues;
 pub use http::values::Values;
 pub use http::version::Version;
+pub use server::BoundServer;
 pub use server::Context;
+pub use server::Event;
+pub use server::EventSender;
 pub use server::Extend;
 pub use server::HttpServer;
+pub use server::ServerHandle;
 pub use server::limit::Limit;
+pub use websocket::CloseFrame;
+pub use websocket::Message;
+pub use websocket::WebSocket;
 
 mod server;
 mod http;
 pub mod utils;
 mod client;
+mod websocket;
@@ -15,29 +15,55 @@
 use std::borrow::Borrow;
 use std::collections::HashMap;
 use std::hash::Hash;
-use std::net::TcpStream;
+use std::io::Read;
+use std::sync::Arc;
 
-use crate::{Header, Response, Status, Version, Requester};
-use crate::http::header::{ContentType, Cookie};
+use crate::{Header, Method, Response, Status, Version, Requester};
+use crate::http::body::{BodyStream, IterReader};
+use crate::http::header::{AcceptEncoding, ContentType, Cookie};
+use crate::http::stream::HttpStream;
 use crate::http::url::authority::{Addr, Userinfo};
+use crate::http::url::Scheme;
 use crate::http::values::FileHeader;
-use crate::utils::errors::StarryResult;
+use crate::server::sse;
+use crate::server::sse::EventSender;
+use crate::server::state::{Extensions, State};
+use crate::utils::concurrent::Thread;
+use crate::utils::errors::{Errs, StarryResult};
+use crate::websocket::{accept_key, Upgrade, WebSocket};
 
 #[derive(Debug)]
 pub struct Context {
-    requester: Requester<TcpStream>,
+    requester: Requester<HttpStream>,
     response: Response,
     fields: HashMap<String, String>,
+    /// 应用共享状态
+    state: Arc<State>,
+    /// 单次请求扩展数据
+    extensions: Extensions,
+    /// 流式回复数据体，存在时回复将忽略已写入的数据体
+    body_stream: Option<BodyStream>,
     /// 是否已经执行过response方法
     pub(crate) executed: bool,
+    /// 协议升级完成后接管连接的处理方法
+    upgrade: Option<Upgrade>,
 }
 
 /// request相关
 impl Context {
-    pub(crate) fn new(requester: Requester<TcpStream>, fields: HashMap<String, String>, compress: bool) -> Self {
+    pub(crate) fn new(requester: Requester<HttpStream>, fields: HashMap<String, String>, state: Arc<State>, compress: bool) -> Self {
         let version = requester.version();
         let connection = !requester.request.close;
-        Context { requester, response: Response::new(version, connection, compress), fields, executed: false }
+        Context {
+            requester,
+            response: Response::new(version, connection, compress),
+            fields,
+            state,
+            extensions: Extensions::new(),
+            body_stream: None,
+            executed: false,
+            upgrade: None,
+        }
     }
 
     // pub fn get_request(&self) -> StarryResult<Request> {
@@ -63,6 +89,18 @@ impl Context {
         self.requester.path()
     }
 
+    /// 当前请求所使用的协议，通过tls监听接收的请求为[`Scheme::HTTPS`]
+    ///
+    /// [`Scheme::HTTPS`]: crate::Scheme::HTTPS
+    pub fn req_scheme(&self) -> Scheme {
+        self.requester.scheme()
+    }
+
+    /// 当前请求是否通过tls加密连接传输
+    pub fn req_is_tls(&self) -> bool {
+        self.requester.scheme().is_tls()
+    }
+
     pub fn req_client_addr(&self) -> Addr {
         self.requester.addr()
     }
@@ -78,19 +116,13 @@ impl Context {
     /// Upgrade: websocket
     /// ```
     pub fn req_is_web_socket(&self) -> bool {
-        match self.req_header_get("Connection") {
-            Some(src) => if src.ne("upgrade") {
-                return false;
-            },
-            None => return false,
-        }
-        match self.req_header_get("Upgrade") {
-            Some(src) => if src.eq("websocket") {
-                return true;
-            },
-            None => return false,
-        }
-        false
+        let header = &self.requester.request.header;
+        header.contains_token("Connection", "upgrade") && header.contains_token("Upgrade", "websocket")
+    }
+
+    /// 客户端重连事件流时携带的"Last-Event-ID"
+    pub fn req_last_event_id(&self) -> Option<String> {
+        self.requester.request.header.get_ignore_case("Last-Event-ID")
     }
 
     pub fn req_cookies(&self) -> Vec<Cookie> {
@@ -101,6 +133,22 @@ impl Context {
         self.requester.cookie_get(cookie_name)
     }
 
+    /// 获取请求正文，尚未读取的正文会先从连接中全部读出
+    ///
+    /// 该操作会清空正文，且之后无法再解析表单
+    pub fn req_body(&mut self) -> StarryResult<Vec<u8>> {
+        self.requester.body_all()
+    }
+
+    /// 请求正文读取器，读取时才从连接中获取数据，适用于大数据上传等无需将正文全部读入内存的场景
+    ///
+    /// 正文长度限制在读取过程中检查，可通过[`HttpServer::set_body_limit`]设置
+    ///
+    /// [`HttpServer::set_body_limit`]: crate::HttpServer::set_body_limit
+    pub fn req_body_reader(&mut self) -> impl Read + '_ {
+        self.requester.body_reader()
+    }
+
     /// 返回对应于请求表单中定义参数值的引用。
     pub fn req_form<K: ?Sized>(&mut self, k: &K) -> StarryResult<Option<String>> where
         K: Borrow<K>,
@@ -145,6 +193,8 @@ impl Context {
 
     /// 返回对应于URI资源路径中定义参数值的引用。
     /// 键可以是映射的键类型的任何借用形式，但是借用形式上的Hash和Eq必须与键类型匹配。
+    ///
+    /// 通配资源如`/assets/*rest`，其值为剩余的全部路径，如请求`/assets/css/site.css`时`rest`的值为`css/site.css`
     pub fn req_field<K: ?Sized>(&self, k: &K) -> Option<String> where
         K: Borrow<K>,
         K: Hash + Eq,
@@ -180,6 +230,35 @@ impl Context {
     }
 }
 
+/// 状态及扩展相关
+impl Context {
+    /// 获取通过[`HttpServer::with_state`]注册的应用共享状态，未注册则返回None
+    ///
+    /// [`HttpServer::with_state`]: crate::HttpServer::with_state
+    pub fn state<T: Send + Sync + 'static>(&self) -> Option<Arc<T>> {
+        self.state.get::<T>()
+    }
+
+    /// 为本次请求插入扩展数据，如已存在同类型数据，则返回旧值
+    ///
+    /// 常用于过滤器向后续过滤器及处理方法传递数据，如已认证的用户信息
+    pub fn extension_insert<T: Send + Sync + 'static>(&mut self, value: T) -> Option<T> {
+        self.extensions.insert(value)
+    }
+
+    pub fn extension_get<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.extensions.get::<T>()
+    }
+
+    pub fn extension_get_mut<T: Send + Sync + 'static>(&mut self) -> Option<&mut T> {
+        self.extensions.get_mut::<T>()
+    }
+
+    pub fn extension_remove<T: Send + Sync + 'static>(&mut self) -> Option<T> {
+        self.extensions.remove::<T>()
+    }
+}
+
 /// response相关
 impl Context {
     pub fn get_response(&self) -> Response {
@@ -218,6 +297,11 @@ impl Context {
         self.response.set_content_type(src)
     }
 
+    /// 回复数据体使用chunked编码传输，而非通过"Content-Length"声明长度
+    pub fn resp_chunked(&mut self) {
+        self.response.set_chunked()
+    }
+
     pub fn resp_body(&mut self, body: Vec<u8>) {
         self.response.write(body, self.requester.accept_encoding())
     }
@@ -234,11 +318,144 @@ impl Context {
         self.response.write_slice_type(body, content_type, self.requester.accept_encoding())
     }
 
+    /// 以流的方式回复数据体，边读取边使用chunked编码写入，无需事先将数据全部读入内存
+    ///
+    /// 数据类型可通过[`resp_content_type`]设置
+    ///
+    /// [`resp_content_type`]: crate::Context::resp_content_type
+    pub fn resp_stream<R: Read + Send + 'static>(&mut self, reader: R) {
+        self.resp_stream_with(Box::new(reader), None)
+    }
+
+    /// 以流的方式回复已知长度的数据体，通过"Content-Length"声明长度
+    ///
+    /// 启用http压缩且客户端支持时，压缩后长度无法预知，仍会使用chunked编码
+    pub fn resp_stream_sized<R: Read + Send + 'static>(&mut self, reader: R, content_length: u64) {
+        self.resp_stream_with(Box::new(reader), Some(content_length))
+    }
+
+    /// 以迭代器的方式回复数据体，迭代器产生的每一项数据都会作为一个块及时写出
+    pub fn resp_stream_iter<I>(&mut self, iter: I) where I: Iterator<Item=Vec<u8>> + Send + 'static {
+        self.resp_stream_with(Box::new(IterReader::new(iter)), None)
+    }
+
+    /// 以流的方式回复已知长度的数据体，且不进行http压缩，以保证"Content-Length"及"Content-Range"与实际数据一致
+    pub(crate) fn resp_stream_identity<R: Read + Send + 'static>(&mut self, reader: R, content_length: u64) {
+        self.body_stream = Some(self.response.write_stream(Box::new(reader), Some(content_length), AcceptEncoding::None))
+    }
+
+    fn resp_stream_with(&mut self, reader: Box<dyn Read + Send>, content_length: Option<u64>) {
+        let accept_encoding = self.requester.accept_encoding();
+        self.body_stream = Some(self.response.write_stream(reader, content_length, accept_encoding))
+    }
+
+    /// 以"text/event-stream"回复服务端推送事件，每个事件写出后立即发送
+    ///
+    /// `f`在新线程中执行，当前线程负责逐条写出事件及心跳，所有[`EventSender`]释放后事件流结束。
+    /// 客户端断开后事件发送返回错误
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// use starry::{Context, Event, HttpServer};
+    ///
+    /// let server = HttpServer::new();
+    /// server.router("/job").get("/progress", |context: &mut Context| {
+    ///     context.sse(|sender| {
+    ///         let from: u32 = sender.last_event_id().and_then(|src| src.parse().ok()).unwrap_or(0);
+    ///         for i in from..100 {
+    ///             let event = Event::data(&format!("{}%", i + 1)).id(&(i + 1).to_string()).event("progress");
+    ///             if sender.send(event).is_err() {
+    ///                 return; // 客户端已断开
+    ///             }
+    ///             std::thread::sleep(Duration::from_millis(100));
+    ///         }
+    ///     }).unwrap_or(())
+    /// });
+    /// server.listener("127.0.0.1:7878").unwrap();
+    /// ```
+    ///
+    /// [`EventSender`]: crate::EventSender
+    pub fn sse<F>(&mut self, f: F) -> StarryResult<()> where F: FnOnce(EventSender) + Send + 'static {
+        let (sender, stream) = sse::channel(self.req_last_event_id());
+        Thread::spawn(move || f(sender))?;
+        self.resp_content_type(ContentType::custom_str("text/event-stream"));
+        self.resp_set_header_str("Cache-Control", "no-cache");
+        // 事件需要及时送达，不进行http压缩
+        self.body_stream = Some(self.response.write_stream(Box::new(IterReader::new(stream)), None, AcceptEncoding::None));
+        self.response();
+        Ok(())
+    }
+
     pub fn response(&mut self) {
         self.executed = true;
-        match self.requester.response(self.response.clone()) {
+        let res = match self.body_stream.take() {
+            Some(body) => self.requester.response_stream(self.response.clone(), body),
+            None => self.requester.response(self.response.clone()),
+        };
+        match res {
             Ok(()) => {}
-            Err(err) => log::error!("response failed! {}", err)
+            Err(err) => {
+                // 回复可能只写出了部分内容，该连接无法继续复用
+                self.requester.request.close = true;
+                log::error!("response failed! {}", err)
+            }
         }
     }
+
+    /// 完成websocket握手，回复"101 Switching Protocols"，并在本次请求处理结束后将连接交由`f`处理
+    ///
+    /// `f`在当前连接的处理线程中执行，返回后连接即被关闭，该连接不再参与keep-alive。
+    /// 如需协商子协议，可在调用前通过[`resp_set_header_str`]设置"Sec-WebSocket-Protocol"
+    ///
+    /// 请求不是合法的websocket握手时回复"400 Bad Request"，版本不受支持时回复"426 Upgrade Required"，并返回错误
+    ///
+    /// [`resp_set_header_str`]: crate::Context::resp_set_header_str
+    pub fn upgrade_websocket<F>(&mut self, f: F) -> StarryResult<()> where F: FnOnce(WebSocket) + Send + 'static {
+        let header = self.requester.request.header.clone();
+        let key = match header.get_ignore_case("Sec-WebSocket-Key") {
+            Some(src) if self.requester.method() == Method::GET && self.req_is_web_socket() => src,
+            _ => {
+                self.resp_status(Status::BAD_REQUEST);
+                self.response();
+                return Err(Errs::str("request is not a websocket handshake!"));
+            }
+        };
+        match header.get_ignore_case("Sec-WebSocket-Version") {
+            Some(src) if src.trim() == "13" => {}
+            _ => {
+                self.resp_status(Status::UPGRADE_REQUIRED);
+                self.resp_set_header_str("Sec-WebSocket-Version", "13");
+                self.response();
+                return Err(Errs::str("websocket version unsupported, only 13 is supported!"));
+            }
+        }
+        self.resp_status(Status::SWITCHING_PROTOCOLS);
+        self.response.header.del_content_type();
+        self.resp_set_header_str("Upgrade", "websocket");
+        self.resp_set_header_str("Connection", "Upgrade");
+        self.resp_set_header_str("Sec-WebSocket-Accept", &accept_key(&key));
+        self.executed = true;
+        self.requester.response(self.response.clone())?;
+        self.upgrade = Some(Upgrade::new(f));
+        Ok(())
+    }
+
+    /// 取出协议升级完成后接管连接的处理方法，以及解析请求时已读取的、属于升级后协议的数据
+    pub(crate) fn take_upgrade(&mut self) -> Option<(Upgrade, Vec<u8>)> {
+        match self.upgrade.take() {
+            Some(src) => Some((src, std::mem::take(&mut self.requester.surplus))),
+            None => None
+        }
+    }
+
+    /// 丢弃未被读取的请求正文
+    pub(crate) fn drain_body(&mut self) {
+        self.requester.drain_body()
+    }
+
+    /// 本次请求处理结束后是否需要关闭连接
+    pub(crate) fn close(&self) -> bool {
+        self.requester.request.close
+    }
 }
@@ -13,6 +13,7 @@
  */
 
 use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
 
 use crate::{Context, Status};
 pub use crate::server::limit::Limit;
@@ -22,14 +23,14 @@ pub use crate::server::limit::Limit;
 /// 过滤操作尽量不要对数据体里的信息进行校验之类的流程，最好是对path、header和cookie进行过滤
 ///
 /// ctx 请求处理上下文结构
-pub(crate) type Filter = fn(context: &mut Context);
+pub(crate) type Filter = Arc<dyn Fn(&mut Context) + Send + Sync>;
 
 /// 降级服务
 ///
 /// 当服务handler出现不可逆转的错误时，通过该方案进行补偿
 ///
 /// 最终会让用户体验到的是某些功能暂时不可用，但不会是不受控制的返回信息
-pub(crate) type Downgrade = fn(context: &mut Context);
+pub(crate) type Downgrade = Arc<dyn Fn(&mut Context) + Send + Sync>;
 
 #[derive(Clone)]
 pub struct Extend {
@@ -45,8 +46,11 @@ impl Extend {
     /// 扩展生成方法
     ///
     /// 只有过滤
-    pub fn e1(filters: Vec<Filter>) -> Extend {
-        Extend { filters, limit: None, downgrade: None }
+    ///
+    /// 过滤器可以是普通方法，也可以是闭包。如需混合多个不同类型的闭包，可以使用
+    /// `Box<dyn Fn(&mut Context) + Send + Sync>`作为数组元素类型
+    pub fn e1<F>(filters: Vec<F>) -> Extend where F: Fn(&mut Context) + Send + Sync + 'static {
+        Extend { filters: filters_arc(filters), limit: None, downgrade: None }
     }
 
     /// 扩展生成方法
@@ -59,8 +63,8 @@ impl Extend {
     /// 扩展生成方法
     ///
     /// 有限流，有过滤
-    pub fn e3(filters: Vec<Filter>, limit: Limit) -> Extend {
-        Extend { filters, limit: Some(limit), downgrade: None }
+    pub fn e3<F>(filters: Vec<F>, limit: Limit) -> Extend where F: Fn(&mut Context) + Send + Sync + 'static {
+        Extend { filters: filters_arc(filters), limit: Some(limit), downgrade: None }
     }
 
     /// 扩展生成方法
@@ -120,6 +124,15 @@ impl Extend {
     }
 }
 
+/// 将过滤器数组转换为可共享的过滤器集合
+fn filters_arc<F>(filters: Vec<F>) -> Vec<Filter> where F: Fn(&mut Context) + Send + Sync + 'static {
+    let mut res: Vec<Filter> = vec![];
+    for filter in filters {
+        res.push(Arc::new(filter))
+    }
+    res
+}
+
 impl Debug for Extend {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "filter count: {:#?}, \nlimit: {:#?}", self.filters.len(), self.limit)
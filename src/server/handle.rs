@@ -0,0 +1,253 @@
+/*
+ * Copyright (c) 2021. Aberic - All Rights Reserved.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::server::reactor::Reactor;
+use crate::utils::concurrent::{Thread, ThreadPool};
+use crate::utils::errors::{Errs, StarryResult};
+
+/// 强制关闭连接后等待工作线程退出的最短时间
+const FORCE_GRACE: Duration = Duration::from_millis(100);
+
+/// 通过[`HttpServer::start`]启动的服务句柄，用于查询监听地址及关闭服务
+///
+/// 句柄被释放时不会关闭服务，需显式调用[`ServerHandle::shutdown`]
+///
+/// [`HttpServer::start`]: crate::HttpServer::start
+pub struct ServerHandle {
+    local_addr: SocketAddr,
+    connections: Arc<Connections>,
+    reactor: Reactor,
+    thread_pool: ThreadPool,
+    accept: JoinHandle<()>,
+}
+
+impl ServerHandle {
+    pub(crate) fn new(local_addr: SocketAddr, connections: Arc<Connections>, reactor: Reactor, thread_pool: ThreadPool,
+                      accept: JoinHandle<()>) -> Self {
+        ServerHandle { local_addr, connections, reactor, thread_pool, accept }
+    }
+
+    /// 实际监听的地址，绑定端口号为0时可通过该方法获取操作系统分配的端口
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// 优雅关闭服务
+    ///
+    /// 1. 停止接收新连接，停止事件循环并关闭空闲的keep-alive连接
+    /// 2. 等待处理中的请求完成，完成后关闭其连接，已升级的连接（如WebSocket、SSE）同样会被等待
+    /// 3. 超过`timeout`仍未完成的连接将被强制关闭
+    /// 4. 关闭线程池并等待工作线程退出
+    ///
+    /// 返回是否在`timeout`内完成了全部请求
+    pub fn shutdown(self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        self.connections.stop();
+        // 监听线程阻塞在accept上，通过一次本地连接将其唤醒
+        match TcpStream::connect_timeout(&wake_addr(self.local_addr), remaining(deadline).max(FORCE_GRACE)) {
+            Ok(_) => {}
+            Err(err) => log::warn!("server shutdown wake listener failed! {}", err)
+        }
+        match self.accept.join() {
+            Ok(()) => {}
+            Err(_) => log::error!("server listener thread panicked!")
+        }
+        // 关闭所有等待下一个请求的空闲连接，此后处理完成的连接不再保持
+        self.reactor.stop();
+        let drained = self.connections.wait(deadline);
+        if !drained {
+            log::warn!("server shutdown timeout, force close {} connections!", self.connections.len());
+            self.connections.force();
+        }
+        self.thread_pool.shutdown(remaining(deadline).max(FORCE_GRACE)) && drained
+    }
+}
+
+impl fmt::Debug for ServerHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ServerHandle")
+            .field("local_addr", &self.local_addr)
+            .field("connections", &self.connections.len())
+            .finish()
+    }
+}
+
+fn remaining(deadline: Instant) -> Duration {
+    deadline.saturating_duration_since(Instant::now())
+}
+
+/// 监听未指定地址时，通过回环地址唤醒
+fn wake_addr(local_addr: SocketAddr) -> SocketAddr {
+    match local_addr.ip() {
+        IpAddr::V4(ip) if ip.is_unspecified() => SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), local_addr.port()),
+        IpAddr::V6(ip) if ip.is_unspecified() => SocketAddr::new(IpAddr::V6(Ipv6Addr::LOCALHOST), local_addr.port()),
+        _ => local_addr
+    }
+}
+
+/// 服务端当前持有的连接
+///
+/// 每个连接在工作线程中握手、读取及处理请求期间标记为忙碌，在事件循环中等待请求期间为空闲。
+/// 停止后空闲连接被立即关闭，忙碌连接在请求处理完成后关闭
+pub(crate) struct Connections {
+    inner: Mutex<Inner>,
+}
+
+struct Inner {
+    stopping: bool,
+    counter: u64,
+    streams: HashMap<u64, Tracked>,
+}
+
+struct Tracked {
+    stream: TcpStream,
+    busy: bool,
+}
+
+impl Connections {
+    pub(crate) fn new() -> Arc<Self> {
+        Arc::new(Connections { inner: Mutex::new(Inner { stopping: false, counter: 0, streams: HashMap::new() }) })
+    }
+
+    /// 登记新连接，服务已停止时返回None，释放返回值即注销连接
+    pub(crate) fn register(self: &Arc<Self>, stream: &TcpStream) -> StarryResult<Option<Registration>> {
+        let stream = match stream.try_clone() {
+            Ok(src) => src,
+            Err(err) => return Err(Errs::strs("server tcp stream clone in register failed!", err))
+        };
+        let mut inner = self.inner.lock().unwrap();
+        if inner.stopping {
+            return Ok(None);
+        }
+        inner.counter += 1;
+        let id = inner.counter;
+        inner.streams.insert(id, Tracked { stream, busy: false });
+        Ok(Some(Registration { id, connections: self.clone() }))
+    }
+
+    pub(crate) fn is_stopping(&self) -> bool {
+        self.inner.lock().unwrap().stopping
+    }
+
+    fn len(&self) -> usize {
+        self.inner.lock().unwrap().streams.len()
+    }
+
+    /// 停止服务，关闭所有空闲连接
+    pub(crate) fn stop(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.stopping = true;
+        for tracked in inner.streams.values().filter(|tracked| !tracked.busy) {
+            tracked.stream.shutdown(Shutdown::Both).unwrap_or(())
+        }
+    }
+
+    /// 等待所有连接注销，超过deadline时返回false
+    fn wait(&self, deadline: Instant) -> bool {
+        loop {
+            if self.inner.lock().unwrap().streams.is_empty() {
+                return true;
+            }
+            if Instant::now() >= deadline {
+                return false;
+            }
+            Thread::sleep(Duration::from_millis(5))
+        }
+    }
+
+    /// 强制关闭剩余的全部连接
+    fn force(&self) {
+        for tracked in self.inner.lock().unwrap().streams.values() {
+            tracked.stream.shutdown(Shutdown::Both).unwrap_or(())
+        }
+    }
+
+    /// 更新连接状态，返回服务是否已停止
+    fn set_busy(&self, id: u64, busy: bool) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(tracked) = inner.streams.get_mut(&id) {
+            tracked.busy = busy
+        }
+        inner.stopping
+    }
+}
+
+/// 已登记的连接，释放时注销
+pub(crate) struct Registration {
+    id: u64,
+    connections: Arc<Connections>,
+}
+
+impl Registration {
+    /// 连接交由工作线程处理，返回服务是否已停止，已停止时本次请求处理完成后应关闭连接
+    pub(crate) fn busy(&self) -> bool {
+        self.connections.set_busy(self.id, true)
+    }
+
+    /// 连接即将交还事件循环等待下一个请求，返回服务是否已停止，已停止时应关闭连接
+    pub(crate) fn idle(&self) -> bool {
+        self.connections.set_busy(self.id, false)
+    }
+
+    /// 服务是否已停止
+    pub(crate) fn is_stopping(&self) -> bool {
+        self.connections.is_stopping()
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        self.connections.inner.lock().unwrap().streams.remove(&self.id);
+    }
+}
+
+#[cfg(test)]
+mod handle_test {
+    use std::net::{TcpListener, TcpStream};
+    use std::time::{Duration, Instant};
+
+    use crate::http::stream::HttpStream;
+    use crate::server::handle::Connections;
+
+    #[test]
+    fn connections() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let _c1 = TcpStream::connect(addr).unwrap();
+        let _c2 = TcpStream::connect(addr).unwrap();
+        let (t1, t2) = (listener.accept().unwrap().0, listener.accept().unwrap().0);
+        let connections = Connections::new();
+        let r1 = connections.register(&t1).unwrap().unwrap();
+        let r2 = connections.register(&t2).unwrap().unwrap();
+        let (s1, s2) = (HttpStream::Tcp(t1.try_clone().unwrap()), HttpStream::Tcp(t2));
+        assert!(!r1.busy());
+        connections.stop();
+        assert!(connections.register(&t1).unwrap().is_none());
+        // 空闲连接被关闭，忙碌连接不受影响
+        assert!(!s2.is_alive());
+        assert!(s1.is_alive());
+        drop(r2);
+        assert!(!connections.wait(Instant::now() + Duration::from_millis(20)));
+        assert!(r1.idle());
+        drop(r1);
+        assert!(connections.wait(Instant::now()));
+    }
+}
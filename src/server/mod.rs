@@ -14,8 +14,10 @@
 
 pub use context::Context;
 pub use extend::Extend;
+pub use handle::ServerHandle;
 pub use router::Router;
-pub use server::HttpServer;
+pub use server::{BoundServer, HttpServer};
+pub use sse::{Event, EventSender};
 
 pub(crate) mod server;
 pub(crate) mod context;
@@ -23,3 +25,8 @@ pub(crate) mod node;
 pub(crate) mod router;
 pub(crate) mod limit;
 pub(crate) mod extend;
+pub(crate) mod state;
+pub(crate) mod statics;
+pub(crate) mod sse;
+pub(crate) mod handle;
+pub(crate) mod reactor;
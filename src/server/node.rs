@@ -14,8 +14,9 @@
 
 use std::collections::HashMap;
 use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
 
-use crate::Method;
+use crate::{Context, Method, Status};
 use crate::server::Extend;
 use crate::server::router::Handler;
 use crate::utils::concurrent::Thread;
@@ -57,7 +58,7 @@ impl Root {
 
     /// 新增节点
     ///
-    /// * pattern 资源样式，如`/a/b/:c/d/:e/:f/g`
+    /// * pattern 资源样式，如`/a/b/:c/d/:e/:f/g`，最后一段可以是通配资源，如`/a/b/*path`
     /// * method 请求方法
     /// * handler 待实现接收请求方法
     /// * filters 过滤器/拦截器数组
@@ -80,9 +81,66 @@ impl Root {
 
     /// 获取节点
     ///
+    /// 请求方法未注册时，按如下方式处理：
+    /// * HEAD 使用GET对应节点，回复时不写入数据体
+    /// * OPTIONS 资源存在于其它请求方法时，自动回复"Allow"
+    /// * 其它 资源存在于其它请求方法时，回复405及"Allow"
+    ///
     /// * pattern 资源样式，如`/a/b/c/d/e/f/g`
     /// * method 请求方法
     pub(crate) fn fetch(&self, pattern: String, method: Method) -> Option<(Node, HashMap<String, String>)> {
+        if let Some(res) = self.fetch_method(pattern.clone(), method.clone()) {
+            return Some(res);
+        }
+        if method == Method::HEAD {
+            if let Some(res) = self.fetch_method(pattern.clone(), Method::GET) {
+                return Some(res);
+            }
+        }
+        let allow = self.allow(pattern);
+        if allow.is_empty() {
+            return None;
+        }
+        let allow = allow.iter().map(|method| method.as_str()).collect::<Vec<&str>>().join(", ");
+        let handler: Handler = match method {
+            Method::OPTIONS => Arc::new(move |context: &mut Context| {
+                context.resp_set_header_str("Allow", &allow);
+                context.response()
+            }),
+            _ => Arc::new(move |context: &mut Context| {
+                context.resp_status(Status::METHOD_NOT_ALLOWED);
+                context.resp_set_header_str("Allow", &allow);
+                context.response()
+            })
+        };
+        Some((Node::from_handler(handler), HashMap::new()))
+    }
+
+    /// 获取资源已注册的全部请求方法，资源不存在时返回空
+    ///
+    /// 注册了GET的资源同样允许HEAD，资源存在时总是允许OPTIONS
+    ///
+    /// * pattern 资源样式，如`/a/b/c/d/e/f/g`
+    pub(crate) fn allow(&self, pattern: String) -> Vec<Method> {
+        let mut res = vec![];
+        for method in vec![Method::GET, Method::HEAD, Method::POST, Method::PUT, Method::DELETE, Method::PATCH,
+                           Method::TRACE, Method::CONNECT, Method::LINK, Method::UNLINK, Method::PRI] {
+            if self.fetch_method(pattern.clone(), method.clone()).is_some()
+                || (method == Method::HEAD && res.contains(&Method::GET)) {
+                res.push(method)
+            }
+        }
+        if !res.is_empty() || self.fetch_method(pattern, Method::OPTIONS).is_some() {
+            res.push(Method::OPTIONS)
+        }
+        res
+    }
+
+    /// 获取指定请求方法下的节点
+    ///
+    /// * pattern 资源样式，如`/a/b/c/d/e/f/g`
+    /// * method 请求方法
+    fn fetch_method(&self, pattern: String, method: Method) -> Option<(Node, HashMap<String, String>)> {
         match method {
             Method::OPTIONS => self.root_option.fetch(pattern),
             Method::GET => self.root_get.fetch(pattern),
@@ -153,8 +211,20 @@ impl Node {
         }
     }
 
+    /// 不属于资源树的节点，用于回复自动处理的请求，如OPTIONS及405
+    fn from_handler(handler: Handler) -> Self {
+        Node {
+            pattern: None,
+            pattern_piece: "".to_string(),
+            pattern_piece_value: None,
+            handler: Some(handler),
+            extend: None,
+            next_nodes: vec![],
+        }
+    }
+
     pub(crate) fn handler(&self) -> Handler {
-        self.handler.unwrap()
+        self.handler.clone().unwrap()
     }
 
     /// 新增节点
@@ -188,6 +258,13 @@ impl Node {
         if pattern_piece.starts_with(":") {
             pattern_piece_value = Some(pattern_piece[1..].to_string());
             pattern_piece = "?".to_string()
+        } else if pattern_piece.starts_with("*") {
+            // 通配资源会匹配剩余的全部路径，只能作为资源样式的最后一段
+            if pattern_piece.len() == 1 || index + 1 != pattern_split.len() {
+                panic!("catch-all segment must be named and at the end of path, pattern is {}", pattern)
+            }
+            pattern_piece_value = Some(pattern_piece[1..].to_string());
+            pattern_piece = "*".to_string()
         } else {
             pattern_piece_value = None
         }
@@ -267,42 +344,50 @@ impl Node {
 
     /// 获取可执行服务节点
     ///
+    /// 子结点按照固定资源、匹配资源、通配资源的优先级依次尝试，与注册顺序无关。
+    /// 高优先级子结点后续无法匹配时，会回退尝试低优先级子结点
+    ///
     /// * pattern 资源样式，如`/a/b/c/d/e/f/g`
     fn fetch_fn(&self, pattern: String, pattern_split: Vec<&str>, pattern_split_len: usize,
                 mut index: usize) -> Option<(Self, HashMap<String, String>)> {
         let pattern_piece = pattern_split[index];
-        // if pattern_piece.starts_with(":") {
-        //     pattern_piece = "?";
-        // }
         index += 1;
-        // 遍历子结点是否存在相同资源
-        for next_node in self.next_nodes.iter() {
-            // 如果有，则继续
-            if next_node.pattern_piece.eq(pattern_piece) {
-                let (is_self, src) = next_node.fetch_split(
-                    pattern.clone(), pattern_split.clone(), pattern_split_len, index);
-                if is_self {
-                    return Some((next_node.clone(), HashMap::new()));
-                } else {
-                    match src {
-                        Some(res) => return Some(res),
-                        None => continue
-                    }
-                }
-            } else if next_node.pattern_piece.eq("?") {
-                let (is_self, src) = next_node.fetch_split(
-                    pattern.clone(), pattern_split.clone(), pattern_split_len, index);
-                if is_self { // 如果是子结点，则新建fields集合，开始逆向填充
-                    let mut fields = HashMap::new();
-                    fields.insert(next_node.pattern_piece_value.clone().unwrap(), pattern_piece.to_string());
-                    return Some((next_node.clone(), fields));
-                } else { // 如果不是子结点，则执行逆向填充
-                    match src {
-                        Some((node, mut fields)) => {
+        for priority in 0..3 {
+            // 遍历当前优先级的子结点是否存在可匹配资源
+            for next_node in self.next_nodes.iter().filter(|node| node.priority() == priority) {
+                match priority {
+                    // 固定资源，需要与请求资源完全相同
+                    0 => if next_node.pattern_piece.eq(pattern_piece) {
+                        let (is_self, src) = next_node.fetch_split(
+                            pattern.clone(), pattern_split.clone(), pattern_split_len, index);
+                        if is_self {
+                            if next_node.handler.is_some() {
+                                return Some((next_node.clone(), HashMap::new()));
+                            }
+                        } else if let Some(res) = src {
+                            return Some(res);
+                        }
+                    },
+                    // 匹配资源
+                    1 => {
+                        let (is_self, src) = next_node.fetch_split(
+                            pattern.clone(), pattern_split.clone(), pattern_split_len, index);
+                        if is_self { // 如果是子结点，则新建fields集合，开始逆向填充
+                            if next_node.handler.is_some() {
+                                let mut fields = HashMap::new();
+                                fields.insert(next_node.pattern_piece_value.clone().unwrap(), pattern_piece.to_string());
+                                return Some((next_node.clone(), fields));
+                            }
+                        } else if let Some((node, mut fields)) = src { // 如果不是子结点，则执行逆向填充
                             fields.insert(next_node.pattern_piece_value.clone().unwrap(), pattern_piece.to_string());
                             return Some((node, fields));
                         }
-                        None => continue
+                    }
+                    // 通配资源捕获剩余的全部路径，包括其中的"/"
+                    _ => {
+                        let mut fields = HashMap::new();
+                        fields.insert(next_node.pattern_piece_value.clone().unwrap(), pattern_split[index - 1..].join("/"));
+                        return Some((next_node.clone(), fields));
                     }
                 }
             }
@@ -311,6 +396,19 @@ impl Node {
         None
     }
 
+    /// 资源匹配优先级，数值越小越优先
+    ///
+    /// * 0 固定资源，如`a`
+    /// * 1 匹配资源，如`:a`
+    /// * 2 通配资源，如`*a`
+    fn priority(&self) -> u8 {
+        match self.pattern_piece.as_str() {
+            "?" if self.pattern_piece_value.is_some() => 1,
+            "*" if self.pattern_piece_value.is_some() => 2,
+            _ => 0
+        }
+    }
+
     /// 获取可执行服务节点
     ///
     /// * pattern 资源样式，如`/a/b/c/d/e/f/g`
@@ -342,16 +440,18 @@ impl Debug for Node {
 #[cfg(test)]
 mod node_test {
     use crate::{Context, Method};
+    use std::sync::Arc;
+
     use crate::server::node::Root;
 
     #[test]
     fn node_test() {
         let mut root = Root::new();
-        root.add("/a/b/:c/d/:e/:f/g".to_string(), Method::POST, h1, None);
-        root.add("/a/b/:c/d/e/f".to_string(), Method::POST, h1, None);
-        root.add("/a/b/:c/d/:e/:f/g".to_string(), Method::GET, h2, None);
-        root.add("/a/c/:c/d/:e/:f/g".to_string(), Method::GET, h2, None);
-        root.add("/a/b/:c/d/:e/:f/g".to_string(), Method::PUT, h3, None);
+        root.add("/a/b/:c/d/:e/:f/g".to_string(), Method::POST, Arc::new(h1), None);
+        root.add("/a/b/:c/d/e/f".to_string(), Method::POST, Arc::new(h1), None);
+        root.add("/a/b/:c/d/:e/:f/g".to_string(), Method::GET, Arc::new(h2), None);
+        root.add("/a/c/:c/d/:e/:f/g".to_string(), Method::GET, Arc::new(h2), None);
+        root.add("/a/b/:c/d/:e/:f/g".to_string(), Method::PUT, Arc::new(h3), None);
 
         assert!(root.root_option.next_nodes.is_empty());
         assert!(root.root_delete.next_nodes.is_empty());
@@ -369,12 +469,12 @@ mod node_test {
     #[test]
     fn node_match_test1() {
         let mut root = Root::new();
-        root.add("/a/b/:c/d/:e/:f/g".to_string(), Method::POST, h1, None);
-        root.add("/a/b/:c/d/e/f".to_string(), Method::POST, h1, None);
-        root.add("/a/b/:c/d/:e/:f/g".to_string(), Method::GET, h2, None);
-        root.add("/a/c/:c/d/:e/:f/g".to_string(), Method::GET, h2, None);
-        root.add("/a/b/:c/d/:e/:f/g".to_string(), Method::PUT, h3, None);
-        root.add("/a/b/:c/d/:e/:f/g/:h".to_string(), Method::PUT, h3, None);
+        root.add("/a/b/:c/d/:e/:f/g".to_string(), Method::POST, Arc::new(h1), None);
+        root.add("/a/b/:c/d/e/f".to_string(), Method::POST, Arc::new(h1), None);
+        root.add("/a/b/:c/d/:e/:f/g".to_string(), Method::GET, Arc::new(h2), None);
+        root.add("/a/c/:c/d/:e/:f/g".to_string(), Method::GET, Arc::new(h2), None);
+        root.add("/a/b/:c/d/:e/:f/g".to_string(), Method::PUT, Arc::new(h3), None);
+        root.add("/a/b/:c/d/:e/:f/g/:h".to_string(), Method::PUT, Arc::new(h3), None);
 
         assert_eq!(root.root_post.next_nodes.len(), 1);
         assert_eq!(root.root_post.next_nodes[0].pattern_piece, "a");
@@ -401,9 +501,9 @@ mod node_test {
     #[test]
     fn node_match_test2() {
         let mut root = Root::new();
-        root.add("/a/b/:c/d/:e/:f/g".to_string(), Method::GET, h2, None);
-        root.add("/a/c/:c/d/:e/:f/g".to_string(), Method::GET, h2, None);
-        root.add("/d/b/:c/d/:e/:f/g".to_string(), Method::GET, h3, None);
+        root.add("/a/b/:c/d/:e/:f/g".to_string(), Method::GET, Arc::new(h2), None);
+        root.add("/a/c/:c/d/:e/:f/g".to_string(), Method::GET, Arc::new(h2), None);
+        root.add("/d/b/:c/d/:e/:f/g".to_string(), Method::GET, Arc::new(h3), None);
 
         assert_eq!(root.root_get.next_nodes.len(), 2);
         assert_eq!(root.root_get.next_nodes[0].pattern_piece, "a");
@@ -426,13 +526,13 @@ mod node_test {
     #[test]
     fn node_fetch_test() {
         let mut root = Root::new();
-        root.add("/a/b/:c/d/:e/:f/g".to_string(), Method::POST, h1, None);
-        root.add("/a/b/:c/d/e/f".to_string(), Method::POST, h2, None);
-        root.add("/a/b/:c/d/:e/:f/g".to_string(), Method::GET, h1, None);
-        root.add("/a/c/:c/d/:e/:f/g".to_string(), Method::GET, h2, None);
-        root.add("/d/b/:c/d/:e/:f/g".to_string(), Method::GET, h3, None);
-        root.add("/a/b/:c/d/:e/:f/g".to_string(), Method::PUT, h1, None);
-        root.add("/a/b/:c/d/:e/:f/g/:h".to_string(), Method::PUT, h1, None);
+        root.add("/a/b/:c/d/:e/:f/g".to_string(), Method::POST, Arc::new(h1), None);
+        root.add("/a/b/:c/d/e/f".to_string(), Method::POST, Arc::new(h2), None);
+        root.add("/a/b/:c/d/:e/:f/g".to_string(), Method::GET, Arc::new(h1), None);
+        root.add("/a/c/:c/d/:e/:f/g".to_string(), Method::GET, Arc::new(h2), None);
+        root.add("/d/b/:c/d/:e/:f/g".to_string(), Method::GET, Arc::new(h3), None);
+        root.add("/a/b/:c/d/:e/:f/g".to_string(), Method::PUT, Arc::new(h1), None);
+        root.add("/a/b/:c/d/:e/:f/g/:h".to_string(), Method::PUT, Arc::new(h1), None);
 
         // let (n1, _fields) = root.fetch("/a/b/c/d/e/f/g".to_string(), Method::POST).unwrap();
         // assert_eq!(n1.handler, root.root_post.next_nodes[0].next_nodes[0].next_nodes[0].next_nodes[0].next_nodes[0].next_nodes[0].next_nodes[0].handler);
@@ -441,6 +541,70 @@ mod node_test {
         assert_eq!(n1, root.root_put.next_nodes[0].next_nodes[0].next_nodes[0].next_nodes[0].next_nodes[0].next_nodes[0].next_nodes[0].next_nodes[0]);
     }
 
+    #[test]
+    fn node_fetch_catch_all_test() {
+        let mut root = Root::new();
+        root.add("/assets/*filepath".to_string(), Method::GET, Arc::new(h1), None);
+
+        let (n1, fields) = root.fetch("/assets/css/site.css".to_string(), Method::GET).unwrap();
+        assert_eq!(n1, root.root_get.next_nodes[0].next_nodes[0]);
+        assert_eq!(fields.get("filepath").unwrap(), "css/site.css");
+        let (_n2, fields) = root.fetch("/assets/".to_string(), Method::GET).unwrap();
+        assert_eq!(fields.get("filepath").unwrap(), "");
+    }
+
+    #[test]
+    fn node_fetch_priority_test() {
+        let mut root = Root::new();
+        root.add("/a/*rest".to_string(), Method::GET, Arc::new(h3), None);
+        root.add("/a/b/:c/d/:e/:f/g".to_string(), Method::GET, Arc::new(h1), None);
+        root.add("/a/b/x/d/y/z/g".to_string(), Method::GET, Arc::new(h2), None);
+        root.add("/a/:b/m".to_string(), Method::GET, Arc::new(h2), None);
+
+        // 固定资源优先于匹配资源，与注册顺序无关
+        let (n1, fields) = root.fetch("/a/b/x/d/y/z/g".to_string(), Method::GET).unwrap();
+        assert_eq!(n1.pattern.unwrap(), "/a/b/x/d/y/z/g");
+        assert!(fields.is_empty());
+        // 固定资源后续无法匹配时回退至匹配资源
+        let (n2, fields) = root.fetch("/a/b/x/d/y/q/g".to_string(), Method::GET).unwrap();
+        assert_eq!(n2.pattern.unwrap(), "/a/b/:c/d/:e/:f/g");
+        assert_eq!(fields.get("c").unwrap(), "x");
+        assert_eq!(fields.get("f").unwrap(), "q");
+        let (n3, fields) = root.fetch("/a/b/m".to_string(), Method::GET).unwrap();
+        assert_eq!(n3.pattern.unwrap(), "/a/:b/m");
+        assert_eq!(fields.get("b").unwrap(), "b");
+        // 匹配资源均无法匹配时，由通配资源捕获剩余路径
+        let (n4, fields) = root.fetch("/a/b/x/d".to_string(), Method::GET).unwrap();
+        assert_eq!(n4.pattern.unwrap(), "/a/*rest");
+        assert_eq!(fields.get("rest").unwrap(), "b/x/d");
+        assert_eq!(fields.len(), 1);
+    }
+
+    #[test]
+    fn node_allow_test() {
+        let mut root = Root::new();
+        root.add("/a/:b".to_string(), Method::GET, Arc::new(h1), None);
+        root.add("/a/b".to_string(), Method::PUT, Arc::new(h2), None);
+
+        assert_eq!(root.allow("/a/b".to_string()), vec![Method::GET, Method::HEAD, Method::PUT, Method::OPTIONS]);
+        assert_eq!(root.allow("/a/c".to_string()), vec![Method::GET, Method::HEAD, Method::OPTIONS]);
+        assert!(root.allow("/b".to_string()).is_empty());
+        // HEAD回退至GET对应节点，未注册的请求方法返回自动处理节点
+        let (n1, fields) = root.fetch("/a/c".to_string(), Method::HEAD).unwrap();
+        assert_eq!(n1, root.root_get.next_nodes[0].next_nodes[0]);
+        assert_eq!(fields.get("b").unwrap(), "c");
+        let (n2, _fields) = root.fetch("/a/c".to_string(), Method::POST).unwrap();
+        assert!(n2.pattern.is_none() && n2.handler.is_some());
+        assert!(root.fetch("/b".to_string(), Method::POST).is_none());
+    }
+
+    #[test]
+    #[should_panic]
+    fn node_catch_all_not_last_test() {
+        let mut root = Root::new();
+        root.add("/assets/*filepath/a".to_string(), Method::GET, Arc::new(h1), None);
+    }
+
     fn h1(_context: &mut Context) {}
 
     fn h2(_context: &mut Context) {}
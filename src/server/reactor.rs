@@ -0,0 +1,387 @@
+/*
+ * Copyright (c) 2021. Aberic - All Rights Reserved.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! 等待请求的连接由单个事件循环线程统一监听，连接可读时才交由线程池解析及处理请求，
+//! 请求处理完成后再交还事件循环，因此空闲的keep-alive连接不占用工作线程
+//!
+//! 每个等待中的连接都有一个超时定时器，新连接使用首个请求的等待时间，keep-alive连接使用空闲时间，
+//! 超时未收到请求的连接由事件循环关闭
+
+use std::collections::{BTreeSet, HashMap};
+use std::io::ErrorKind;
+use std::net::{Shutdown, TcpStream};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use mio::{Events, Interest, Poll, Token, Waker};
+use mio::unix::SourceFd;
+
+use crate::http::stream::HttpStream;
+use crate::http::url::authority::Addr;
+use crate::server::handle::Registration;
+use crate::utils::concurrent::Thread;
+use crate::utils::errors::{Errs, StarryResult};
+
+/// 唤醒事件循环所使用的标识，连接的标识从1开始
+const WAKER: Token = Token(0);
+
+/// 连接可读时的处理方法，通常将连接交由线程池处理
+pub(crate) type Dispatch = Box<dyn Fn(Conn, Reactor) + Send>;
+
+/// 等待下一个请求的连接
+pub(crate) struct Conn {
+    pub(crate) socket: Socket,
+    pub(crate) peer: Addr,
+    pub(crate) local: Addr,
+    /// 连接登记信息，随连接一同释放
+    pub(crate) registration: Registration,
+}
+
+pub(crate) enum Socket {
+    /// 已接收但尚未完成tls握手的连接，可读即表示客户端已发起握手
+    Handshake(TcpStream),
+    /// 可以直接读取请求的连接
+    Ready(HttpStream),
+}
+
+impl Conn {
+    fn raw_fd(&self) -> RawFd {
+        match &self.socket {
+            Socket::Handshake(src) => src.as_raw_fd(),
+            Socket::Ready(src) => src.as_raw_fd(),
+        }
+    }
+
+    /// 是否已有待读取的数据，此时无需等待可读事件
+    fn has_buffered(&self) -> bool {
+        match &self.socket {
+            Socket::Handshake(_) => false,
+            Socket::Ready(src) => src.has_buffered(),
+        }
+    }
+
+    /// 设置连接的读写超时，None表示不超时
+    pub(crate) fn set_timeout(&self, dur: Option<Duration>) -> std::io::Result<()> {
+        match &self.socket {
+            Socket::Handshake(src) => src.set_read_timeout(dur).and(src.set_write_timeout(dur)),
+            Socket::Ready(src) => src.set_read_timeout(dur).and(src.set_write_timeout(dur)),
+        }
+    }
+
+    fn close(self) {
+        log::debug!("server reactor close idle stream {}!", self.peer.to_string());
+        let res = match &self.socket {
+            Socket::Handshake(src) => src.shutdown(Shutdown::Both),
+            Socket::Ready(src) => src.shutdown(Shutdown::Both),
+        };
+        match res {
+            Ok(()) => {}
+            Err(err) => log::trace!("server reactor stream {} shutdown failed! {}", self.peer.to_string(), err)
+        }
+    }
+}
+
+/// 事件循环句柄，克隆后共享同一个事件循环
+#[derive(Clone)]
+pub(crate) struct Reactor {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    waker: Waker,
+    queue: Mutex<Queue>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+/// 待事件循环处理的命令
+struct Queue {
+    stopped: bool,
+    commands: Vec<Command>,
+}
+
+enum Command {
+    /// 新接收的连接，等待首个请求
+    Accept(Conn),
+    /// 处理完请求的连接，等待下一个请求
+    Park(Conn),
+    Stop,
+}
+
+impl Reactor {
+    /// 启动事件循环线程
+    ///
+    /// * idle_timeout 连接等待下一个请求的最长时间，超时后关闭连接
+    /// * request_timeout 新连接等待首个请求的最长时间，超时后关闭连接
+    /// * dispatch 连接可读时的处理方法，在事件循环线程中执行，不应阻塞
+    pub(crate) fn start(idle_timeout: Duration, request_timeout: Duration, dispatch: Dispatch) -> StarryResult<Reactor> {
+        let poll = match Poll::new() {
+            Ok(src) => src,
+            Err(err) => return Err(Errs::strs("server reactor poll create failed!", err))
+        };
+        let waker = match Waker::new(poll.registry(), WAKER) {
+            Ok(src) => src,
+            Err(err) => return Err(Errs::strs("server reactor waker create failed!", err))
+        };
+        let reactor = Reactor {
+            shared: Arc::new(Shared {
+                waker,
+                queue: Mutex::new(Queue { stopped: false, commands: vec![] }),
+                thread: Mutex::new(None),
+            })
+        };
+        let event_loop = EventLoop {
+            poll,
+            idle_timeout,
+            request_timeout,
+            dispatch,
+            counter: 0,
+            conns: HashMap::new(),
+            timers: BTreeSet::new(),
+        };
+        let reactor_c = reactor.clone();
+        let thread = Thread::spawn_on_name("starry-http-reactor".to_string(), move || event_loop.run(reactor_c))?;
+        *reactor.shared.thread.lock().unwrap() = Some(thread);
+        Ok(reactor)
+    }
+
+    /// 将新接收的连接交由事件循环等待首个请求，事件循环已停止时直接关闭连接
+    pub(crate) fn accept(&self, conn: Conn) {
+        self.send(Command::Accept(conn))
+    }
+
+    /// 将连接交由事件循环等待下一个请求，事件循环已停止时直接关闭连接
+    pub(crate) fn park(&self, conn: Conn) {
+        self.send(Command::Park(conn))
+    }
+
+    /// 停止事件循环，关闭所有等待中的连接，并等待事件循环线程退出
+    pub(crate) fn stop(&self) {
+        self.send(Command::Stop);
+        let thread = self.shared.thread.lock().unwrap().take();
+        if let Some(src) = thread {
+            match src.join() {
+                Ok(()) => {}
+                Err(_) => log::error!("server reactor thread panicked!")
+            }
+        }
+    }
+
+    fn send(&self, command: Command) {
+        {
+            let mut queue = self.shared.queue.lock().unwrap();
+            if queue.stopped {
+                match command {
+                    Command::Accept(conn) | Command::Park(conn) => conn.close(),
+                    Command::Stop => {}
+                }
+                return;
+            }
+            match command {
+                Command::Stop => queue.stopped = true,
+                Command::Accept(_) | Command::Park(_) => {}
+            }
+            queue.commands.push(command);
+        }
+        match self.shared.waker.wake() {
+            Ok(()) => {}
+            Err(err) => log::error!("server reactor wake failed! {}", err)
+        }
+    }
+
+    /// 取出待处理的命令，事件循环异常退出时标记为已停止
+    fn take(&self, stop: bool) -> Vec<Command> {
+        let mut queue = self.shared.queue.lock().unwrap();
+        queue.stopped = queue.stopped || stop;
+        std::mem::take(&mut queue.commands)
+    }
+}
+
+struct EventLoop {
+    poll: Poll,
+    idle_timeout: Duration,
+    request_timeout: Duration,
+    dispatch: Dispatch,
+    /// 最近分配的连接标识
+    counter: usize,
+    /// 等待中的连接及其超时时间
+    conns: HashMap<Token, (Conn, Instant)>,
+    /// 按超时时间排序的定时器
+    timers: BTreeSet<(Instant, Token)>,
+}
+
+impl EventLoop {
+    fn run(mut self, reactor: Reactor) {
+        let mut events = Events::with_capacity(1024);
+        loop {
+            let timeout = self.timers.iter().next().map(|(deadline, _)| deadline.saturating_duration_since(Instant::now()));
+            match self.poll.poll(&mut events, timeout) {
+                Ok(()) => {}
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) => {
+                    log::error!("server reactor poll failed! {}", err);
+                    break;
+                }
+            }
+            for event in events.iter() {
+                if event.token() == WAKER {
+                    continue;
+                }
+                // 可读、对端关闭或出错均交由处理方法，由其读取请求或发现连接已关闭
+                if let Some(conn) = self.remove(event.token()) {
+                    (self.dispatch)(conn, reactor.clone())
+                }
+            }
+            for command in reactor.take(false) {
+                match command {
+                    Command::Accept(conn) => self.park(conn, self.request_timeout, &reactor),
+                    Command::Park(conn) => self.park(conn, self.idle_timeout, &reactor),
+                    Command::Stop => {
+                        self.close_all();
+                        return;
+                    }
+                }
+            }
+            self.expire();
+        }
+        for command in reactor.take(true) {
+            match command {
+                Command::Accept(conn) | Command::Park(conn) => conn.close(),
+                Command::Stop => {}
+            }
+        }
+        self.close_all()
+    }
+
+    /// 监听连接可读事件，超过timeout仍不可读时关闭连接
+    fn park(&mut self, conn: Conn, timeout: Duration, reactor: &Reactor) {
+        if conn.has_buffered() {
+            (self.dispatch)(conn, reactor.clone());
+            return;
+        }
+        self.counter = self.counter.wrapping_add(1).max(1);
+        let token = Token(self.counter);
+        // 连接已有数据时注册后会立即产生可读事件
+        match self.poll.registry().register(&mut SourceFd(&conn.raw_fd()), token, Interest::READABLE) {
+            Ok(()) => {
+                let deadline = Instant::now() + timeout;
+                self.timers.insert((deadline, token));
+                self.conns.insert(token, (conn, deadline));
+            }
+            Err(err) => {
+                log::error!("server reactor register stream {} failed! {}", conn.peer.to_string(), err);
+                conn.close()
+            }
+        }
+    }
+
+    /// 移除等待中的连接，并取消其定时器及事件监听
+    fn remove(&mut self, token: Token) -> Option<Conn> {
+        match self.conns.remove(&token) {
+            Some((conn, deadline)) => {
+                self.timers.remove(&(deadline, token));
+                match self.poll.registry().deregister(&mut SourceFd(&conn.raw_fd())) {
+                    Ok(()) => {}
+                    Err(err) => log::trace!("server reactor deregister stream {} failed! {}", conn.peer.to_string(), err)
+                }
+                Some(conn)
+            }
+            None => None
+        }
+    }
+
+    /// 关闭等待超时的连接
+    fn expire(&mut self) {
+        let now = Instant::now();
+        let expired: Vec<Token> = self.timers.iter()
+            .take_while(|(deadline, _)| *deadline <= now)
+            .map(|(_, token)| *token)
+            .collect();
+        for token in expired {
+            if let Some(conn) = self.remove(token) {
+                conn.close()
+            }
+        }
+    }
+
+    fn close_all(&mut self) {
+        let tokens: Vec<Token> = self.conns.keys().cloned().collect();
+        for token in tokens {
+            if let Some(conn) = self.remove(token) {
+                conn.close()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod reactor_test {
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::time::Duration;
+
+    use crate::http::stream::HttpStream;
+    use crate::http::url::authority::Addr;
+    use crate::server::handle::Connections;
+    use crate::server::reactor::{Conn, Reactor, Socket};
+    use crate::utils::concurrent::Thread;
+
+    #[test]
+    fn park() {
+        let (tx, rx) = crossbeam::channel::unbounded();
+        let reactor = Reactor::start(Duration::from_millis(200), Duration::from_secs(2),
+                                     Box::new(move |conn: Conn, _| tx.send(conn).unwrap())).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let connections = Connections::new();
+        let accept = |client: &TcpStream| {
+            let stream = listener.accept().unwrap().0;
+            let registration = connections.register(&stream).unwrap().unwrap();
+            let addr = client.local_addr().unwrap();
+            let peer = Addr::from(addr.ip().to_string(), addr.port());
+            Conn { socket: Socket::Ready(HttpStream::Tcp(stream)), peer: peer.clone(), local: peer, registration }
+        };
+        let addr = listener.local_addr().unwrap();
+
+        // 连接可读时交由处理方法
+        let mut readable = TcpStream::connect(addr).unwrap();
+        reactor.accept(accept(&readable));
+        Thread::sleep(Duration::from_millis(50));
+        assert!(rx.try_recv().is_err());
+        readable.write_all(b"GET").unwrap();
+        let conn = rx.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert_eq!(conn.peer.port(), readable.local_addr().unwrap().port());
+
+        // 空闲超时的连接被关闭，新连接使用首个请求的等待时间
+        let mut idle = TcpStream::connect(addr).unwrap();
+        idle.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+        reactor.park(accept(&idle));
+        let mut fresh = TcpStream::connect(addr).unwrap();
+        fresh.set_read_timeout(Some(Duration::from_secs(3))).unwrap();
+        reactor.accept(accept(&fresh));
+        assert_eq!(idle.read(&mut [0; 1]).unwrap(), 0);
+        assert!(rx.try_recv().is_err());
+        fresh.write_all(b"GET").unwrap();
+        rx.recv_timeout(Duration::from_secs(1)).unwrap();
+
+        // 停止后关闭等待中及此后交还的连接
+        let mut parked = TcpStream::connect(addr).unwrap();
+        parked.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+        reactor.park(accept(&parked));
+        reactor.stop();
+        assert_eq!(parked.read(&mut [0; 1]).unwrap(), 0);
+        reactor.park(conn);
+        assert_eq!(readable.read(&mut [0; 1]).unwrap(), 0);
+    }
+}
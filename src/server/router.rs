@@ -14,16 +14,20 @@
 
 use std::fmt::{Debug, Formatter};
 use std::ops::Add;
+use std::path::PathBuf;
 use std::sync::{Arc, RwLock};
 
 use crate::{Context, Method};
 use crate::server::Extend;
 use crate::server::node::Root;
+use crate::server::statics;
 
 /// 待实现接收请求方法
 ///
+/// 可以是普通方法，也可以是捕获了数据库连接池、配置等共享状态的闭包
+///
 /// ctx 请求处理上下文结构
-pub(crate) type Handler = fn(context: &mut Context);
+pub(crate) type Handler = Arc<dyn Fn(&mut Context) + Send + Sync>;
 
 pub struct Router {
     /// 临时存储group值
@@ -46,16 +50,18 @@ impl Router {
     ///
     /// 过滤操作尽量不要对数据体里的信息进行校验之类的流程，最好是对path、header和cookie进行过滤
     ///
-    /// 服务资源尽量不要有重复前缀，如果存咋重复，则当匹配类型与固定类型重合时，会优先选择最先定义的资源，如下：
+    /// 资源匹配时，固定资源优先于匹配资源（如`:c`），匹配资源优先于通配资源（如`*rest`），与定义顺序无关，如下：
     /// ```res
     /// /a/b/:c/d/:e/:f/g
     /// /a/b/x/d/y/z/g
+    /// /a/*rest
     /// ```
-    /// 当接收到`/a/b/x/d/y/z/g`请求时，并不会真正触发第二行资源反馈，而是执行第一条资源
+    /// 当接收到`/a/b/x/d/y/z/g`请求时，执行第二条资源；接收到`/a/b/m/d/y/z/g`请求时，执行第一条资源；
+    /// 接收到`/a/m/n`请求时，执行第三条资源，且`rest`的值为`m/n`
     ///
     /// 资源长度越长、重复率越高对性能影响越大，应尽可能进行简便简短的设计，使得匹配机制执行一次即可获得期望的结果
     ///
-    /// * pattern 资源样式，如`/a/b/:c/d/:e/:f/g`
+    /// * pattern 资源样式，如`/a/b/:c/d/:e/:f/g`，最后一段可以是通配资源，如`/a/b/*rest`
     /// * method 请求方法
     /// * handler 待实现接收请求方法
     /// * filters 过滤器/拦截器数组
@@ -67,7 +73,7 @@ impl Router {
                 match self.extend.clone() {
                     Some(mut src2) => {
                         filters.append(&mut src2.filters);
-                        let downgrade = match src1.downgrade {
+                        let downgrade = match src1.downgrade.clone() {
                             Some(src) => Some(src),
                             None => match src2.downgrade {
                                 Some(src) => Some(src),
@@ -98,116 +104,167 @@ impl Router {
 
     /// 新增服务资源
     ///
-    /// 服务资源尽量不要有重复前缀，如果存咋重复，则当匹配类型与固定类型重合时，会优先选择最先定义的资源，如下：
+    /// 资源匹配时，固定资源优先于匹配资源（如`:c`），匹配资源优先于通配资源（如`*rest`），与定义顺序无关，如下：
     /// ```res
     /// /a/b/:c/d/:e/:f/g
     /// /a/b/x/d/y/z/g
+    /// /a/*rest
     /// ```
-    /// 当接收到`/a/b/x/d/y/z/g`请求时，并不会真正触发第二行资源反馈，而是执行第一条资源
+    /// 当接收到`/a/b/x/d/y/z/g`请求时，执行第二条资源；接收到`/a/b/m/d/y/z/g`请求时，执行第一条资源；
+    /// 接收到`/a/m/n`请求时，执行第三条资源，且`rest`的值为`m/n`
     ///
     /// 资源长度越长、重复率越高对性能影响越大，应尽可能进行简便简短的设计，使得匹配机制执行一次即可获得期望的结果
     ///
-    /// * pattern 资源样式，如`/a/b/:c/d/:e/:f/g`
+    /// * pattern 资源样式，如`/a/b/:c/d/:e/:f/g`，最后一段可以是通配资源，如`/a/b/*rest`
     /// * method 请求方法
     /// * handler 待实现接收请求方法
     fn repo(&self, pattern: &str, method: Method, handler: Handler) {
         self.repo_wf(pattern, method, handler, None)
     }
 
-    pub fn option_wf(&self, pattern: &str, handler: Handler, extend: Extend) {
-        self.repo_wf(pattern, Method::OPTIONS, handler, Some(extend))
+    /// 新增静态目录资源，将`pattern`之后的路径映射为`dir`下的文件，如`static_dir("/assets", "./public")`时，
+    /// `/assets/css/site.css`对应`./public/css/site.css`，请求目录时返回目录下的`index.html`
+    ///
+    /// 数据类型根据文件扩展名推测，支持"ETag"、"Last-Modified"协商缓存及"Range"范围请求，
+    /// 包含`..`等越权访问的路径（包括其百分号编码形式）会被拒绝
+    ///
+    /// * pattern 资源前缀，如`/assets`
+    /// * dir 静态文件所在目录
+    pub fn static_dir(&self, pattern: &str, dir: &str) {
+        let dir = PathBuf::from(dir);
+        let pattern = format!("{}/*{}", pattern.trim_end_matches('/'), statics::FILEPATH);
+        self.get(&pattern, move |context: &mut Context| statics::serve_dir(context, &dir))
+    }
+
+    /// 新增静态文件资源，如`static_file("/favicon.ico", "./public/favicon.ico")`
+    ///
+    /// 数据类型根据文件扩展名推测，支持"ETag"、"Last-Modified"协商缓存及"Range"范围请求
+    ///
+    /// * pattern 资源样式
+    /// * file 静态文件路径
+    pub fn static_file(&self, pattern: &str, file: &str) {
+        let file = PathBuf::from(file);
+        self.get(pattern, move |context: &mut Context| statics::serve_file(context, &file))
+    }
+
+    pub fn option_wf<F>(&self, pattern: &str, handler: F, extend: Extend)
+        where F: Fn(&mut Context) + Send + Sync + 'static {
+        self.repo_wf(pattern, Method::OPTIONS, Arc::new(handler), Some(extend))
     }
 
-    pub fn get_wf(&self, pattern: &str, handler: Handler, extend: Extend) {
-        self.repo_wf(pattern, Method::GET, handler, Some(extend))
+    pub fn get_wf<F>(&self, pattern: &str, handler: F, extend: Extend)
+        where F: Fn(&mut Context) + Send + Sync + 'static {
+        self.repo_wf(pattern, Method::GET, Arc::new(handler), Some(extend))
     }
 
-    pub fn post_wf(&self, pattern: &str, handler: Handler, extend: Extend) {
-        self.repo_wf(pattern, Method::POST, handler, Some(extend))
+    pub fn post_wf<F>(&self, pattern: &str, handler: F, extend: Extend)
+        where F: Fn(&mut Context) + Send + Sync + 'static {
+        self.repo_wf(pattern, Method::POST, Arc::new(handler), Some(extend))
     }
 
-    pub fn put_wf(&self, pattern: &str, handler: Handler, extend: Extend) {
-        self.repo_wf(pattern, Method::PUT, handler, Some(extend))
+    pub fn put_wf<F>(&self, pattern: &str, handler: F, extend: Extend)
+        where F: Fn(&mut Context) + Send + Sync + 'static {
+        self.repo_wf(pattern, Method::PUT, Arc::new(handler), Some(extend))
     }
 
-    pub fn delete_wf(&self, pattern: &str, handler: Handler, extend: Extend) {
-        self.repo_wf(pattern, Method::DELETE, handler, Some(extend))
+    pub fn delete_wf<F>(&self, pattern: &str, handler: F, extend: Extend)
+        where F: Fn(&mut Context) + Send + Sync + 'static {
+        self.repo_wf(pattern, Method::DELETE, Arc::new(handler), Some(extend))
     }
 
-    pub fn head_wf(&self, pattern: &str, handler: Handler, extend: Extend) {
-        self.repo_wf(pattern, Method::HEAD, handler, Some(extend))
+    pub fn head_wf<F>(&self, pattern: &str, handler: F, extend: Extend)
+        where F: Fn(&mut Context) + Send + Sync + 'static {
+        self.repo_wf(pattern, Method::HEAD, Arc::new(handler), Some(extend))
     }
 
-    pub fn trace_wf(&self, pattern: &str, handler: Handler, extend: Extend) {
-        self.repo_wf(pattern, Method::TRACE, handler, Some(extend))
+    pub fn trace_wf<F>(&self, pattern: &str, handler: F, extend: Extend)
+        where F: Fn(&mut Context) + Send + Sync + 'static {
+        self.repo_wf(pattern, Method::TRACE, Arc::new(handler), Some(extend))
     }
 
-    pub fn connect_wf(&self, pattern: &str, handler: Handler, extend: Extend) {
-        self.repo_wf(pattern, Method::CONNECT, handler, Some(extend))
+    pub fn connect_wf<F>(&self, pattern: &str, handler: F, extend: Extend)
+        where F: Fn(&mut Context) + Send + Sync + 'static {
+        self.repo_wf(pattern, Method::CONNECT, Arc::new(handler), Some(extend))
     }
 
-    pub fn patch_wf(&self, pattern: &str, handler: Handler, extend: Extend) {
-        self.repo_wf(pattern, Method::PATCH, handler, Some(extend))
+    pub fn patch_wf<F>(&self, pattern: &str, handler: F, extend: Extend)
+        where F: Fn(&mut Context) + Send + Sync + 'static {
+        self.repo_wf(pattern, Method::PATCH, Arc::new(handler), Some(extend))
     }
 
-    pub fn link_wf(&self, pattern: &str, handler: Handler, extend: Extend) {
-        self.repo_wf(pattern, Method::LINK, handler, Some(extend))
+    pub fn link_wf<F>(&self, pattern: &str, handler: F, extend: Extend)
+        where F: Fn(&mut Context) + Send + Sync + 'static {
+        self.repo_wf(pattern, Method::LINK, Arc::new(handler), Some(extend))
     }
 
-    pub fn unlink_wf(&self, pattern: &str, handler: Handler, extend: Extend) {
-        self.repo_wf(pattern, Method::UNLINK, handler, Some(extend))
+    pub fn unlink_wf<F>(&self, pattern: &str, handler: F, extend: Extend)
+        where F: Fn(&mut Context) + Send + Sync + 'static {
+        self.repo_wf(pattern, Method::UNLINK, Arc::new(handler), Some(extend))
     }
 
-    pub fn pri_wf(&self, pattern: &str, handler: Handler, extend: Extend) {
-        self.repo_wf(pattern, Method::PRI, handler, Some(extend))
+    pub fn pri_wf<F>(&self, pattern: &str, handler: F, extend: Extend)
+        where F: Fn(&mut Context) + Send + Sync + 'static {
+        self.repo_wf(pattern, Method::PRI, Arc::new(handler), Some(extend))
     }
 
-    pub fn option(&self, pattern: &str, handler: Handler) {
-        self.repo(pattern, Method::OPTIONS, handler)
+    pub fn option<F>(&self, pattern: &str, handler: F)
+        where F: Fn(&mut Context) + Send + Sync + 'static {
+        self.repo(pattern, Method::OPTIONS, Arc::new(handler))
     }
 
-    pub fn get(&self, pattern: &str, handler: Handler) {
-        self.repo(pattern, Method::GET, handler)
+    pub fn get<F>(&self, pattern: &str, handler: F)
+        where F: Fn(&mut Context) + Send + Sync + 'static {
+        self.repo(pattern, Method::GET, Arc::new(handler))
     }
 
-    pub fn post(&self, pattern: &str, handler: Handler) {
-        self.repo(pattern, Method::POST, handler)
+    pub fn post<F>(&self, pattern: &str, handler: F)
+        where F: Fn(&mut Context) + Send + Sync + 'static {
+        self.repo(pattern, Method::POST, Arc::new(handler))
     }
 
-    pub fn put(&self, pattern: &str, handler: Handler) {
-        self.repo(pattern, Method::PUT, handler)
+    pub fn put<F>(&self, pattern: &str, handler: F)
+        where F: Fn(&mut Context) + Send + Sync + 'static {
+        self.repo(pattern, Method::PUT, Arc::new(handler))
     }
 
-    pub fn delete(&self, pattern: &str, handler: Handler) {
-        self.repo(pattern, Method::DELETE, handler)
+    pub fn delete<F>(&self, pattern: &str, handler: F)
+        where F: Fn(&mut Context) + Send + Sync + 'static {
+        self.repo(pattern, Method::DELETE, Arc::new(handler))
     }
 
-    pub fn head(&self, pattern: &str, handler: Handler) {
-        self.repo(pattern, Method::HEAD, handler)
+    pub fn head<F>(&self, pattern: &str, handler: F)
+        where F: Fn(&mut Context) + Send + Sync + 'static {
+        self.repo(pattern, Method::HEAD, Arc::new(handler))
     }
 
-    pub fn trace(&self, pattern: &str, handler: Handler) {
-        self.repo(pattern, Method::TRACE, handler)
+    pub fn trace<F>(&self, pattern: &str, handler: F)
+        where F: Fn(&mut Context) + Send + Sync + 'static {
+        self.repo(pattern, Method::TRACE, Arc::new(handler))
     }
 
-    pub fn connect(&self, pattern: &str, handler: Handler) {
-        self.repo(pattern, Method::CONNECT, handler)
+    pub fn connect<F>(&self, pattern: &str, handler: F)
+        where F: Fn(&mut Context) + Send + Sync + 'static {
+        self.repo(pattern, Method::CONNECT, Arc::new(handler))
     }
 
-    pub fn patch(&self, pattern: &str, handler: Handler) {
-        self.repo(pattern, Method::PATCH, handler)
+    pub fn patch<F>(&self, pattern: &str, handler: F)
+        where F: Fn(&mut Context) + Send + Sync + 'static {
+        self.repo(pattern, Method::PATCH, Arc::new(handler))
     }
 
-    pub fn link(&self, pattern: &str, handler: Handler) {
-        self.repo(pattern, Method::LINK, handler)
+    pub fn link<F>(&self, pattern: &str, handler: F)
+        where F: Fn(&mut Context) + Send + Sync + 'static {
+        self.repo(pattern, Method::LINK, Arc::new(handler))
     }
 
-    pub fn unlink(&self, pattern: &str, handler: Handler) {
-        self.repo(pattern, Method::UNLINK, handler)
+    pub fn unlink<F>(&self, pattern: &str, handler: F)
+        where F: Fn(&mut Context) + Send + Sync + 'static {
+        self.repo(pattern, Method::UNLINK, Arc::new(handler))
     }
 
-    pub fn pri(&self, pattern: &str, handler: Handler) {
-        self.repo(pattern, Method::PRI, handler)
+    pub fn pri<F>(&self, pattern: &str, handler: F)
+        where F: Fn(&mut Context) + Send + Sync + 'static {
+        self.repo(pattern, Method::PRI, Arc::new(handler))
     }
 }
 
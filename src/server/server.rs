@@ -12,21 +12,30 @@
  * limitations under the License.
  */
 
-use std::io::{Error, Write};
+use std::fmt;
+use std::io::Error;
 use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
 use std::sync::{Arc, RwLock};
+use std::time::Duration;
 
 use log::LevelFilter;
+use openssl::pkey::PKey;
+use openssl::ssl::{SslAcceptor, SslMethod};
+use openssl::x509::X509;
 
 use crate::{Context, Requester};
 use crate::Extend;
+use crate::http::stream::HttpStream;
 use crate::http::url::authority::Addr;
+use crate::server::handle::{Connections, Registration, ServerHandle};
 use crate::server::node::Root;
+use crate::server::reactor::{Conn, Reactor, Socket};
 use crate::server::Router;
-use crate::utils::{Channel, Time};
+use crate::server::state::State;
 use crate::utils::concurrent::{Thread, ThreadPool};
 use crate::utils::errors::{Errs, StarryResult};
 use crate::utils::log::LogModule;
+use crate::websocket::Upgrade;
 use crate::http::requester::SERVER_TCP_STREAM_HAD_NO_DATA;
 
 #[derive(Debug, Clone)]
@@ -35,18 +44,24 @@ pub struct HttpServer {
     ///
     /// 线程池的大小是生成的工作线程的数量。默认情况下，等于CPU核数
     pool_size: usize,
-    /// 设置是否在接受的连接上启用`TCP keepalive`消息，单位ms。如果request启用，默认30000
+    /// 连接等待下一个请求的最长空闲时间，超时后由事件循环关闭连接，单位ms，默认30000
     keepalive: i64,
+    /// 新连接等待首个请求，以及工作线程握手、读取请求和写入回复时单次读写的最长时间，单位ms，默认30000
+    request_timeout: u64,
     /// 是否启用http压缩，如gzip、deflate等
     compress: bool,
+    /// 请求正文允许的最大长度，单位字节，0表示不限制，默认不限制
+    body_limit: u64,
     /// 日志策略
     module: Option<LogModule>,
     root: Arc<RwLock<Root>>,
+    /// 应用共享状态
+    state: State,
 }
 
 impl HttpServer {
     pub fn new() -> Self {
-        HttpServer { pool_size: 0, keepalive: 30000, compress: false, module: None, root: Arc::new(RwLock::new(Root::new())) }
+        HttpServer { pool_size: 0, keepalive: 30000, request_timeout: 30000, compress: false, body_limit: 0, module: None, root: Arc::new(RwLock::new(Root::new())), state: State::new() }
     }
 
     /// 创建路由组
@@ -69,6 +84,15 @@ impl HttpServer {
         Router::new_wf(pattern.to_string(), extend, self.root.clone())
     }
 
+    /// 注册应用共享状态，在任意处理方法或过滤器中可通过[`Context::state`]获取
+    ///
+    /// 状态以类型区分，同类型的状态仅保留最后一次注册的值，需在服务启动前完成注册
+    ///
+    /// [`Context::state`]: crate::Context::state
+    pub fn with_state<T: Send + Sync + 'static>(&mut self, state: Arc<T>) {
+        self.state.insert(state)
+    }
+
     pub fn set_pool_size(&mut self, pool_size: usize) {
         self.pool_size = pool_size
     }
@@ -77,6 +101,18 @@ impl HttpServer {
         self.compress = true
     }
 
+    /// 设置请求正文允许的最大长度，单位字节，0表示不限制
+    ///
+    /// 声明长度超出限制的请求直接返回413，chunked等长度未知的请求在读取过程中超出限制时读取失败并关闭连接
+    pub fn set_body_limit(&mut self, body_limit: u64) {
+        self.body_limit = body_limit
+    }
+
+    /// 设置连接等待下一个请求的最长空闲时间，单位ms，0表示不保持连接，回复后即关闭
+    ///
+    /// 空闲连接由事件循环统一等待，不占用线程池中的工作线程。新连接等待首个请求的时间由[`set_request_timeout`]设置
+    ///
+    /// [`set_request_timeout`]: HttpServer::set_request_timeout
     pub fn set_keepalive(&mut self, keepalive: i64) {
         if keepalive < 0 {
             self.keepalive = 0;
@@ -85,6 +121,16 @@ impl HttpServer {
         }
     }
 
+    /// 设置请求超时时间，单位ms，默认30000，传入0时保持原值
+    ///
+    /// 新连接超时未收到请求时由事件循环关闭；工作线程进行tls握手、读取请求及写入回复期间，
+    /// 单次读写超时后关闭连接，避免只发送部分请求或停止响应的客户端长期占用工作线程
+    pub fn set_request_timeout(&mut self, request_timeout: u64) {
+        if request_timeout > 0 {
+            self.request_timeout = request_timeout
+        }
+    }
+
     /// http服务日志设置
     ///
     /// * level输出日志级别，默认DEBUG
@@ -120,53 +166,231 @@ impl HttpServer {
         }).config_log(vec![])
     }
 
-    /// 创建一个新的HttpListener，它将被绑定到指定的端口。
+    /// 创建一个新的HttpListener，它将被绑定到指定的端口，并在当前线程中循环接收连接。
     ///
     /// 返回的侦听器已准备好接受连接。
-    /// 绑定端口号为0将请求操作系统为该侦听器分配一个端口。分配的端口可以通过[`context::local_addr`]方法查询。
+    /// 绑定端口号为0将请求操作系统为该侦听器分配一个端口。分配的端口可以通过[`bind`]后的[`BoundServer::local_addr`]方法查询。
     /// 地址类型可以是[`ToSocketAddrs`] trait的任何实现。具体示例请参阅其文档
     /// 如果addr产生多个地址，bind将对每个地址进行尝试，直到其中一个成功并返回监听器。
     /// 如果没有一个地址成功创建侦听器，则返回上次尝试返回的错误(最后一个地址)。
     ///
-    /// [`context::local_addr`]: crate::Context::local_addr
+    /// [`bind`]: crate::HttpServer::bind
+    /// [`BoundServer::local_addr`]: crate::BoundServer::local_addr
     /// [`ToSocketAddrs`]: std::net::ToSocketAddrs
     pub fn listener<A: ToSocketAddrs>(&self, addr: A) -> StarryResult<()> {
-        self.log_init();
+        self.bind(addr)?.serve()
+    }
+
+    /// 创建一个新的基于tls的HttpsListener，它将被绑定到指定的端口。
+    ///
+    /// 每个被接受的连接都会在线程池中完成tls握手，之后与[`listener`]执行相同的解析及处理流程，
+    /// 处理方法中可以通过[`Context::req_scheme`]获取到[`Scheme::HTTPS`]，以此判断连接是否加密。
+    ///
+    /// * addr 监听地址，参考[`listener`]
+    /// * cert_pem PEM格式的服务端证书，如存在证书链，则第一个为服务端证书，后续依次为中间证书
+    /// * key_pem PEM格式的服务端私钥
+    ///
+    /// [`listener`]: crate::HttpServer::listener
+    /// [`Context::req_scheme`]: crate::Context::req_scheme
+    /// [`Scheme::HTTPS`]: crate::Scheme::HTTPS
+    pub fn listener_tls<A: ToSocketAddrs>(&self, addr: A, cert_pem: &[u8], key_pem: &[u8]) -> StarryResult<()> {
+        self.bind_tls(addr, cert_pem, key_pem)?.serve()
+    }
+
+    /// 绑定地址但暂不接收连接，绑定失败时返回错误
+    ///
+    /// 绑定端口号为0时，可以通过[`BoundServer::local_addr`]获取操作系统分配的端口，
+    /// 之后通过[`BoundServer::serve`]在当前线程或[`BoundServer::start`]在后台线程中接收连接
+    ///
+    /// * addr 监听地址，参考[`listener`]
+    ///
+    /// ```no_run
+    /// use starry::HttpServer;
+    ///
+    /// let server = HttpServer::new();
+    /// let bound = server.bind("127.0.0.1:0").unwrap();
+    /// println!("listening on {}", bound.local_addr());
+    /// bound.serve().unwrap();
+    /// ```
+    ///
+    /// [`listener`]: crate::HttpServer::listener
+    /// [`BoundServer::local_addr`]: crate::BoundServer::local_addr
+    /// [`BoundServer::serve`]: crate::BoundServer::serve
+    /// [`BoundServer::start`]: crate::BoundServer::start
+    pub fn bind<A: ToSocketAddrs>(&self, addr: A) -> StarryResult<BoundServer> {
+        BoundServer::new(self.clone(), addr, None)
+    }
+
+    /// 绑定地址并使用tls接收连接，参数参考[`listener_tls`]，返回值参考[`bind`]
+    ///
+    /// [`listener_tls`]: crate::HttpServer::listener_tls
+    /// [`bind`]: crate::HttpServer::bind
+    pub fn bind_tls<A: ToSocketAddrs>(&self, addr: A, cert_pem: &[u8], key_pem: &[u8]) -> StarryResult<BoundServer> {
+        let acceptor = tls_acceptor(cert_pem, key_pem)?;
+        BoundServer::new(self.clone(), addr, Some(Arc::new(acceptor)))
+    }
+
+    /// 在后台线程中启动服务，立即返回可用于关闭服务的[`ServerHandle`]
+    ///
+    /// * addr 监听地址，参考[`listener`]
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// use starry::HttpServer;
+    ///
+    /// let server = HttpServer::new();
+    /// let handle = server.start("127.0.0.1:8080").unwrap();
+    /// // 最多等待10秒，以便处理中的请求完成
+    /// handle.shutdown(Duration::from_secs(10));
+    /// ```
+    ///
+    /// [`listener`]: crate::HttpServer::listener
+    pub fn start<A: ToSocketAddrs>(&self, addr: A) -> StarryResult<ServerHandle> {
+        self.bind(addr)?.start()
+    }
+
+    /// 在后台线程中启动基于tls的服务，参数参考[`listener_tls`]，返回值参考[`start`]
+    ///
+    /// [`listener_tls`]: crate::HttpServer::listener_tls
+    /// [`start`]: crate::HttpServer::start
+    pub fn start_tls<A: ToSocketAddrs>(&self, addr: A, cert_pem: &[u8], key_pem: &[u8]) -> StarryResult<ServerHandle> {
+        self.bind_tls(addr, cert_pem, key_pem)?.start()
+    }
+
+    fn thread_pool(&self) -> StarryResult<ThreadPool> {
         let mut thread_pool_builder = ThreadPool::builder();
         if self.pool_size > 0 {
             thread_pool_builder.pool_size(self.pool_size);
         }
         thread_pool_builder.name_prefix("starry-http-pool");
-        let thread_pool = thread_pool_builder.create()?;
-        let tcp_listener = TcpListener::bind(addr).unwrap();
-        for tcp_stream_result in tcp_listener.incoming() {
-            match tcp_stream_result {
-                Ok(tcp_stream) => {
-                    let peer;
-                    let local;
-                    match addrs(tcp_stream.peer_addr(), tcp_stream.local_addr()) {
-                        Ok((peer_a, local_a)) => {
-                            peer = peer_a;
-                            local = local_a;
-                        }
-                        Err(err) => {
-                            log::error!("tcp listener stream get addr failed! {}", err);
-                            continue;
-                        }
-                    }
-                    let keepalive = self.keepalive;
-                    let root = self.root.clone();
-                    let compress = self.compress;
-                    match thread_pool.execute(move || handle_connection(tcp_stream, root, keepalive, peer, local, compress)) {
-                        Ok(()) => {}
-                        Err(err) => log::error!("thread pool execute tcp stream failed! {}", err)
-                    }
-                }
-                Err(err) => log::error!("tcp listener failed! {}", err)
+        thread_pool_builder.create()
+    }
+
+    /// 启动事件循环，连接可读时交由线程池处理
+    ///
+    /// * acceptor 如果存在，则对接收到的连接先进行tls握手
+    fn reactor(&self, thread_pool: ThreadPool, acceptor: Option<Arc<SslAcceptor>>) -> StarryResult<Reactor> {
+        let service = Arc::new(Service {
+            root: self.root.clone(),
+            state: Arc::new(self.state.clone()),
+            acceptor,
+            compress: self.compress,
+            body_limit: self.body_limit,
+            keepalive: self.keepalive > 0,
+            request_timeout: Duration::from_millis(self.request_timeout),
+        });
+        // 可读的连接先放入就绪队列，每个任务处理至队列为空，
+        // 线程池任务队列已满时连接留在就绪队列中，由已提交的任务继续处理
+        let (tx, rx) = crossbeam::channel::unbounded::<(Conn, Reactor)>();
+        Reactor::start(Duration::from_millis(self.keepalive as u64), Duration::from_millis(self.request_timeout), Box::new(move |conn, reactor| {
+            tx.send((conn, reactor)).unwrap_or(());
+            let rx = rx.clone();
+            let service = service.clone();
+            match thread_pool.execute(move || while let Ok((conn, reactor)) = rx.try_recv() {
+                handle_connection(conn, reactor, service.clone())
+            }) {
+                Ok(()) => {}
+                Err(err) => log::debug!("thread pool execute tcp stream delayed! {}", err)
             }
-        }
+        }))
+    }
+}
+
+/// 已绑定地址的服务，通过[`HttpServer::bind`]创建
+///
+/// [`HttpServer::bind`]: crate::HttpServer::bind
+pub struct BoundServer {
+    server: HttpServer,
+    tcp_listener: TcpListener,
+    local_addr: SocketAddr,
+    /// 如果存在，则对接收到的连接先进行tls握手
+    acceptor: Option<Arc<SslAcceptor>>,
+}
+
+impl BoundServer {
+    fn new<A: ToSocketAddrs>(server: HttpServer, addr: A, acceptor: Option<Arc<SslAcceptor>>) -> StarryResult<Self> {
+        let tcp_listener = match TcpListener::bind(addr) {
+            Ok(src) => src,
+            Err(err) => return Err(Errs::strs("server tcp listener bind failed!", err))
+        };
+        let local_addr = match tcp_listener.local_addr() {
+            Ok(src) => src,
+            Err(err) => return Err(Errs::strs("server tcp listener get local addr failed!", err))
+        };
+        Ok(BoundServer { server, tcp_listener, local_addr, acceptor })
+    }
+
+    /// 实际监听的地址，绑定端口号为0时为操作系统分配的端口
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// 在当前线程中循环接收连接
+    pub fn serve(self) -> StarryResult<()> {
+        self.server.log_init();
+        let tls = self.acceptor.is_some();
+        let reactor = self.server.reactor(self.server.thread_pool()?, self.acceptor)?;
+        accept(self.tcp_listener, reactor, tls, Connections::new());
         Ok(())
     }
+
+    /// 在后台线程中循环接收连接，立即返回可用于关闭服务的[`ServerHandle`]
+    pub fn start(self) -> StarryResult<ServerHandle> {
+        self.server.log_init();
+        let tls = self.acceptor.is_some();
+        let thread_pool = self.server.thread_pool()?;
+        let reactor = self.server.reactor(thread_pool.clone(), self.acceptor)?;
+        let connections = Connections::new();
+        let (tcp_listener, reactor_c, connections_c) = (self.tcp_listener, reactor.clone(), connections.clone());
+        let accept = Thread::spawn_on_name("starry-http-listener".to_string(),
+                                           move || accept(tcp_listener, reactor_c, tls, connections_c))?;
+        Ok(ServerHandle::new(self.local_addr, connections, reactor, thread_pool, accept))
+    }
+}
+
+impl fmt::Debug for BoundServer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BoundServer")
+            .field("local_addr", &self.local_addr)
+            .field("tls", &self.acceptor.is_some())
+            .finish()
+    }
+}
+
+/// 根据PEM格式的证书及私钥创建tls握手器
+fn tls_acceptor(cert_pem: &[u8], key_pem: &[u8]) -> StarryResult<SslAcceptor> {
+    let mut certs = match X509::stack_from_pem(cert_pem) {
+        Ok(src) => src.into_iter(),
+        Err(err) => return Err(Errs::strs("server tls cert parse from pem failed!", err))
+    };
+    let cert = match certs.next() {
+        Some(src) => src,
+        None => return Err(Errs::str("server tls cert not found in pem!"))
+    };
+    let key = match PKey::private_key_from_pem(key_pem) {
+        Ok(src) => src,
+        Err(err) => return Err(Errs::strs("server tls private key parse from pem failed!", err))
+    };
+    let mut builder = match SslAcceptor::mozilla_intermediate(SslMethod::tls()) {
+        Ok(src) => src,
+        Err(err) => return Err(Errs::strs("server tls acceptor create failed!", err))
+    };
+    if let Err(err) = builder.set_certificate(&cert) {
+        return Err(Errs::strs("server tls acceptor set cert failed!", err));
+    }
+    for chain in certs {
+        if let Err(err) = builder.add_extra_chain_cert(chain) {
+            return Err(Errs::strs("server tls acceptor add chain cert failed!", err));
+        }
+    }
+    if let Err(err) = builder.set_private_key(&key) {
+        return Err(Errs::strs("server tls acceptor set private key failed!", err));
+    }
+    if let Err(err) = builder.check_private_key() {
+        return Err(Errs::strs("server tls cert and private key unmatched!", err));
+    }
+    Ok(builder.build())
 }
 
 /// 根据tcp stream匹配远端和本地addr
@@ -182,39 +406,155 @@ fn addrs(peer_addr: Result<SocketAddr, Error>, local_addr: Result<SocketAddr, Er
     }
 }
 
-/// 针对本次stream进行处理
-fn handle_connection(tcp_stream: TcpStream, root: Arc<RwLock<Root>>, mut keepalive: i64, peer: Addr, local: Addr, compress: bool) {
+/// 处理连接所需的服务端配置
+struct Service {
+    root: Arc<RwLock<Root>>,
+    /// 应用共享状态
+    state: Arc<State>,
+    /// 如果存在，则对接收到的连接先进行tls握手
+    acceptor: Option<Arc<SslAcceptor>>,
+    compress: bool,
+    body_limit: u64,
+    /// 是否保持连接等待下一个请求
+    keepalive: bool,
+    /// 工作线程处理连接期间单次读写的最长时间
+    request_timeout: Duration,
+}
+
+/// 循环接收连接并交由事件循环等待请求，服务停止后返回
+///
+/// * tls 接收到的连接是否需要先进行tls握手
+fn accept(tcp_listener: TcpListener, reactor: Reactor, tls: bool, connections: Arc<Connections>) {
+    for tcp_stream_result in tcp_listener.incoming() {
+        if connections.is_stopping() {
+            log::debug!("server tcp listener stopped!");
+            return;
+        }
+        match tcp_stream_result {
+            Ok(tcp_stream) => {
+                let peer;
+                let local;
+                match addrs(tcp_stream.peer_addr(), tcp_stream.local_addr()) {
+                    Ok((peer_a, local_a)) => {
+                        peer = peer_a;
+                        local = local_a;
+                    }
+                    Err(err) => {
+                        log::error!("tcp listener stream get addr failed! {}", err);
+                        continue;
+                    }
+                }
+                // 登记连接，以便关闭服务时可以关闭空闲连接并等待处理中的请求
+                let registration = match connections.register(&tcp_stream) {
+                    Ok(Some(src)) => src,
+                    Ok(None) => return,
+                    Err(err) => {
+                        log::error!("{}", err);
+                        continue;
+                    }
+                };
+                let socket = if tls { Socket::Handshake(tcp_stream) } else { Socket::Ready(HttpStream::Tcp(tcp_stream)) };
+                reactor.accept(Conn { socket, peer, local, registration })
+            }
+            Err(err) => log::error!("tcp listener failed! {}", err)
+        }
+    }
+}
+
+/// 处理可读的连接，在线程池中执行
+///
+/// 完成tls握手或请求处理后，需要保持的连接交还事件循环等待下一个请求
+fn handle_connection(conn: Conn, reactor: Reactor, service: Arc<Service>) {
     log::trace!("server handle connection");
-    match tcp_stream.try_clone() {
-        Ok(src) => {
-            let close = exec_stream(src, root.clone(), peer.clone(), local.clone(), compress);
-            if close { // 如果不保持连接或连接关闭，直接返回
-                tcp_stream_shutdown(tcp_stream, peer);
-                return;
+    // 连接交由工作线程后即视为处理中，包括握手及读取请求期间，关闭服务时等待其完成而非直接关闭
+    conn.registration.busy();
+    // 握手及处理请求期间设置读写超时，避免只发送部分数据或停止响应的客户端长期占用工作线程
+    match conn.set_timeout(Some(service.request_timeout)) {
+        Ok(()) => {}
+        Err(err) => {
+            log::error!("server stream {} set timeout failed! {}", conn.peer.to_string(), err);
+            return;
+        }
+    }
+    let Conn { socket, peer, local, registration } = conn;
+    let stream = match socket {
+        Socket::Ready(src) => src,
+        Socket::Handshake(tcp_stream) => {
+            if let Some(src) = handshake(tcp_stream, &service, &peer) {
+                if registration.idle() {
+                    stream_shutdown(src, peer)
+                } else {
+                    hand_back(Conn { socket: Socket::Ready(src), peer, local, registration }, &reactor, true)
+                }
             }
-            match tcp_stream.try_clone() {
-                Ok(src) => {
-                    // 双线异步循环执行超时检查和stream解析
-                    loop_exec(src, root.clone(), keepalive, peer, local, compress)
+            return;
+        }
+    };
+    match stream.try_clone() {
+        Ok(src) => match exec_stream(src, &service, &registration, peer.clone(), local.clone()) {
+            // 交还事件循环前标记为空闲，服务已停止时直接关闭
+            Next::KeepAlive => if registration.idle() {
+                stream_shutdown(stream, peer)
+            } else {
+                hand_back(Conn { socket: Socket::Ready(stream), peer, local, registration }, &reactor, false)
+            },
+            Next::Close => stream_shutdown(stream, peer), // 如果不保持连接或连接关闭，直接返回
+            Next::Upgrade(upgrade, pending) => upgrade_exec(stream, upgrade, pending, peer),
+        },
+        Err(err) => log::error!("server tcp stream clone in handle connection failed! {}", err)
+    }
+}
+
+/// 清除读写超时后将连接交还事件循环，等待中的连接由事件循环的定时器控制超时
+///
+/// * fresh 是否为尚未收到请求的新连接，新连接等待首个请求，否则等待下一个请求
+fn hand_back(conn: Conn, reactor: &Reactor, fresh: bool) {
+    match conn.set_timeout(None) {
+        Ok(()) => if fresh {
+            reactor.accept(conn)
+        } else {
+            reactor.park(conn)
+        },
+        // 连接随之释放并关闭
+        Err(err) => log::error!("server stream {} clear timeout failed! {}", conn.peer.to_string(), err)
+    }
+}
+
+/// 对接收到的连接进行tls握手
+fn handshake(tcp_stream: TcpStream, service: &Service, peer: &Addr) -> Option<HttpStream> {
+    match &service.acceptor {
+        Some(acceptor) => match acceptor.accept(tcp_stream) {
+            Ok(ssl_stream) => match HttpStream::tls(ssl_stream) {
+                Ok(src) => Some(src),
+                Err(err) => {
+                    log::error!("server tls stream clone from {} failed! {}", peer.to_string(), err);
+                    None
                 }
-                Err(err) => log::error!("server tcp stream clone while handle connection 1 failed! {}", err.to_string())
+            }
+            Err(err) => {
+                log::info!("server tls handshake from {} failed! {}", peer.to_string(), err);
+                None
             }
         }
-        Err(err) => log::error!("server tcp stream clone in handle connection 2 failed! {}", err.to_string())
+        None => Some(HttpStream::Tcp(tcp_stream))
     }
 }
 
 /// 执行stream解析操作
 ///
-/// * 是否立刻关闭连接
-/// * 是否立刻关闭读连接
-fn exec_stream(tcp_stream: TcpStream, root: Arc<RwLock<Root>>, peer: Addr, local: Addr, compress: bool) -> bool {
-    match Requester::from(tcp_stream, root.clone(), peer, local) {
+/// 返回本次请求处理结束后连接的去向
+fn exec_stream(stream: HttpStream, service: &Service, registration: &Registration, peer: Addr, local: Addr) -> Next {
+    let scheme = stream.scheme();
+    match Requester::from(stream, service.root.clone(), scheme, peer, local, service.body_limit) {
         // request分预解析和解析两个过程，预解析用于判断请求有效性，如无效，则放弃后续解析操作
-        Ok((requester, node, fields)) => {
+        Ok((mut requester, node, fields)) => {
+            // 服务已停止或不保持连接时，本次请求处理完成后关闭连接，并告知客户端
+            if registration.is_stopping() || !service.keepalive {
+                requester.request.close = true;
+            }
             let close = requester.request.close;
             log::debug!("method = {}, path = {}, from = {}", requester.method(), requester.path(), requester.client());
-            let mut context = Box::new(Context::new(requester, fields, compress));
+            let mut context = Box::new(Context::new(requester, fields, service.state.clone(), service.compress));
             log::trace!("context = {:#?}", context);
             match node.extend.clone() {
                 Some(extend) => extend.exec(context.as_mut()), // 扩展执行，自我诊断
@@ -223,7 +563,12 @@ fn exec_stream(tcp_stream: TcpStream, root: Arc<RwLock<Root>>, peer: Addr, local
             if !context.executed {
                 node.handler()(context.as_mut())
             }
-            close
+            // 丢弃未被读取的请求正文，避免影响同一连接上的下一个请求
+            context.drain_body();
+            match context.take_upgrade() {
+                Some((upgrade, pending)) => Next::Upgrade(upgrade, pending),
+                None => if close || context.close() { Next::Close } else { Next::KeepAlive }
+            }
         }
         Err(err) => {
             if err.to_string().eq(SERVER_TCP_STREAM_HAD_NO_DATA) {
@@ -231,103 +576,71 @@ fn exec_stream(tcp_stream: TcpStream, root: Arc<RwLock<Root>>, peer: Addr, local
             } else {
                 log::info!("server request from failed! {}", err.to_string());
             }
-            true
+            Next::Close
         }
     }
 }
 
-/// 双线异步循环执行超时检查和stream解析
-fn loop_exec(mut tcp_stream: TcpStream, root: Arc<RwLock<Root>>, keepalive: i64, peer: Addr, local: Addr, compress: bool) {
-    // 创建一个可以将stream接收信号同步更新至检查超时线程的通道
-    let channel = Arc::new(Channel::unbounded());
-    match tcp_stream.try_clone() {
-        Ok(src) => {
-            let channel = channel.clone();
-            let peer = peer.clone();
-            // 新启线程检查当前stream是否超时
-            match Thread::spawn(move || check_keepalive(src, keepalive, channel, peer)) {
-                Ok(_) => {}
-                Err(err) => {
-                    log::error!("loop exec check stream failed! {}", err.to_string());
-                    match tcp_stream.write("HTTP/1.1 500 Internal Server Error\r\n\r\n".as_bytes()) {
-                        Ok(_) => {}
-                        Err(err) => {
-                            log::error!("loop exec check stream with write failed! {}", err.to_string());
-                            return;
-                        }
-                    }
-                }
-            };
-        }
-        Err(err) => log::error!("server tcp stream clone in loop exec 1 failed! {}", err.to_string())
-    }
-    // 当前线程开启循环读取stream操作
-    loop {
-        match tcp_stream.try_clone() {
-            Ok(src) => {
-                let close = exec_stream(src, root.clone(), peer.clone(), local.clone(), compress);
-                if close { // 如果连接关闭，直接返回
-                    channel.send(Check::Break).unwrap_or(());
-                    return;
-                }
-                // 处理完当前stream后需要更新超时时间起始参考值
-                let channel = channel.clone();
-                channel.send(Check::Update).unwrap_or(());
-            }
-            Err(err) => log::error!("server tcp stream clone in loop exec 2 failed! {}", err.to_string())
+/// 将已完成协议升级的连接交由处理方法接管，处理方法返回后关闭连接
+///
+/// * pending 解析请求时已读取的、属于升级后协议的数据
+fn upgrade_exec(stream: HttpStream, upgrade: Upgrade, pending: Vec<u8>, peer: Addr) {
+    // 升级后的协议由处理方法自行控制读写超时
+    match stream.set_read_timeout(None).and(stream.set_write_timeout(None)) {
+        Ok(()) => {}
+        Err(err) => {
+            log::error!("server stream {} clear timeout failed! {}", peer.to_string(), err);
+            stream_shutdown(stream, peer);
+            return;
         }
     }
-}
-
-/// 检查当前stream是否超时
-fn check_keepalive(tcp_stream: TcpStream, keepalive: i64, channel: Arc<Channel<Check>>, peer: Addr) {
-    let mut time = Time::now();
-    time.add_milliseconds(keepalive);
-    let mut expect_time = time.num_milliseconds();
-    loop {
-        let mut time_now = Time::now();
-        if expect_time <= time_now.num_milliseconds() {
-            log::trace!("server expect_time = {}", Time::format_data(Time::from_milliseconds(expect_time), "%Y-%m-%d %H:%M:%S"));
-            break;
-        } else {
-            match channel.try_recv() {
-                Ok(src) => match src {
-                    Check::Update => {
-                        log::trace!("server channel receive update!");
-                        time_now.add_milliseconds(keepalive);
-                        expect_time = time_now.num_milliseconds();
-                    }
-                    Check::Break => break
-                }
-                Err(_) => {}
-            }
-        }
+    match stream.try_clone() {
+        Ok(src) => upgrade.run(src, pending),
+        Err(err) => log::error!("server tcp stream clone in upgrade exec failed! {}", err)
     }
-    tcp_stream_shutdown(tcp_stream, peer)
+    stream_shutdown(stream, peer)
 }
 
-fn tcp_stream_shutdown(tcp_stream: TcpStream, peer: Addr) {
+fn stream_shutdown(stream: HttpStream, peer: Addr) {
     log::debug!("server check stream {} shutdown!", peer.to_string());
-    match tcp_stream.shutdown(Shutdown::Both) {
+    match stream.shutdown(Shutdown::Both) {
         Ok(_) => log::trace!("server tcp stream {} shutdown success!", peer.to_string()),
         Err(err) => log::error!("server tcp stream {} shutdown failed! {}", peer.to_string(), err.to_string())
     }
 }
 
-/// 超时检查
-enum Check {
-    /// 更新超时起始参考值
-    Update,
-    /// 退出检查
-    Break,
+/// 本次请求处理结束后连接的去向
+enum Next {
+    /// 关闭连接
+    Close,
+    /// 保持连接，继续处理下一个请求
+    KeepAlive,
+    /// 连接已升级为其它协议，交由处理方法接管
+    ///
+    /// 第二项为解析请求时已读取的、属于升级后协议的数据
+    Upgrade(Upgrade, Vec<u8>),
 }
 
 #[cfg(test)]
-mod server_test {
+pub(crate) mod server_test {
     use std::collections::HashMap;
+    use std::fs;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::time::{Duration, Instant};
+
+    use openssl::asn1::Asn1Time;
+    use openssl::hash::MessageDigest;
+    use openssl::pkey::PKey;
+    use openssl::rsa::Rsa;
+    use openssl::ssl::{SslConnector, SslMethod, SslVerifyMode};
+    use openssl::x509::{X509, X509NameBuilder};
 
-    use crate::{Context, HttpServer, Method};
+    use crate::{Context, Event, Extend, HttpServer, Method, ServerHandle, Status};
     use crate::server::node::Node;
+    use crate::utils::concurrent::Thread;
 
     impl HttpServer {
         pub(crate) fn fetch(&self, pattern: String, method: Method) -> Option<(Node, HashMap<String, String>)> {
@@ -374,6 +687,527 @@ mod server_test {
         assert_eq!(n3, server.root.read().unwrap().root_get.next_nodes[1].next_nodes[0].next_nodes[1].next_nodes[0].next_nodes[0].next_nodes[0]);
     }
 
+    #[test]
+    fn server_test_tls() {
+        let (cert_pem, key_pem) = self_signed();
+        let server = HttpServer::new();
+        let router = server.router("/tls");
+        router.get("/scheme", h_scheme);
+        let handle = serve_tls(&server, &cert_pem, &key_pem);
+
+        let mut builder = SslConnector::builder(SslMethod::tls()).unwrap();
+        builder.set_verify(SslVerifyMode::NONE);
+        let connector = builder.build();
+        let tcp_stream = TcpStream::connect(handle.local_addr()).unwrap();
+        let mut stream = connector.connect("localhost", tcp_stream).unwrap();
+        stream.write_all(b"GET /tls/scheme HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let resp = read_all(&mut stream);
+        assert!(resp.starts_with("HTTP/1.1 200 OK"), "resp = {}", resp);
+        assert!(resp.ends_with("https true"), "resp = {}", resp);
+    }
+
+    #[test]
+    fn server_test_closure() {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let filtered = Arc::new(AtomicUsize::new(0));
+        let server = HttpServer::new();
+        let router = server.router("/state");
+        let hits_handler = hits.clone();
+        let filtered_filter = filtered.clone();
+        router.get_wf("/count", move |context: &mut Context| {
+            let count = hits_handler.fetch_add(1, Ordering::SeqCst) + 1;
+            context.resp_body(format!("count {}", count).into_bytes());
+            context.response();
+        }, Extend::e1(vec![move |_context: &mut Context| {
+            filtered_filter.fetch_add(1, Ordering::SeqCst);
+        }]));
+        let addr = serve(&server).local_addr().to_string();
+
+        for i in 1..3 {
+            let resp = tcp_get(&addr, "/state/count");
+            assert!(resp.ends_with(&format!("count {}", i)), "resp = {}", resp);
+        }
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+        assert_eq!(filtered.load(Ordering::SeqCst), 2);
+    }
+
+    struct AppState {
+        name: String,
+    }
+
+    struct User(String);
+
+    #[test]
+    fn server_test_state() {
+        let mut server = HttpServer::new();
+        server.with_state(Arc::new(AppState { name: "starry".to_string() }));
+        let router = server.router_wf("/state", Extend::e1(vec![f_user]));
+        router.get("/user", h_user);
+        let addr = serve(&server).local_addr().to_string();
+
+        let resp = tcp_get(&addr, "/state/user");
+        assert!(resp.ends_with("starry aberic"), "resp = {}", resp);
+    }
+
+    fn f_user(context: &mut Context) {
+        context.extension_insert(User("aberic".to_string()));
+    }
+
+    fn h_user(context: &mut Context) {
+        let name = context.state::<AppState>().unwrap().name.clone();
+        let user = context.extension_get::<User>().unwrap().0.clone();
+        context.resp_body(format!("{} {}", name, user).into_bytes());
+        context.response();
+    }
+
+    #[test]
+    fn server_test_chunked() {
+        let server = HttpServer::new();
+        server.router("/chunked").post("/echo", h_echo);
+        let addr = serve(&server).local_addr().to_string();
+
+        let mut stream = TcpStream::connect(&addr).unwrap();
+        stream.write_all(b"POST /chunked/echo HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello").unwrap();
+        Thread::sleep(Duration::from_millis(100));
+        stream.write_all(b"\r\n8\r\n, starry\r\n0\r\nX-Sum: 13\r\n\r\n").unwrap();
+        let resp = read_all(&mut stream);
+        assert!(resp.starts_with("HTTP/1.1 200 OK"), "resp = {}", resp);
+        assert!(resp.contains("Transfer-Encoding: chunked\r\n"), "resp = {}", resp);
+        assert!(resp.ends_with("\r\n18\r\nhello, starry trailer 13\r\n0\r\n\r\n"), "resp = {}", resp);
+    }
+
+    fn h_echo(context: &mut Context) {
+        let mut body = context.req_body().unwrap();
+        body.extend_from_slice(format!(" trailer {}", context.req_header_get("X-Sum").unwrap()).as_bytes());
+        context.resp_chunked();
+        context.resp_body(body);
+        context.response();
+    }
+
+    #[test]
+    fn server_test_body_reader() {
+        let server = HttpServer::new();
+        let router = server.router("/lazy");
+        router.post("/ignore", |context: &mut Context| {
+            context.resp_body(b"ignored".to_vec());
+            context.response();
+        });
+        router.post("/count", |context: &mut Context| {
+            let mut count = 0;
+            let mut buffer = [0; 1000];
+            {
+                let mut reader = context.req_body_reader();
+                loop {
+                    match reader.read(&mut buffer).unwrap() {
+                        0 => break,
+                        size => count += size
+                    }
+                }
+            }
+            context.resp_body(format!("count {}", count).into_bytes());
+            context.response();
+        });
+        let addr = serve(&server).local_addr().to_string();
+
+        let mut stream = TcpStream::connect(&addr).unwrap();
+        // 处理方法未读取的正文会被丢弃，连接可继续处理下一个请求
+        stream.write_all(b"POST /lazy/ignore HTTP/1.1\r\nHost: localhost\r\nConnection: keep-alive\r\nContent-Length: 5\r\n\r\nhello").unwrap();
+        let mut data = vec![];
+        let mut buffer = [0; 1024];
+        while !data.ends_with(b"ignored") {
+            let size = stream.read(&mut buffer).unwrap();
+            assert!(size > 0);
+            data.extend_from_slice(&buffer[0..size])
+        }
+        let body = vec![b'a'; 100000];
+        stream.write_all(format!("POST /lazy/count HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n", body.len()).as_bytes()).unwrap();
+        stream.write_all(body.as_slice()).unwrap();
+        let resp = read_all(&mut stream);
+        assert!(resp.ends_with("count 100000"), "resp = {}", resp);
+    }
+
+    #[test]
+    fn server_test_body_limit() {
+        let mut server = HttpServer::new();
+        server.set_body_limit(4);
+        server.router("/limit").post("/echo", h_echo);
+        let addr = serve(&server).local_addr().to_string();
+
+        let mut stream = TcpStream::connect(&addr).unwrap();
+        stream.write_all(b"POST /limit/echo HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\n\r\nhello").unwrap();
+        let resp = read_all(&mut stream);
+        assert!(resp.contains(" 413 Payload Too Large\r\n"), "resp = {}", resp);
+    }
+
+    #[test]
+    fn server_test_static() {
+        let dir = std::env::temp_dir().join(format!("starry_static_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let public = dir.join("public");
+        fs::create_dir_all(public.join("css")).unwrap();
+        fs::write(public.join("css/site.css"), "body { color: red; }").unwrap();
+        fs::write(public.join("index.html"), "<h1>index</h1>").unwrap();
+        fs::write(dir.join("secret.txt"), "secret").unwrap();
+
+        let server = HttpServer::new();
+        let router = server.router("");
+        router.static_dir("/assets", public.to_str().unwrap());
+        router.static_file("/secret", dir.join("secret.txt").to_str().unwrap());
+        let addr = serve(&server).local_addr().to_string();
+        let resp = tcp_get(&addr, "/assets/css/site.css");
+        assert!(resp.starts_with("HTTP/1.1 200 OK\r\n"), "resp = {}", resp);
+        assert!(resp.contains("Content-Type: text/css\r\n"));
+        assert!(resp.contains("Content-Length: 20\r\n"));
+        assert!(resp.ends_with("\r\n\r\nbody { color: red; }"));
+        let etag = resp.split("\r\n").find(|line| line.starts_with("ETag: ")).unwrap()[6..].to_string();
+
+        let resp = tcp_get(&addr, "/assets/");
+        assert!(resp.contains("Content-Type: text/html\r\n"));
+        assert!(resp.ends_with("<h1>index</h1>"));
+        assert!(tcp_get(&addr, "/assets/none.css").contains(" 404 Not Found\r\n"));
+        assert!(tcp_get(&addr, "/assets/../secret.txt").contains(" 403 Forbidden\r\n"));
+        assert!(tcp_get(&addr, "/assets/css/%2e%2e/%2E%2E/secret.txt").contains(" 403 Forbidden\r\n"));
+        assert!(tcp_get(&addr, "/secret").ends_with("\r\n\r\nsecret"));
+
+        let resp = tcp_request(&addr, &format!(
+            "GET /assets/css/site.css HTTP/1.1\r\nHost: {}\r\nIf-None-Match: {}\r\n\r\n", addr, etag));
+        assert!(resp.starts_with("HTTP/1.1 304 Not Modified\r\n"), "resp = {}", resp);
+        assert!(resp.ends_with("\r\n\r\n"));
+        assert!(!resp.contains("Content-Length"));
+        let resp = tcp_request(&addr, &format!(
+            "GET /assets/css/site.css HTTP/1.1\r\nHost: {}\r\nIf-Modified-Since: Fri, 01 Jan 2100 00:00:00 GMT\r\n\r\n", addr));
+        assert!(resp.starts_with("HTTP/1.1 304 Not Modified\r\n"), "resp = {}", resp);
+
+        let resp = tcp_request(&addr, &format!(
+            "GET /assets/css/site.css HTTP/1.1\r\nHost: {}\r\nRange: bytes=7-11\r\n\r\n", addr));
+        assert!(resp.starts_with("HTTP/1.1 206 Partial Content\r\n"), "resp = {}", resp);
+        assert!(resp.contains("Content-Range: bytes 7-11/20\r\n"));
+        assert!(resp.ends_with("\r\n\r\ncolor"));
+        let resp = tcp_request(&addr, &format!(
+            "GET /assets/css/site.css HTTP/1.1\r\nHost: {}\r\nRange: bytes=20-\r\n\r\n", addr));
+        assert!(resp.starts_with("HTTP/1.1 416 Range Not Satisfiable\r\n"), "resp = {}", resp);
+        assert!(resp.contains("Content-Range: bytes */20\r\n"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn server_test_sse() {
+        let server = HttpServer::new();
+        let router = server.router("/sse");
+        router.get("/events", |context: &mut Context| {
+            context.sse(|sender| {
+                let from: u32 = sender.last_event_id().unwrap().parse().unwrap();
+                sender.send(Event::data("first\nline").id(&(from + 1).to_string()).event("progress")).unwrap();
+                sender.comment("ping").unwrap();
+                sender.send_data("done").unwrap();
+            }).unwrap()
+        });
+        let disconnected = Arc::new(AtomicBool::new(false));
+        let flag = disconnected.clone();
+        router.get("/forever", move |context: &mut Context| {
+            let flag = flag.clone();
+            context.sse(move |sender| {
+                sender.set_heartbeat(Duration::from_millis(20));
+                loop {
+                    if sender.send_data("tick").is_err() {
+                        flag.store(true, Ordering::SeqCst);
+                        return;
+                    }
+                    Thread::sleep(Duration::from_millis(20));
+                }
+            }).unwrap()
+        });
+        let addr = serve(&server).local_addr().to_string();
+
+        let resp = tcp_request(&addr, "GET /sse/events HTTP/1.1\r\nHost: 127.0.0.1\r\nLast-Event-ID: 5\r\nConnection: close\r\n\r\n");
+        assert!(resp.contains("Content-Type: text/event-stream\r\n"), "{}", resp);
+        assert!(resp.contains("Cache-Control: no-cache\r\n"));
+        assert!(resp.contains("Transfer-Encoding: chunked\r\n"));
+        assert!(resp.contains("id: 6\nevent: progress\ndata: first\ndata: line\n\n"));
+        assert!(resp.contains(": ping\n\n"));
+        assert!(resp.contains("data: done\n\n"));
+        assert!(resp.ends_with("0\r\n\r\n"));
+
+        // 客户端断开后发送返回错误
+        let mut stream = TcpStream::connect(&addr).unwrap();
+        stream.write_all(b"GET /sse/forever HTTP/1.1\r\nHost: 127.0.0.1\r\n\r\n").unwrap();
+        let mut buffer = [0; 1024];
+        assert!(stream.read(&mut buffer).unwrap() > 0);
+        drop(stream);
+        for _ in 0..100 {
+            if disconnected.load(Ordering::SeqCst) {
+                break;
+            }
+            Thread::sleep(Duration::from_millis(20));
+        }
+        assert!(disconnected.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn server_test_method() {
+        let server = HttpServer::new();
+        let router = server.router("/method");
+        router.get("/res", |context: &mut Context| {
+            context.resp_body("hello".as_bytes().to_vec());
+            context.response()
+        });
+        router.put("/res", |context: &mut Context| context.response());
+        let addr = serve(&server).local_addr().to_string();
+        let resp = tcp_request(&addr, &format!("POST /method/res HTTP/1.1\r\nHost: {}\r\n\r\n", addr));
+        assert!(resp.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"), "resp = {}", resp);
+        assert!(resp.contains("Allow: GET, HEAD, PUT, OPTIONS\r\n"));
+        let resp = tcp_request(&addr, &format!("OPTIONS /method/res HTTP/1.1\r\nHost: {}\r\n\r\n", addr));
+        assert!(resp.starts_with("HTTP/1.1 200 OK\r\n"), "resp = {}", resp);
+        assert!(resp.contains("Allow: GET, HEAD, PUT, OPTIONS\r\n"));
+        let resp = tcp_request(&addr, &format!("HEAD /method/res HTTP/1.1\r\nHost: {}\r\n\r\n", addr));
+        assert!(resp.starts_with("HTTP/1.1 200 OK\r\n"), "resp = {}", resp);
+        assert!(resp.contains("Content-Length: 5\r\n"));
+        assert!(resp.ends_with("\r\n\r\n"));
+        assert!(tcp_get(&addr, "/method/res").ends_with("\r\n\r\nhello"));
+        assert!(tcp_get(&addr, "/method/none").contains(" 404 Not Found\r\n"));
+    }
+
+    #[test]
+    fn server_test_shutdown() {
+        let mut server = HttpServer::new();
+        server.set_pool_size(1);
+        let router = server.router("/shutdown");
+        router.get("/fast", |context: &mut Context| {
+            context.resp_body("fast".as_bytes().to_vec());
+            context.response()
+        });
+        router.get("/slow", h_slow);
+        let handle = serve(&server);
+        let addr = handle.local_addr().to_string();
+
+        // 空闲的keep-alive连接
+        let mut idle = TcpStream::connect(&addr).unwrap();
+        idle.set_read_timeout(Some(Duration::from_secs(3))).unwrap();
+        idle.write_all(keepalive(&addr, "/shutdown/fast").as_bytes()).unwrap();
+        let mut buffer = [0; 1024];
+        let size = idle.read(&mut buffer).unwrap();
+        assert!(String::from_utf8_lossy(&buffer[..size]).ends_with("\r\n\r\nfast"));
+
+        // 处理中的请求在关闭期间完成
+        let req = keepalive(&addr, "/shutdown/slow");
+        let a = addr.clone();
+        let slow = Thread::spawn(move || tcp_request(&a, &req)).unwrap();
+        Thread::sleep(Duration::from_millis(100));
+        assert!(handle.shutdown(Duration::from_secs(3)));
+        let resp = slow.join().unwrap();
+        assert!(resp.starts_with("HTTP/1.1 200 OK\r\n"), "resp = {}", resp);
+        assert!(resp.ends_with("\r\n\r\nslow"), "resp = {}", resp);
+        assert_eq!(idle.read(&mut buffer).unwrap(), 0);
+        assert!(TcpStream::connect(&addr).is_err());
+
+        // 超时未完成的请求被强制关闭
+        let server = HttpServer::new();
+        server.router("/shutdown").get("/slow", h_slow);
+        let handle = serve(&server);
+        let addr = handle.local_addr().to_string();
+        let req = keepalive(&addr, "/shutdown/slow");
+        let slow = Thread::spawn(move || tcp_request(&addr, &req)).unwrap();
+        Thread::sleep(Duration::from_millis(100));
+        assert!(!handle.shutdown(Duration::from_millis(100)));
+        assert_eq!(slow.join().unwrap(), "");
+    }
+
+    #[test]
+    fn server_test_bind() {
+        let server = HttpServer::new();
+        server.router("/bind").get("/res", |context: &mut Context| {
+            context.resp_body("hello".as_bytes().to_vec());
+            context.response()
+        });
+        let bound = server.bind("127.0.0.1:0").unwrap();
+        let addr = bound.local_addr();
+        assert_ne!(addr.port(), 0);
+        // 端口已被占用时返回错误
+        assert!(server.bind(addr).is_err());
+        assert!(server.listener(addr).is_err());
+        Thread::spawn(move || bound.serve().unwrap()).unwrap();
+        assert!(tcp_get(&addr.to_string(), "/bind/res").ends_with("\r\n\r\nhello"));
+    }
+
+    #[test]
+    fn server_test_idle() {
+        let mut server = HttpServer::new();
+        server.set_pool_size(2);
+        server.set_keepalive(2000);
+        server.router("/idle").get("/res", |context: &mut Context| {
+            context.resp_body("hello".as_bytes().to_vec());
+            context.response()
+        });
+        let handle = serve(&server);
+        let addr = handle.local_addr().to_string();
+
+        // 大量空闲的keep-alive连接不占用工作线程
+        let mut idles = vec![];
+        for _ in 0..200 {
+            let mut stream = TcpStream::connect(&addr).unwrap();
+            stream.set_read_timeout(Some(Duration::from_secs(3))).unwrap();
+            stream.write_all(keepalive(&addr, "/idle/res").as_bytes()).unwrap();
+            idles.push(stream);
+        }
+        let mut buffer = [0; 1024];
+        for stream in idles.iter_mut() {
+            let size = stream.read(&mut buffer).unwrap();
+            assert!(String::from_utf8_lossy(&buffer[..size]).ends_with("\r\n\r\nhello"));
+            // 同一连接上的下一个请求
+            stream.write_all(keepalive(&addr, "/idle/res").as_bytes()).unwrap();
+        }
+        for stream in idles.iter_mut() {
+            let size = stream.read(&mut buffer).unwrap();
+            assert!(String::from_utf8_lossy(&buffer[..size]).ends_with("\r\n\r\nhello"));
+        }
+        assert!(tcp_get(&addr, "/idle/res").ends_with("\r\n\r\nhello"));
+
+        // 空闲超时后连接被关闭
+        for stream in idles.iter_mut() {
+            assert_eq!(stream.read(&mut buffer).unwrap(), 0);
+        }
+        assert!(handle.shutdown(Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn server_test_keepalive_zero() {
+        let mut server = HttpServer::new();
+        server.set_keepalive(0);
+        server.router("/keepalive").get("/res", |context: &mut Context| {
+            context.resp_body("hello".as_bytes().to_vec());
+            context.response()
+        });
+        let handle = serve(&server);
+        let addr = handle.local_addr().to_string();
+
+        // 首个请求正常处理，回复后关闭连接
+        let resp = tcp_request(&addr, &keepalive(&addr, "/keepalive/res"));
+        assert!(resp.starts_with("HTTP/1.1 200 OK\r\n"), "resp = {}", resp);
+        assert!(!resp.contains("Connection: keep-alive"), "resp = {}", resp);
+        assert!(resp.ends_with("\r\n\r\nhello"));
+        // 连接建立后稍晚发送的请求同样被处理
+        let mut stream = TcpStream::connect(&addr).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(3))).unwrap();
+        Thread::sleep(Duration::from_millis(100));
+        stream.write_all(keepalive(&addr, "/keepalive/res").as_bytes()).unwrap();
+        let mut data = vec![];
+        stream.read_to_end(&mut data).unwrap();
+        assert!(String::from_utf8_lossy(&data).ends_with("\r\n\r\nhello"));
+        assert!(handle.shutdown(Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn server_test_request_timeout() {
+        let mut server = HttpServer::new();
+        server.set_pool_size(1);
+        server.set_request_timeout(300);
+        let router = server.router("/timeout");
+        router.get("/res", |context: &mut Context| {
+            context.resp_body("hello".as_bytes().to_vec());
+            context.response()
+        });
+        router.post("/body", |context: &mut Context| {
+            match context.req_body() {
+                Ok(body) => context.resp_body(body),
+                Err(_) => context.resp_status(Status::REQUEST_TIMEOUT)
+            }
+            context.response()
+        });
+        let handle = serve(&server);
+        let addr = handle.local_addr().to_string();
+
+        // 只发送部分请求正文后停止发送的连接，超时后读取失败，唯一的工作线程随之释放
+        let mut stalled = TcpStream::connect(&addr).unwrap();
+        stalled.write_all(b"POST /timeout/body HTTP/1.1\r\nHost: localhost\r\nContent-Length: 10\r\n\r\nhel").unwrap();
+        Thread::sleep(Duration::from_millis(100));
+        let start = Instant::now();
+        let mut stream = TcpStream::connect(&addr).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(3))).unwrap();
+        stream.write_all(format!("GET /timeout/res HTTP/1.1\r\nHost: {}\r\n\r\n", addr).as_bytes()).unwrap();
+        assert!(read_all(&mut stream).ends_with("\r\n\r\nhello"));
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert!(handle.shutdown(Duration::from_secs(1)));
+
+        // tls握手中途停止的连接同样超时关闭，关闭服务时无需等待至截止时间
+        let (cert, key) = self_signed();
+        let handle = serve_tls(&server, &cert, &key);
+        let mut stalled = TcpStream::connect(handle.local_addr()).unwrap();
+        stalled.write_all(&[0x16, 0x03, 0x01]).unwrap();
+        Thread::sleep(Duration::from_millis(100));
+        let start = Instant::now();
+        assert!(handle.shutdown(Duration::from_secs(3)));
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    fn h_slow(context: &mut Context) {
+        Thread::sleep(Duration::from_millis(500));
+        context.resp_body("slow".as_bytes().to_vec());
+        context.response()
+    }
+
+    fn keepalive(addr: &str, path: &str) -> String {
+        format!("GET {} HTTP/1.1\r\nHost: {}\r\nConnection: keep-alive\r\n\r\n", path, addr)
+    }
+
+    /// 通过明文tcp连接发送GET请求，并读取全部响应
+    fn tcp_get(addr: &str, path: &str) -> String {
+        tcp_request(addr, &format!("GET {} HTTP/1.1\r\nHost: {}\r\n\r\n", path, addr))
+    }
+
+    /// 通过明文tcp连接发送原始请求，并读取全部响应
+    fn tcp_request(addr: &str, req: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(req.as_bytes()).unwrap();
+        read_all(&mut stream)
+    }
+
+    /// 读取全部数据直至对端关闭连接或读取出错
+    pub(crate) fn read_all<R: Read>(stream: &mut R) -> String {
+        let mut data = vec![];
+        let mut buffer = [0; 1024];
+        loop {
+            match stream.read(&mut buffer) {
+                Ok(0) | Err(_) => break,
+                Ok(size) => data.extend_from_slice(&buffer[0..size])
+            }
+        }
+        String::from_utf8_lossy(data.as_slice()).to_string()
+    }
+
+    /// 在操作系统分配的端口上启动服务，通过返回句柄的`local_addr`获取监听地址
+    pub(crate) fn serve(server: &HttpServer) -> ServerHandle {
+        server.start("127.0.0.1:0").unwrap()
+    }
+
+    /// 在操作系统分配的端口上启动https服务
+    pub(crate) fn serve_tls(server: &HttpServer, cert_pem: &[u8], key_pem: &[u8]) -> ServerHandle {
+        server.start_tls("127.0.0.1:0", cert_pem, key_pem).unwrap()
+    }
+
+    /// 生成测试使用的自签名证书及私钥
+    pub(crate) fn self_signed() -> (Vec<u8>, Vec<u8>) {
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_text("CN", "localhost").unwrap();
+        let name = name.build();
+        let mut builder = X509::builder().unwrap();
+        builder.set_version(2).unwrap();
+        builder.set_subject_name(&name).unwrap();
+        builder.set_issuer_name(&name).unwrap();
+        builder.set_pubkey(&key).unwrap();
+        builder.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+        builder.set_not_after(&Asn1Time::days_from_now(1).unwrap()).unwrap();
+        builder.sign(&key, MessageDigest::sha256()).unwrap();
+        (builder.build().to_pem().unwrap(), key.private_key_to_pem_pkcs8().unwrap())
+    }
+
+    fn h_scheme(context: &mut Context) {
+        let body = format!("{} {}", context.req_scheme().as_str(), context.req_is_tls());
+        context.resp_body(body.into_bytes());
+        context.response();
+    }
+
     fn router1(server: HttpServer) {
         let router1 = server.router("/m/n");
         router1.get("/test1/:a/c", h3);
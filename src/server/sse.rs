@@ -0,0 +1,208 @@
+/*
+ * Copyright (c) 2021. Aberic - All Rights Reserved.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use crossbeam::channel::{Receiver, RecvTimeoutError, Sender};
+
+use crate::utils::errors::{Errs, StarryResult};
+
+/// 默认心跳间隔，15秒
+const HEARTBEAT: Duration = Duration::from_secs(15);
+
+/// 服务端推送事件，详见[`HTML Living Standard，Server-sent events`]
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use starry::Event;
+///
+/// let event = Event::data("50%").id("7").event("progress").retry(Duration::from_secs(3));
+/// assert_eq!(event.encode(), b"id: 7\nevent: progress\nretry: 3000\ndata: 50%\n\n".to_vec());
+/// ```
+///
+/// [`HTML Living Standard，Server-sent events`]: https://html.spec.whatwg.org/multipage/server-sent-events.html
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Event {
+    id: Option<String>,
+    event: Option<String>,
+    data: String,
+    retry: Option<Duration>,
+}
+
+impl Event {
+    /// 以数据创建事件，多行数据会拆分为多个"data"字段
+    pub fn data(data: &str) -> Self {
+        Event { id: None, event: None, data: data.to_string(), retry: None }
+    }
+
+    /// 设置事件id，客户端重连时通过"Last-Event-ID"请求头携带最后收到的事件id
+    pub fn id(mut self, id: &str) -> Self {
+        self.id = Some(single_line(id));
+        self
+    }
+
+    /// 设置事件类型，未设置时客户端按"message"处理
+    pub fn event(mut self, event: &str) -> Self {
+        self.event = Some(single_line(event));
+        self
+    }
+
+    /// 设置客户端断线后的重连等待时间
+    pub fn retry(mut self, retry: Duration) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// 编码为"text/event-stream"格式，以空行结尾
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = String::new();
+        if let Some(src) = &self.id {
+            data.push_str(&format!("id: {}\n", src))
+        }
+        if let Some(src) = &self.event {
+            data.push_str(&format!("event: {}\n", src))
+        }
+        if let Some(src) = self.retry {
+            data.push_str(&format!("retry: {}\n", src.as_millis()))
+        }
+        for line in self.data.replace("\r\n", "\n").replace('\r', "\n").split('\n') {
+            data.push_str(&format!("data: {}\n", line));
+        }
+        data.push('\n');
+        data.into_bytes()
+    }
+}
+
+/// 换行会破坏字段结构，替换为空格
+fn single_line(src: &str) -> String {
+    src.replace(['\r', '\n'], " ")
+}
+
+/// 注释行，客户端会忽略
+fn comment(text: &str) -> Vec<u8> {
+    format!(": {}\n\n", single_line(text)).into_bytes()
+}
+
+/// 事件发送器，通过[`Context::sse`]获取
+///
+/// 可以克隆后在其它线程中使用，所有发送器释放后事件流结束。客户端断开后发送返回错误，
+/// 空闲时按心跳间隔发送注释行，以便及时发现断开的连接
+///
+/// [`Context::sse`]: crate::Context::sse
+#[derive(Clone, Debug)]
+pub struct EventSender {
+    tx: Sender<Vec<u8>>,
+    heartbeat: Arc<AtomicU64>,
+    last_event_id: Option<String>,
+}
+
+impl EventSender {
+    /// 发送事件
+    pub fn send(&self, event: Event) -> StarryResult<()> {
+        self.write(event.encode())
+    }
+
+    /// 发送仅包含数据的事件
+    pub fn send_data(&self, data: &str) -> StarryResult<()> {
+        self.send(Event::data(data))
+    }
+
+    /// 发送注释行，可用作自定义心跳
+    pub fn comment(&self, text: &str) -> StarryResult<()> {
+        self.write(comment(text))
+    }
+
+    /// 设置心跳间隔，默认15秒，为0时不发送心跳
+    ///
+    /// 新的间隔在当前等待结束后生效
+    pub fn set_heartbeat(&self, interval: Duration) {
+        self.heartbeat.store(interval.as_millis() as u64, Ordering::SeqCst)
+    }
+
+    /// 客户端重连时携带的"Last-Event-ID"
+    pub fn last_event_id(&self) -> Option<String> {
+        self.last_event_id.clone()
+    }
+
+    fn write(&self, data: Vec<u8>) -> StarryResult<()> {
+        match self.tx.send(data) {
+            Ok(()) => Ok(()),
+            Err(_) => Err(Errs::connection("sse client disconnected!"))
+        }
+    }
+}
+
+/// 事件流，每一项为一个事件或心跳，配合chunked编码逐项写出
+///
+/// 写出失败时事件流随回复一同释放，此后[`EventSender`]发送即返回错误
+pub(crate) struct EventStream {
+    rx: Receiver<Vec<u8>>,
+    heartbeat: Arc<AtomicU64>,
+}
+
+impl Iterator for EventStream {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let heartbeat = self.heartbeat.load(Ordering::SeqCst);
+        if heartbeat == 0 {
+            return self.rx.recv().ok();
+        }
+        match self.rx.recv_timeout(Duration::from_millis(heartbeat)) {
+            Ok(src) => Some(src),
+            Err(RecvTimeoutError::Timeout) => Some(comment("heartbeat")),
+            Err(RecvTimeoutError::Disconnected) => None
+        }
+    }
+}
+
+/// 创建事件发送器及对应的事件流
+pub(crate) fn channel(last_event_id: Option<String>) -> (EventSender, EventStream) {
+    let (tx, rx) = crossbeam::channel::unbounded();
+    let heartbeat = Arc::new(AtomicU64::new(HEARTBEAT.as_millis() as u64));
+    (EventSender { tx, heartbeat: heartbeat.clone(), last_event_id }, EventStream { rx, heartbeat })
+}
+
+#[cfg(test)]
+mod sse_test {
+    use std::time::Duration;
+
+    use crate::server::sse::{channel, Event};
+
+    #[test]
+    fn encode() {
+        assert_eq!(Event::data("").encode(), b"data: \n\n".to_vec());
+        assert_eq!(Event::data("a\r\nb\nc").id("1\n2").encode(), b"id: 1 2\ndata: a\ndata: b\ndata: c\n\n".to_vec());
+    }
+
+    #[test]
+    fn stream() {
+        let (sender, mut stream) = channel(Some("3".to_string()));
+        assert_eq!(sender.last_event_id(), Some("3".to_string()));
+        sender.send_data("a").unwrap();
+        assert_eq!(stream.next(), Some(b"data: a\n\n".to_vec()));
+        // 空闲时产生心跳
+        sender.set_heartbeat(Duration::from_millis(10));
+        assert_eq!(stream.next(), Some(b": heartbeat\n\n".to_vec()));
+        drop(sender);
+        assert_eq!(stream.next(), None);
+
+        let (sender, stream) = channel(None);
+        drop(stream);
+        assert!(sender.send_data("a").is_err());
+    }
+}
@@ -0,0 +1,145 @@
+/*
+ * Copyright (c) 2021. Aberic - All Rights Reserved.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+
+/// 应用共享状态，以类型为键进行存储
+///
+/// 在服务启动前通过[`HttpServer::with_state`]注册，所有请求共享同一份状态
+///
+/// [`HttpServer::with_state`]: crate::HttpServer::with_state
+#[derive(Clone)]
+pub(crate) struct State {
+    map: HashMap<TypeId, Arc<dyn Any + Send + Sync>>,
+}
+
+impl State {
+    pub(crate) fn new() -> Self {
+        State { map: HashMap::new() }
+    }
+
+    /// 注册状态，同类型的状态仅保留最后一次注册的值
+    pub(crate) fn insert<T: Send + Sync + 'static>(&mut self, state: Arc<T>) {
+        self.map.insert(TypeId::of::<T>(), state);
+    }
+
+    pub(crate) fn get<T: Send + Sync + 'static>(&self) -> Option<Arc<T>> {
+        match self.map.get(&TypeId::of::<T>()) {
+            Some(src) => src.clone().downcast::<T>().ok(),
+            None => None
+        }
+    }
+}
+
+impl Debug for State {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("State").field("len", &self.map.len()).finish()
+    }
+}
+
+/// 单次请求扩展数据，以类型为键进行存储
+///
+/// 生命周期与[`Context`]一致，可用于过滤器向后续过滤器及处理方法传递数据，如已认证的用户信息
+///
+/// [`Context`]: crate::Context
+pub(crate) struct Extensions {
+    map: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
+}
+
+impl Extensions {
+    pub(crate) fn new() -> Self {
+        Extensions { map: HashMap::new() }
+    }
+
+    /// 插入扩展数据，如已存在同类型数据，则返回旧值
+    pub(crate) fn insert<T: Send + Sync + 'static>(&mut self, value: T) -> Option<T> {
+        match self.map.insert(TypeId::of::<T>(), Box::new(value)) {
+            Some(src) => match src.downcast::<T>() {
+                Ok(old) => Some(*old),
+                Err(_) => None
+            },
+            None => None
+        }
+    }
+
+    pub(crate) fn get<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        match self.map.get(&TypeId::of::<T>()) {
+            Some(src) => src.downcast_ref::<T>(),
+            None => None
+        }
+    }
+
+    pub(crate) fn get_mut<T: Send + Sync + 'static>(&mut self) -> Option<&mut T> {
+        match self.map.get_mut(&TypeId::of::<T>()) {
+            Some(src) => src.downcast_mut::<T>(),
+            None => None
+        }
+    }
+
+    pub(crate) fn remove<T: Send + Sync + 'static>(&mut self) -> Option<T> {
+        match self.map.remove(&TypeId::of::<T>()) {
+            Some(src) => match src.downcast::<T>() {
+                Ok(value) => Some(*value),
+                Err(_) => None
+            },
+            None => None
+        }
+    }
+}
+
+impl Debug for Extensions {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Extensions").field("len", &self.map.len()).finish()
+    }
+}
+
+#[cfg(test)]
+mod state_test {
+    use std::sync::Arc;
+
+    use crate::server::state::{Extensions, State};
+
+    #[derive(Debug, PartialEq)]
+    struct AppState {
+        name: String,
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct User(String);
+
+    #[test]
+    fn state_get() {
+        let mut state = State::new();
+        assert!(state.get::<AppState>().is_none());
+        state.insert(Arc::new(AppState { name: "starry".to_string() }));
+        state.insert(Arc::new(10u32));
+        assert_eq!(state.get::<AppState>().unwrap().name, "starry");
+        assert_eq!(*state.get::<u32>().unwrap(), 10);
+        assert!(state.get::<u64>().is_none());
+    }
+
+    #[test]
+    fn extensions_insert_remove() {
+        let mut extensions = Extensions::new();
+        assert_eq!(extensions.insert(User("a".to_string())), None);
+        assert_eq!(extensions.insert(User("b".to_string())), Some(User("a".to_string())));
+        extensions.get_mut::<User>().unwrap().0.push('c');
+        assert_eq!(extensions.get::<User>(), Some(&User("bc".to_string())));
+        assert_eq!(extensions.remove::<User>(), Some(User("bc".to_string())));
+        assert!(extensions.get::<User>().is_none());
+    }
+}
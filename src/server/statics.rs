@@ -0,0 +1,301 @@
+/*
+ * Copyright (c) 2021. Aberic - All Rights Reserved.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::{Context, Status};
+use crate::http::header::ContentType;
+use crate::utils::Time;
+
+/// 静态目录资源中用于捕获文件相对路径的通配段名称
+pub(crate) const FILEPATH: &str = "filepath";
+
+/// 目录请求时默认返回的文件
+const INDEX: &str = "index.html";
+
+/// HTTP-date格式，如`Sun, 06 Nov 1994 08:49:37 GMT`
+const HTTP_DATE: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+/// 处理静态目录请求，将通配段捕获的相对路径映射为`dir`下的文件
+pub(crate) fn serve_dir(context: &mut Context, dir: &Path) {
+    let tail = context.req_field(FILEPATH).unwrap_or_default();
+    match resolve(dir, &tail) {
+        Ok(path) => serve_file(context, &path),
+        Err(status) => reply(context, status)
+    }
+}
+
+/// 处理静态文件请求
+///
+/// 支持通过"If-None-Match"及"If-Modified-Since"协商缓存，命中时返回304，
+/// 支持通过"Range"获取单段范围数据，返回206
+pub(crate) fn serve_file(context: &mut Context, path: &Path) {
+    let mut file = match File::open(path) {
+        Ok(src) => src,
+        Err(_) => return reply(context, Status::NOT_FOUND)
+    };
+    let metadata = match file.metadata() {
+        Ok(src) => src,
+        Err(err) => {
+            log::error!("static file {:?} read metadata failed! {}", path, err);
+            return reply(context, Status::INTERNAL_SERVER_ERROR);
+        }
+    };
+    if !metadata.is_file() {
+        return reply(context, Status::NOT_FOUND);
+    }
+    let len = metadata.len();
+    let modified = match metadata.modified() {
+        Ok(src) => match src.duration_since(UNIX_EPOCH) {
+            Ok(src) => src.as_secs() as i64,
+            Err(_) => 0
+        },
+        Err(_) => 0
+    };
+    let etag = format!("\"{:x}-{:x}\"", modified, len);
+    let last_modified = Time::from_secs(modified).format_string(HTTP_DATE);
+    context.resp_set_header_str("ETag", &etag);
+    context.resp_set_header_str("Last-Modified", &last_modified);
+    context.resp_set_header_str("Accept-Ranges", "bytes");
+
+    if not_modified(context, &etag, modified) {
+        return reply(context, Status::NOT_MODIFIED);
+    }
+    let range = match context.req_header_get("Range") {
+        // "If-Range"与当前文件不一致时，说明客户端缓存的部分数据已过期，需返回完整文件
+        Some(src) => match context.req_header_get("If-Range") {
+            Some(if_range) if if_range != etag && if_range != last_modified => Range::Full,
+            _ => Range::parse(&src, len)
+        },
+        None => Range::Full
+    };
+    let content_type = match path.extension() {
+        Some(ext) => ContentType::from_extension(&ext.to_string_lossy()),
+        None => ContentType::default()
+    };
+    match range {
+        Range::Full => {
+            context.resp_content_type(content_type);
+            context.resp_stream_identity(file, len);
+        }
+        Range::Partial(start, end) => {
+            match file.seek(SeekFrom::Start(start)) {
+                Ok(_) => {}
+                Err(err) => {
+                    log::error!("static file {:?} seek failed! {}", path, err);
+                    return reply(context, Status::INTERNAL_SERVER_ERROR);
+                }
+            }
+            context.resp_status(Status::PARTIAL_CONTENT);
+            context.resp_set_header(String::from("Content-Range"), format!("bytes {}-{}/{}", start, end, len));
+            context.resp_content_type(content_type);
+            context.resp_stream_identity(file.take(end - start + 1), end - start + 1);
+        }
+        Range::Unsatisfiable => {
+            context.resp_set_header(String::from("Content-Range"), format!("bytes */{}", len));
+            return reply(context, Status::RANGE_NOT_SATISFIABLE);
+        }
+    }
+    context.response()
+}
+
+fn reply(context: &mut Context, status: Status) {
+    context.resp_status(status);
+    context.response()
+}
+
+/// 判断客户端缓存是否仍然有效，"If-None-Match"存在时优先于"If-Modified-Since"
+fn not_modified(context: &Context, etag: &str, modified: i64) -> bool {
+    match context.req_header_get("If-None-Match") {
+        Some(src) => src.split(',').any(|tag| {
+            let tag = tag.trim();
+            tag == "*" || tag.trim_start_matches("W/") == etag
+        }),
+        None => match context.req_header_get("If-Modified-Since") {
+            Some(src) => match Time::parse_from_str(src.trim(), HTTP_DATE) {
+                Ok(since) => modified <= since.num_seconds(),
+                Err(_) => false
+            },
+            None => false
+        }
+    }
+}
+
+/// 将请求中的相对路径解析为`dir`下的文件路径
+///
+/// 相对路径会先进行百分号解码，解码后包含`.`、`..`、`\`等片段的路径视为越权访问，返回403。
+/// 解析后的真实路径（符号链接展开后）不在`dir`下时同样返回403
+fn resolve(dir: &Path, tail: &str) -> Result<PathBuf, Status> {
+    let tail = match percent_decode(tail) {
+        Some(src) => src,
+        None => return Err(Status::BAD_REQUEST)
+    };
+    let mut path = dir.to_path_buf();
+    for piece in tail.split('/') {
+        if piece.is_empty() {
+            continue;
+        }
+        if piece == "." || piece == ".." || piece.contains('\\') || piece.contains('\0') || piece.contains(':') {
+            return Err(Status::FORBIDDEN);
+        }
+        path.push(piece)
+    }
+    let root = match fs::canonicalize(dir) {
+        Ok(src) => src,
+        Err(_) => return Err(Status::NOT_FOUND)
+    };
+    let mut real = match fs::canonicalize(&path) {
+        Ok(src) => src,
+        Err(_) => return Err(Status::NOT_FOUND)
+    };
+    if !real.starts_with(&root) {
+        return Err(Status::FORBIDDEN);
+    }
+    if real.is_dir() {
+        real.push(INDEX)
+    }
+    Ok(real)
+}
+
+/// 百分号解码，编码不合法或解码结果不是utf-8字符串时返回None
+fn percent_decode(src: &str) -> Option<String> {
+    let bytes = src.as_bytes();
+    let mut res = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            if i + 2 >= bytes.len() || !bytes[i + 1].is_ascii_hexdigit() || !bytes[i + 2].is_ascii_hexdigit() {
+                return None;
+            }
+            let hex = String::from_utf8_lossy(&bytes[i + 1..i + 3]).to_string();
+            match u8::from_str_radix(&hex, 16) {
+                Ok(b) => res.push(b),
+                Err(_) => return None
+            }
+            i += 3
+        } else {
+            res.push(bytes[i]);
+            i += 1
+        }
+    }
+    String::from_utf8(res).ok()
+}
+
+/// 单段范围请求解析结果，多段范围请求按完整文件回复
+#[derive(Debug, PartialEq)]
+enum Range {
+    /// 回复完整文件
+    Full,
+    /// 回复指定范围，包括首尾
+    Partial(u64, u64),
+    /// 范围超出文件长度，返回416
+    Unsatisfiable,
+}
+
+impl Range {
+    /// 解析"Range"请求头，如`bytes=0-499`、`bytes=500-`、`bytes=-500`，无法识别的格式按完整文件回复
+    fn parse(src: &str, len: u64) -> Range {
+        let spec = match src.trim().strip_prefix("bytes=") {
+            Some(src) => src.trim(),
+            None => return Range::Full
+        };
+        if spec.contains(',') {
+            return Range::Full;
+        }
+        let (start, end) = match spec.find('-') {
+            Some(pos) => (spec[..pos].trim(), spec[pos + 1..].trim()),
+            None => return Range::Full
+        };
+        if start.is_empty() {
+            // 后缀范围，即文件最后n个字节
+            return match end.parse::<u64>() {
+                Ok(0) => Range::Unsatisfiable,
+                Ok(_) if len == 0 => Range::Unsatisfiable,
+                Ok(n) => Range::Partial(len - n.min(len), len - 1),
+                Err(_) => Range::Full
+            };
+        }
+        let start = match start.parse::<u64>() {
+            Ok(src) => src,
+            Err(_) => return Range::Full
+        };
+        if start >= len {
+            return Range::Unsatisfiable;
+        }
+        if end.is_empty() {
+            return Range::Partial(start, len - 1);
+        }
+        match end.parse::<u64>() {
+            Ok(end) if end >= start => Range::Partial(start, end.min(len - 1)),
+            _ => Range::Full
+        }
+    }
+}
+
+#[cfg(test)]
+mod statics_test {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use crate::server::statics::{percent_decode, Range, resolve};
+    use crate::Status;
+
+    #[test]
+    fn range_parse() {
+        assert_eq!(Range::parse("bytes=0-4", 10), Range::Partial(0, 4));
+        assert_eq!(Range::parse("bytes=5-", 10), Range::Partial(5, 9));
+        assert_eq!(Range::parse("bytes=-3", 10), Range::Partial(7, 9));
+        assert_eq!(Range::parse("bytes=-30", 10), Range::Partial(0, 9));
+        assert_eq!(Range::parse("bytes=8-100", 10), Range::Partial(8, 9));
+        assert_eq!(Range::parse("bytes=10-", 10), Range::Unsatisfiable);
+        assert_eq!(Range::parse("bytes=-0", 10), Range::Unsatisfiable);
+        assert_eq!(Range::parse("bytes=0-1,3-4", 10), Range::Full);
+        assert_eq!(Range::parse("bytes=5-2", 10), Range::Full);
+        assert_eq!(Range::parse("items=0-4", 10), Range::Full);
+    }
+
+    #[test]
+    fn percent_decode_test() {
+        assert_eq!(percent_decode("a%20b/c.txt").unwrap(), "a b/c.txt");
+        assert_eq!(percent_decode("%2e%2E/%2f").unwrap(), "..//");
+        assert!(percent_decode("a%2").is_none());
+        assert!(percent_decode("a%zz").is_none());
+    }
+
+    #[test]
+    fn resolve_test() {
+        let dir = test_dir("resolve");
+        fs::create_dir_all(dir.join("css")).unwrap();
+        fs::write(dir.join("css/site.css"), "body {}").unwrap();
+        fs::write(dir.join("index.html"), "index").unwrap();
+
+        assert_eq!(resolve(&dir, "css/site.css").unwrap(), fs::canonicalize(dir.join("css/site.css")).unwrap());
+        assert_eq!(resolve(&dir, "").unwrap(), fs::canonicalize(&dir).unwrap().join("index.html"));
+        assert_eq!(resolve(&dir, "css/none.css").unwrap_err(), Status::NOT_FOUND);
+        assert_eq!(resolve(&dir, "../resolve/index.html").unwrap_err(), Status::FORBIDDEN);
+        assert_eq!(resolve(&dir, "css/%2e%2e/%2E%2E/secret").unwrap_err(), Status::FORBIDDEN);
+        assert_eq!(resolve(&dir, "%2e%2e%2fsecret").unwrap_err(), Status::FORBIDDEN);
+        assert_eq!(resolve(&dir, "..%5csecret").unwrap_err(), Status::FORBIDDEN);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("starry_statics_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+}
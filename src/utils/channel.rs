@@ -31,22 +31,6 @@ impl<T> Channel<T> {
         Channel { tx, rx }
     }
 
-    /// 新建不限容量通道
-    pub(crate) fn unbounded() -> Self {
-        let (tx, rx) = crossbeam::channel::unbounded();
-        Channel { tx, rx }
-    }
-
-    /// 尝试在不阻塞的情况下将消息发送到信道。
-    /// 此方法将立即向通道发送消息，或者在通道已满或断开连接时返回错误。返回的错误包含原始消息。
-    /// 如果在零容量信道上调用，此方法将只在信道的另一边碰巧同时有接收操作时才发送消息。
-    pub(crate) fn try_send(&self, t: T) -> StarryResult<()> {
-        match self.tx.try_send(t) {
-            Ok(_) => Ok(()),
-            Err(err) => Err(Errs::string(err.to_string()))
-        }
-    }
-
     /// 阻塞当前线程，直到消息发送或通道断开。
     /// 如果通道已满且未断开，此调用将阻塞，直到发送操作可以继续。如果通道断开，这个调用将被唤醒并返回一个错误。
     /// 返回的错误包含原始消息。
@@ -68,15 +52,5 @@ impl<T> Channel<T> {
             Err(err) => Err(Errs::err(err))
         }
     }
-
-    /// 尝试不阻塞地从信道接收消息。
-    /// 此方法将立即从通道接收消息，如果通道为空则返回一个错误。
-    /// 如果在零容量信道上调用，此方法将仅在信道的另一边碰巧同时有发送操作时才接收消息。
-    pub(crate) fn try_recv(&self) -> StarryResult<T> {
-        match self.rx.try_recv() {
-            Ok(src) => Ok(src),
-            Err(err) => Err(Errs::err(err))
-        }
-    }
 }
 
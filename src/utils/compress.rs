@@ -77,6 +77,21 @@ impl Compress {
         }
     }
 
+    /// 流式zlib压缩，读取返回值时从reader中读取原始数据并压缩，无需事先获取全部数据
+    pub fn zlib_reader<R: Read>(reader: R, level: Compression) -> flate2::read::ZlibEncoder<R> {
+        flate2::read::ZlibEncoder::new(reader, level)
+    }
+
+    /// 流式deflate压缩，读取返回值时从reader中读取原始数据并压缩，无需事先获取全部数据
+    pub fn deflate_reader<R: Read>(reader: R, level: Compression) -> flate2::read::DeflateEncoder<R> {
+        flate2::read::DeflateEncoder::new(reader, level)
+    }
+
+    /// 流式gzip压缩，读取返回值时从reader中读取原始数据并压缩，无需事先获取全部数据
+    pub fn gzip_reader<R: Read>(reader: R, level: Compression) -> flate2::read::GzEncoder<R> {
+        flate2::read::GzEncoder::new(reader, level)
+    }
+
     pub fn un_gzip(data: &[u8]) -> StarryResult<Vec<u8>> {
         let mut d = GzDecoder::new(data);
         let mut s = vec![];
@@ -85,10 +100,41 @@ impl Compress {
             Err(err) => Err(Errs::strs("gzip uncompress failed!", err))
         }
     }
+
+    /// 按gzip格式解压，解压后的数据超过`limit`字节时返回错误，以防范压缩炸弹耗尽内存
+    pub fn un_gzip_limit(data: &[u8], limit: u64) -> StarryResult<Vec<u8>> {
+        Compress::read_limit(GzDecoder::new(data), limit, "gzip")
+    }
+
+    /// 按zlib格式解压，解压后的数据超过`limit`字节时返回错误
+    pub fn un_zlib_limit(data: &[u8], limit: u64) -> StarryResult<Vec<u8>> {
+        Compress::read_limit(ZlibDecoder::new(data), limit, "zlib")
+    }
+
+    /// 按原始deflate格式解压，解压后的数据超过`limit`字节时返回错误
+    pub fn un_deflate_limit(data: &[u8], limit: u64) -> StarryResult<Vec<u8>> {
+        Compress::read_limit(DeflateDecoder::new(data), limit, "deflate")
+    }
+
+    /// 最多读取`limit + 1`字节，读满即说明解压后的数据超出上限
+    fn read_limit<R: Read>(d: R, limit: u64, name: &str) -> StarryResult<Vec<u8>> {
+        let mut s = vec![];
+        match d.take(limit.saturating_add(1)).read_to_end(&mut s) {
+            Ok(_) => {},
+            Err(err) => return Err(Errs::strings(format!("{} uncompress failed!", name), err))
+        }
+        if s.len() as u64 > limit {
+            Err(Errs::string(format!("{} uncompressed data exceeds limit {}!", name, limit)))
+        } else {
+            Ok(s)
+        }
+    }
 }
 
 #[cfg(test)]
 mod compress_test {
+    use std::io::Read;
+
     use flate2::Compression;
     use crate::utils::compress::Compress;
 
@@ -117,6 +163,28 @@ mod compress_test {
         let data_bak = Compress::un_gzip(res.as_slice()).unwrap();
         assert_eq!(String::from_utf8(data_bak).unwrap(), "test http response body whether valid!", "data.len() = {}, res.len() = {}", data.len(), res.len());
     }
+
+    #[test]
+    fn un_limit_test() {
+        let data = vec![0u8; 1024 * 1024];
+        let res = Compress::gzip(&data, Compression::best()).unwrap();
+        assert!(res.len() < 4096);
+        assert_eq!(Compress::un_gzip_limit(&res, data.len() as u64).unwrap().len(), data.len());
+        assert!(Compress::un_gzip_limit(&res, data.len() as u64 - 1).is_err());
+        let res = Compress::zlib(&data, Compression::best()).unwrap();
+        assert!(Compress::un_zlib_limit(&res, 1024).is_err());
+        let res = Compress::deflate(&data, Compression::best()).unwrap();
+        assert!(Compress::un_deflate_limit(&res, 1024).is_err());
+        assert_eq!(Compress::un_deflate_limit(&res, 2 * 1024 * 1024).unwrap(), data);
+    }
+
+    #[test]
+    fn gzip_reader_test() {
+        let mut res = vec![];
+        Compress::gzip_reader(DD.as_bytes(), Compression::default()).read_to_end(&mut res).unwrap();
+        let data_bak = Compress::un_gzip(res.as_slice()).unwrap();
+        assert_eq!(String::from_utf8(data_bak).unwrap(), DD);
+    }
 }
 
 
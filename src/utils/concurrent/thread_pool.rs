@@ -49,7 +49,9 @@
 //! 放满后，会返回失败，在使用线程池执行任务时，应处理该失败信息
 
 use std::fmt;
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 use std::{cmp, thread};
 
 use crossbeam::atomic::AtomicCell;
@@ -289,6 +291,43 @@ impl ThreadPool {
     pub fn size(&self) -> usize {
         self.pool_state.size
     }
+
+    /// 关闭线程池，并等待工作线程退出
+    ///
+    /// 已分配给工作线程的任务会执行完毕，尚未分配的任务将被丢弃，此后提交的任务不再执行。
+    /// 超过`timeout`仍未退出的工作线程不再等待
+    ///
+    /// 返回是否所有工作线程均已退出
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    ///
+    /// use starry::utils::concurrent::ThreadPool;
+    ///
+    /// let pool = ThreadPool::new(2).unwrap();
+    /// pool.execute(|| println!("hello")).unwrap();
+    /// assert!(pool.shutdown(Duration::from_secs(1)));
+    /// ```
+    pub fn shutdown(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let workers = self.threads.read().unwrap().clone();
+        for worker in workers.iter() {
+            worker.send(Message::Close);
+        }
+        self.tx_notify.send(Notify::Close).unwrap_or(());
+        for worker in workers.iter() {
+            loop {
+                if worker.join_finished() {
+                    break;
+                }
+                if Instant::now() >= deadline {
+                    return false;
+                }
+                Thread::sleep(Duration::from_millis(5))
+            }
+        }
+        true
+    }
 }
 
 impl Clone for ThreadPool {
@@ -583,8 +622,8 @@ impl Drop for Sentinel {
             if thread::panicking() {
                 self.pool_state.panic_count.fetch_add(1);
             }
-            // 创建新线程补充
-            self.tx_notify.send(Notify::Fill(self.counter)).unwrap()
+            // 创建新线程补充，线程池已关闭时无需补充
+            self.tx_notify.send(Notify::Fill(self.counter)).unwrap_or(())
         }
         // else {
         //     println!("count = {}", self.pool_state.count.take());
@@ -601,6 +640,8 @@ struct Worker {
     name: String,
     /// 待执行任务跨线程通信发送机制
     tx_execute: Sender<Message>,
+    /// 线程句柄，线程退出后被取出并回收
+    handle: Arc<Mutex<Option<JoinHandle<()>>>>,
 }
 
 impl Worker {
@@ -615,20 +656,34 @@ impl Worker {
     ) -> StarryResult<Self> {
         let (tx_execute, rx_execute) = crossbeam::channel::unbounded();
         let pool_state_c = pool_state.clone();
-        match Thread::spawn_on_custom(
+        let handle = match Thread::spawn_on_custom(
             format!("{}{}", pool_state.name_prefix, counter),
             pool_state.stack_size,
             move || Worker::work(Sentinel::new(counter, pool_state_c, tx_notify, rx_execute)),
         ) {
             Err(err) => return Err(Errs::strs("thread builder spawn", err)),
-            _ => {}
-        }
+            Ok(src) => src,
+        };
         Ok(Self {
             name: pool_state.name_prefix.clone(),
             tx_execute,
+            handle: Arc::new(Mutex::new(Some(handle))),
         })
     }
 
+    /// 线程已退出时回收线程并返回true，线程仍在运行时返回false
+    fn join_finished(&self) -> bool {
+        let mut handle = self.handle.lock().unwrap();
+        match handle.as_ref() {
+            Some(src) if !src.is_finished() => false,
+            Some(_) => {
+                handle.take().map(|src| src.join());
+                true
+            }
+            None => true
+        }
+    }
+
     fn work(sentinel: Sentinel) {
         let rx = sentinel.rx_execute.clone();
         loop {
@@ -636,21 +691,24 @@ impl Worker {
                 Ok(res) => match res {
                     Message::Run(task) => {
                         task.run();
-                        sentinel
-                            .tx_notify
-                            .send(Notify::Idle(sentinel.counter))
-                            .unwrap()
+                        // 线程池已关闭并释放时通知无法送达，直接退出
+                        match sentinel.tx_notify.send(Notify::Idle(sentinel.counter)) {
+                            Ok(()) => {}
+                            Err(_) => break,
+                        }
                     }
                     Message::Close => break,
                 },
-                _ => {}
+                // 线程池已释放
+                Err(_) => break,
             }
         }
         sentinel.cancel()
     }
 
     fn send(&self, msg: Message) {
-        self.tx_execute.send(msg).unwrap()
+        // 线程已退出时消息无法送达，直接忽略
+        self.tx_execute.send(msg).unwrap_or(())
     }
 }
 
@@ -659,6 +717,7 @@ impl Clone for Worker {
         Self {
             name: self.name.clone(),
             tx_execute: self.tx_execute.clone(),
+            handle: self.handle.clone(),
         }
     }
 }
@@ -727,6 +786,24 @@ mod thread_pool_test {
         thread::sleep(Duration::from_secs(10));
     }
 
+    #[test]
+    fn shutdown() {
+        let thread_pool = ThreadPool::new(2).unwrap();
+        let (tx, rx) = crossbeam::channel::unbounded();
+        for n in 0..2 {
+            let tx = tx.clone();
+            thread_pool.execute(move || {
+                thread::sleep(Duration::from_millis(200));
+                tx.send(n).unwrap()
+            }).unwrap();
+        }
+        thread::sleep(Duration::from_millis(50));
+        // 执行中的任务完成后线程退出
+        assert!(!thread_pool.shutdown(Duration::from_millis(10)));
+        assert!(thread_pool.shutdown(Duration::from_secs(1)));
+        assert_eq!(rx.try_iter().count(), 2);
+    }
+
     #[test]
     fn test_4() {
         let mut thread_pool_builder = ThreadPool::builder();
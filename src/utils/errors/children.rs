@@ -27,3 +27,31 @@ impl Display for StringError {
 }
 
 impl std::error::Error for StringError {}
+
+/// 子类型 Error，表示操作超时，如连接、读写超时或超出请求总时限
+#[derive(Debug, Clone)]
+pub struct TimeoutError {
+    pub error_msg: String,
+}
+
+impl Display for TimeoutError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}", self.error_msg)
+    }
+}
+
+impl std::error::Error for TimeoutError {}
+
+/// 子类型 Error，表示连接异常，如连接被拒绝、被重置或对端未返回任何数据即关闭
+#[derive(Debug, Clone)]
+pub struct ConnectionError {
+    pub error_msg: String,
+}
+
+impl Display for ConnectionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}", self.error_msg)
+    }
+}
+
+impl std::error::Error for ConnectionError {}
@@ -13,7 +13,9 @@
  */
 
 use std::fmt::{Display, Formatter, Result};
-use crate::utils::errors::children::StringError;
+use std::io::ErrorKind;
+
+use crate::utils::errors::children::{ConnectionError, StringError, TimeoutError};
 
 trait GeorgeStringErr<M, N>: Sized {
     fn string(_: M, _: N) -> Self;
@@ -27,12 +29,28 @@ trait GeorgeString<M>: Sized {
 #[derive(Debug, Clone)]
 pub enum Error {
     StringError(StringError),
+    TimeoutError(TimeoutError),
+    ConnectionError(ConnectionError),
+}
+
+impl Error {
+    /// 是否为超时错误，可据此决定是否重试
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, Error::TimeoutError(_))
+    }
+
+    /// 是否为连接异常，如连接被拒绝、被重置或复用的连接已被对端关闭，可据此决定是否重试
+    pub fn is_connection(&self) -> bool {
+        matches!(self, Error::ConnectionError(_))
+    }
 }
 
 impl std::error::Error for Error {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match &self {
             Error::StringError(ref e) => Some(e),
+            Error::TimeoutError(ref e) => Some(e),
+            Error::ConnectionError(ref e) => Some(e),
         }
     }
 }
@@ -41,6 +59,8 @@ impl Display for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         match &self {
             Error::StringError(ref e) => e.fmt(f),
+            Error::TimeoutError(ref e) => e.fmt(f),
+            Error::ConnectionError(ref e) => e.fmt(f),
         }
     }
 }
@@ -97,6 +117,29 @@ impl Errs {
     pub fn strings<Err: ToString>(msg: String, err: Err) -> Error {
         err_strings(msg, err)
     }
+
+    /// 超时错误
+    pub fn timeout(msg: &str) -> Error {
+        Error::TimeoutError(TimeoutError { error_msg: msg.to_string() })
+    }
+
+    /// 连接异常
+    pub fn connection(msg: &str) -> Error {
+        Error::ConnectionError(ConnectionError { error_msg: msg.to_string() })
+    }
+
+    /// 根据io错误类型生成错误，读写超时（`TimedOut`或`WouldBlock`）时生成超时错误，
+    /// 连接被拒绝、重置、中断等情况生成连接异常
+    pub fn ios(msg: &str, err: std::io::Error) -> Error {
+        match err.kind() {
+            ErrorKind::TimedOut | ErrorKind::WouldBlock =>
+                Error::TimeoutError(TimeoutError { error_msg: format!("{}: {}", msg, err) }),
+            ErrorKind::ConnectionRefused | ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted
+            | ErrorKind::NotConnected | ErrorKind::BrokenPipe | ErrorKind::UnexpectedEof =>
+                Error::ConnectionError(ConnectionError { error_msg: format!("{}: {}", msg, err) }),
+            _ => err_strs(msg, err)
+        }
+    }
 }
 
 fn err_string(msg: String) -> Error {
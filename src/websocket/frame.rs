@@ -0,0 +1,229 @@
+/*
+ * Copyright (c) 2021. Aberic - All Rights Reserved.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::io;
+use std::io::{Read, Write};
+
+use openssl::rand::rand_bytes;
+
+use crate::websocket::CloseFrame;
+
+/// 控制帧载荷的最大长度
+pub(crate) const MAX_CONTROL_PAYLOAD: usize = 125;
+
+/// 帧类型，详见[`RFC6455，第5.2节`]
+///
+/// [`RFC6455，第5.2节`]: https://datatracker.ietf.org/doc/html/rfc6455#section-5.2
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum OpCode {
+    Continuation,
+    Text,
+    Binary,
+    Close,
+    Ping,
+    Pong,
+}
+
+impl OpCode {
+    fn from_u8(src: u8) -> Option<OpCode> {
+        match src {
+            0x0 => Some(OpCode::Continuation),
+            0x1 => Some(OpCode::Text),
+            0x2 => Some(OpCode::Binary),
+            0x8 => Some(OpCode::Close),
+            0x9 => Some(OpCode::Ping),
+            0xA => Some(OpCode::Pong),
+            _ => None
+        }
+    }
+
+    fn as_u8(&self) -> u8 {
+        match self {
+            OpCode::Continuation => 0x0,
+            OpCode::Text => 0x1,
+            OpCode::Binary => 0x2,
+            OpCode::Close => 0x8,
+            OpCode::Ping => 0x9,
+            OpCode::Pong => 0xA,
+        }
+    }
+
+    /// 是否为控制帧，控制帧不允许分片且载荷不超过125字节
+    pub(crate) fn is_control(&self) -> bool {
+        matches!(self, OpCode::Close | OpCode::Ping | OpCode::Pong)
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Frame {
+    /// 是否为消息的最后一个分片
+    pub(crate) fin: bool,
+    pub(crate) opcode: OpCode,
+    /// 已去除掩码的载荷
+    pub(crate) payload: Vec<u8>,
+}
+
+/// 读取帧失败的原因
+#[derive(Debug)]
+pub(crate) enum FrameError {
+    /// 连接读取失败或被关闭
+    Io(io::Error),
+    /// 对端违反协议，需以对应关闭码关闭连接
+    Protocol(u16, String),
+}
+
+impl From<io::Error> for FrameError {
+    fn from(err: io::Error) -> Self {
+        FrameError::Io(err)
+    }
+}
+
+/// 读取一帧
+///
+/// * masked 对端发送的帧是否必须使用掩码，客户端发送的帧必须使用掩码，服务端发送的帧不允许使用掩码
+/// * max 允许的最大载荷长度，超出时以1009关闭
+pub(crate) fn read<R: Read>(reader: &mut R, masked: bool, max: usize) -> Result<Frame, FrameError> {
+    let mut head = [0; 2];
+    reader.read_exact(&mut head)?;
+    let fin = head[0] & 0x80 != 0;
+    // 未协商任何扩展，保留位必须为0
+    if head[0] & 0x70 != 0 {
+        return Err(FrameError::Protocol(CloseFrame::PROTOCOL_ERROR, "reserved bits must be 0".to_string()));
+    }
+    let opcode = match OpCode::from_u8(head[0] & 0x0F) {
+        Some(src) => src,
+        None => return Err(FrameError::Protocol(CloseFrame::PROTOCOL_ERROR, format!("unknown opcode {}", head[0] & 0x0F)))
+    };
+    if (head[1] & 0x80 != 0) != masked {
+        return Err(FrameError::Protocol(CloseFrame::PROTOCOL_ERROR,
+                                        if masked { "frame must be masked" } else { "frame must not be masked" }.to_string()));
+    }
+    let len = match head[1] & 0x7F {
+        126 => {
+            let mut buf = [0; 2];
+            reader.read_exact(&mut buf)?;
+            u16::from_be_bytes(buf) as u64
+        }
+        127 => {
+            let mut buf = [0; 8];
+            reader.read_exact(&mut buf)?;
+            u64::from_be_bytes(buf)
+        }
+        src => src as u64
+    };
+    if opcode.is_control() && (!fin || len > MAX_CONTROL_PAYLOAD as u64) {
+        return Err(FrameError::Protocol(CloseFrame::PROTOCOL_ERROR, "control frame must not be fragmented or longer than 125".to_string()));
+    }
+    if len > max as u64 {
+        return Err(FrameError::Protocol(CloseFrame::TOO_BIG, format!("message exceeds {} bytes", max)));
+    }
+    let mut mask = [0; 4];
+    if masked {
+        reader.read_exact(&mut mask)?;
+    }
+    let mut payload = vec![0; len as usize];
+    reader.read_exact(&mut payload)?;
+    if masked {
+        apply_mask(&mut payload, mask);
+    }
+    Ok(Frame { fin, opcode, payload })
+}
+
+/// 写入一帧，帧头与载荷合并后一次写入
+///
+/// * masked 是否使用随机掩码，客户端发送的帧必须使用掩码
+pub(crate) fn write<W: Write>(writer: &mut W, fin: bool, opcode: OpCode, payload: &[u8], masked: bool) -> io::Result<()> {
+    let mut data = Vec::with_capacity(payload.len() + 14);
+    data.push(if fin { 0x80 } else { 0 } | opcode.as_u8());
+    let mask_bit = if masked { 0x80 } else { 0 };
+    let len = payload.len();
+    if len < 126 {
+        data.push(mask_bit | len as u8);
+    } else if len <= u16::MAX as usize {
+        data.push(mask_bit | 126);
+        data.extend_from_slice(&(len as u16).to_be_bytes());
+    } else {
+        data.push(mask_bit | 127);
+        data.extend_from_slice(&(len as u64).to_be_bytes());
+    }
+    if masked {
+        let mut mask = [0; 4];
+        match rand_bytes(&mut mask) {
+            Ok(()) => {}
+            Err(err) => return Err(io::Error::other(err))
+        }
+        data.extend_from_slice(&mask);
+        let from = data.len();
+        data.extend_from_slice(payload);
+        apply_mask(&mut data[from..], mask);
+    } else {
+        data.extend_from_slice(payload);
+    }
+    writer.write_all(&data)?;
+    writer.flush()
+}
+
+fn apply_mask(data: &mut [u8], mask: [u8; 4]) {
+    for (i, b) in data.iter_mut().enumerate() {
+        *b ^= mask[i % 4]
+    }
+}
+
+#[cfg(test)]
+mod frame_test {
+    use std::io::Cursor;
+
+    use crate::websocket::CloseFrame;
+    use crate::websocket::frame::{FrameError, OpCode, read, write};
+
+    #[test]
+    fn read_write() {
+        for len in vec![0, 125, 126, 65535, 65536] {
+            let payload = vec![b'a'; len];
+            let mut data = vec![];
+            write(&mut data, true, OpCode::Binary, &payload, true).unwrap();
+            let frame = read(&mut Cursor::new(data), true, 1 << 20).unwrap();
+            assert!(frame.fin);
+            assert_eq!(frame.opcode, OpCode::Binary);
+            assert_eq!(frame.payload, payload);
+        }
+        // RFC6455第5.7节示例：服务端发送的未分片文本消息
+        let mut data = vec![];
+        write(&mut data, true, OpCode::Text, b"Hello", false).unwrap();
+        assert_eq!(data, vec![0x81, 0x05, 0x48, 0x65, 0x6c, 0x6c, 0x6f]);
+        // 客户端发送的带掩码文本消息
+        let frame = read(&mut Cursor::new(vec![0x81, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0x7f, 0x9f, 0x4d, 0x51, 0x58]), true, 125).unwrap();
+        assert_eq!(frame.payload, b"Hello".to_vec());
+    }
+
+    #[test]
+    fn read_invalid() {
+        let code = |data: Vec<u8>, masked: bool, max: usize| match read(&mut Cursor::new(data), masked, max) {
+            Err(FrameError::Protocol(code, _)) => code,
+            res => panic!("unexpected {:?}", res)
+        };
+        // 未使用掩码、保留位非0、未知帧类型
+        assert_eq!(code(vec![0x81, 0x00], true, 125), CloseFrame::PROTOCOL_ERROR);
+        assert_eq!(code(vec![0xC1, 0x00], false, 125), CloseFrame::PROTOCOL_ERROR);
+        assert_eq!(code(vec![0x83, 0x00], false, 125), CloseFrame::PROTOCOL_ERROR);
+        // 分片的控制帧
+        assert_eq!(code(vec![0x09, 0x00], false, 125), CloseFrame::PROTOCOL_ERROR);
+        // 超出长度限制
+        assert_eq!(code(vec![0x82, 0x7E, 0x01, 0x00], false, 255), CloseFrame::TOO_BIG);
+        match read(&mut Cursor::new(vec![0x82, 0x05, 0x01]), false, 125) {
+            Err(FrameError::Io(_)) => {}
+            res => panic!("unexpected {:?}", res)
+        }
+    }
+}
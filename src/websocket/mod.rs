@@ -0,0 +1,195 @@
+/*
+ * Copyright (c) 2021. Aberic - All Rights Reserved.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::fmt;
+
+use openssl::sha::sha1;
+
+pub use socket::{CloseFrame, Message, WebSocket};
+
+use crate::http::stream::HttpStream;
+use crate::utils::cryptos::base64::Base64Encoder;
+use crate::utils::cryptos::Base64;
+
+mod frame;
+mod socket;
+
+/// 计算"Sec-WebSocket-Accept"时拼接在"Sec-WebSocket-Key"之后的固定值
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// 根据"Sec-WebSocket-Key"计算"Sec-WebSocket-Accept"，详见[`RFC6455，第4.2.2节`]
+///
+/// [`RFC6455，第4.2.2节`]: https://datatracker.ietf.org/doc/html/rfc6455#section-4.2.2
+pub(crate) fn accept_key(key: &str) -> String {
+    Base64::encode(&sha1(format!("{}{}", key.trim(), GUID).as_bytes())[..])
+}
+
+/// 握手完成后接管连接的处理方法
+pub(crate) struct Upgrade(Box<dyn FnOnce(WebSocket) + Send>);
+
+impl Upgrade {
+    pub(crate) fn new<F>(f: F) -> Self where F: FnOnce(WebSocket) + Send + 'static {
+        Upgrade(Box::new(f))
+    }
+
+    /// 以服务端身份在当前线程中执行处理方法，处理方法返回后连接即被关闭
+    ///
+    /// * pending 读取握手请求时一同读取的数据，如客户端紧随握手发送的帧，会在读取连接前被优先读取
+    pub(crate) fn run(self, stream: HttpStream, pending: Vec<u8>) {
+        (self.0)(WebSocket::new(stream, false, pending))
+    }
+}
+
+impl fmt::Debug for Upgrade {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Upgrade")
+    }
+}
+
+#[cfg(test)]
+mod websocket_test {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::time::Duration;
+
+    use crate::{CloseFrame, Context, HttpServer, Message};
+    use crate::server::server::server_test::serve;
+    use crate::websocket::accept_key;
+    use crate::websocket::frame::{OpCode, read, write};
+
+    #[test]
+    fn accept_key_test() {
+        // RFC6455第1.3节示例
+        assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+    }
+
+    fn h_echo(context: &mut Context) {
+        context.upgrade_websocket(|mut ws| {
+            ws.set_max_message_size(1024);
+            ws.set_fragment_size(4);
+            loop {
+                match ws.read() {
+                    Ok(Message::Text(src)) => ws.send_text(&src).unwrap(),
+                    Ok(Message::Binary(src)) => ws.send_binary(src).unwrap(),
+                    Ok(Message::Close(_)) | Err(_) => return,
+                    Ok(_) => {}
+                }
+            }
+        }).unwrap_or(())
+    }
+
+    /// 完成握手，返回握手回复
+    ///
+    /// * extra 握手请求中额外的报头，为None时表示握手请求已发送，仅读取回复
+    fn handshake(stream: &mut TcpStream, extra: Option<&str>) -> String {
+        match extra {
+            Some(src) => stream.write_all(format!("GET /ws/echo HTTP/1.1\r\nHost: 127.0.0.1\r\nconnection: keep-alive, Upgrade\r\n\
+            upgrade: WebSocket\r\nsec-websocket-key: dGhlIHNhbXBsZSBub25jZQ==\r\n{}\r\n", src).as_bytes()).unwrap(),
+            None => {}
+        }
+        let mut head = vec![];
+        let mut buffer = [0; 1];
+        while !head.ends_with(b"\r\n\r\n") {
+            match stream.read(&mut buffer).unwrap() {
+                0 => break,
+                _ => head.push(buffer[0])
+            }
+        }
+        String::from_utf8(head).unwrap()
+    }
+
+    /// 读取一条完整消息，返回类型及重组后的载荷
+    fn read_message(stream: &mut TcpStream) -> (OpCode, Vec<u8>) {
+        let frame = read(stream, false, 1 << 20).unwrap();
+        let (opcode, mut data, mut fin) = (frame.opcode, frame.payload, frame.fin);
+        while !fin {
+            let frame = read(stream, false, 1 << 20).unwrap();
+            assert_eq!(frame.opcode, OpCode::Continuation);
+            data.extend_from_slice(&frame.payload);
+            fin = frame.fin
+        }
+        (opcode, data)
+    }
+
+    #[test]
+    fn upgrade() {
+        let server = HttpServer::new();
+        server.router("/ws").get("/echo", h_echo);
+        let addr = serve(&server).local_addr();
+
+        // 缺少版本号
+        let mut stream = TcpStream::connect(addr).unwrap();
+        assert!(handshake(&mut stream, Some("")).starts_with("HTTP/1.1 426 Upgrade Required\r\n"));
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let head = handshake(&mut stream, Some("Sec-WebSocket-Version: 13\r\n"));
+        assert!(head.starts_with("HTTP/1.1 101 Switching Protocols\r\n"), "{}", head);
+        assert!(head.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+        assert!(!head.contains("Content-Length"));
+
+        // 分片发送的文本消息中间穿插ping，服务端先回复pong，再以4字节分片回复完整消息
+        write(&mut stream, false, OpCode::Text, "hello ".as_bytes(), true).unwrap();
+        write(&mut stream, true, OpCode::Ping, b"p", true).unwrap();
+        write(&mut stream, true, OpCode::Continuation, "世界".as_bytes(), true).unwrap();
+        assert_eq!(read_message(&mut stream), (OpCode::Pong, b"p".to_vec()));
+        assert_eq!(read_message(&mut stream), (OpCode::Text, "hello 世界".as_bytes().to_vec()));
+        write(&mut stream, true, OpCode::Binary, &[0, 1, 2], true).unwrap();
+        assert_eq!(read_message(&mut stream), (OpCode::Binary, vec![0, 1, 2]));
+
+        // 正常关闭，服务端回复相同的关闭码后关闭连接
+        write(&mut stream, true, OpCode::Close, &[0x03, 0xE8, b'b', b'y', b'e'], true).unwrap();
+        assert_eq!(read_message(&mut stream), (OpCode::Close, vec![0x03, 0xE8]));
+        assert_eq!(stream.read(&mut [0; 1]).unwrap(), 0);
+
+        // 超出最大消息长度时以1009关闭
+        let mut stream = TcpStream::connect(addr).unwrap();
+        handshake(&mut stream, Some("Sec-WebSocket-Version: 13\r\n"));
+        write(&mut stream, false, OpCode::Binary, &[0; 1000], true).unwrap();
+        write(&mut stream, true, OpCode::Continuation, &[0; 100], true).unwrap();
+        let (opcode, data) = read_message(&mut stream);
+        assert_eq!(opcode, OpCode::Close);
+        assert_eq!(u16::from_be_bytes([data[0], data[1]]), CloseFrame::TOO_BIG);
+
+        // 非法UTF-8文本以1007关闭
+        let mut stream = TcpStream::connect(addr).unwrap();
+        handshake(&mut stream, Some("Sec-WebSocket-Version: 13\r\n"));
+        write(&mut stream, true, OpCode::Text, &[0xFF, 0xFE], true).unwrap();
+        let (opcode, data) = read_message(&mut stream);
+        assert_eq!(opcode, OpCode::Close);
+        assert_eq!(u16::from_be_bytes([data[0], data[1]]), CloseFrame::INVALID_PAYLOAD);
+    }
+
+    #[test]
+    fn upgrade_pending() {
+        let server = HttpServer::new();
+        server.router("/ws").get("/echo", h_echo);
+        let handle = serve(&server);
+
+        // 握手请求与第一帧在同一次写入中发送，第一帧不应丢失
+        let mut stream = TcpStream::connect(handle.local_addr()).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(3))).unwrap();
+        let mut data = b"GET /ws/echo HTTP/1.1\r\nHost: 127.0.0.1\r\nConnection: Upgrade\r\nUpgrade: websocket\r\n\
+        Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n".to_vec();
+        write(&mut data, true, OpCode::Text, b"first", true).unwrap();
+        stream.write_all(&data).unwrap();
+        let head = handshake(&mut stream, None);
+        assert!(head.starts_with("HTTP/1.1 101 Switching Protocols\r\n"), "{}", head);
+        assert_eq!(read_message(&mut stream), (OpCode::Text, b"first".to_vec()));
+        write(&mut stream, true, OpCode::Text, b"second", true).unwrap();
+        assert_eq!(read_message(&mut stream), (OpCode::Text, b"second".to_vec()));
+        write(&mut stream, true, OpCode::Close, &[0x03, 0xE8], true).unwrap();
+        assert_eq!(read_message(&mut stream), (OpCode::Close, vec![0x03, 0xE8]));
+        assert!(handle.shutdown(Duration::from_secs(1)));
+    }
+}
@@ -0,0 +1,355 @@
+/*
+ * Copyright (c) 2021. Aberic - All Rights Reserved.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ * http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::io::{Cursor, Read};
+use std::time::Duration;
+
+use crate::http::stream::HttpStream;
+use crate::utils::errors::{Error, Errs, StarryResult};
+use crate::websocket::frame;
+use crate::websocket::frame::{FrameError, MAX_CONTROL_PAYLOAD, OpCode};
+
+/// 默认允许接收的最大消息长度，16MB
+const MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+/// websocket消息
+#[derive(Clone, Debug, PartialEq)]
+pub enum Message {
+    /// 文本消息，已校验为合法的UTF-8
+    Text(String),
+    /// 二进制消息
+    Binary(Vec<u8>),
+    /// ping，接收到时已自动回复pong
+    Ping(Vec<u8>),
+    /// pong
+    Pong(Vec<u8>),
+    /// 关闭，对端未携带关闭码时为None，接收到时已自动回复关闭
+    Close(Option<CloseFrame>),
+}
+
+/// 关闭帧所携带的关闭码及原因
+#[derive(Clone, Debug, PartialEq)]
+pub struct CloseFrame {
+    pub code: u16,
+    pub reason: String,
+}
+
+impl CloseFrame {
+    /// 正常关闭
+    pub const NORMAL: u16 = 1000;
+    /// 服务端停机或客户端离开页面
+    pub const GOING_AWAY: u16 = 1001;
+    /// 对端违反协议
+    pub const PROTOCOL_ERROR: u16 = 1002;
+    /// 接收到无法处理的数据类型
+    pub const UNSUPPORTED: u16 = 1003;
+    /// 消息内容与类型不符，如文本消息不是合法的UTF-8
+    pub const INVALID_PAYLOAD: u16 = 1007;
+    /// 消息违反策略
+    pub const POLICY_VIOLATION: u16 = 1008;
+    /// 消息过大
+    pub const TOO_BIG: u16 = 1009;
+    /// 服务端内部错误
+    pub const INTERNAL_ERROR: u16 = 1011;
+
+    pub fn new(code: u16, reason: &str) -> Self {
+        CloseFrame { code, reason: reason.to_string() }
+    }
+
+    /// 是否为允许在关闭帧中发送的关闭码，详见[`RFC6455，第7.4节`]
+    ///
+    /// [`RFC6455，第7.4节`]: https://datatracker.ietf.org/doc/html/rfc6455#section-7.4
+    fn is_valid_code(code: u16) -> bool {
+        matches!(code, 1000..=1003 | 1007..=1011 | 3000..=4999)
+    }
+}
+
+/// 完成握手后的websocket连接
+///
+/// 接收时自动回复ping及关闭帧，并将分片消息重组为完整消息；对端违反协议、发送非法UTF-8文本或消息过大时，
+/// 以对应关闭码关闭连接并返回错误
+///
+/// ```no_run
+/// use starry::{Context, HttpServer, Message};
+///
+/// fn echo(context: &mut Context) {
+///     match context.upgrade_websocket(|mut ws| loop {
+///         match ws.read() {
+///             Ok(Message::Text(src)) => ws.send_text(&src).unwrap_or(()),
+///             Ok(Message::Binary(src)) => ws.send_binary(src).unwrap_or(()),
+///             Ok(Message::Close(_)) | Err(_) => return,
+///             Ok(_) => {}
+///         }
+///     }) {
+///         Ok(()) => {}
+///         Err(err) => log::info!("websocket upgrade failed! {}", err)
+///     }
+/// }
+///
+/// let server = HttpServer::new();
+/// server.router("/ws").get("/echo", echo);
+/// server.listener("127.0.0.1:7878").unwrap();
+/// ```
+#[derive(Debug)]
+pub struct WebSocket {
+    stream: HttpStream,
+    /// 握手时已读取但属于websocket的数据，读取时优先消费
+    pending: Cursor<Vec<u8>>,
+    /// 是否为客户端，客户端发送的帧必须使用掩码，接收的帧不允许使用掩码
+    client: bool,
+    /// 允许接收的最大消息长度，分片消息按重组后的长度计算
+    max_message_size: usize,
+    /// 发送消息时单个分片的最大长度，0表示不分片
+    fragment_size: usize,
+    /// 正在接收的分片消息
+    partial: Option<(OpCode, Vec<u8>)>,
+    /// 是否已发送关闭帧
+    close_sent: bool,
+    /// 是否已接收关闭帧或因错误终止
+    close_received: bool,
+}
+
+impl WebSocket {
+    pub(crate) fn new(stream: HttpStream, client: bool, pending: Vec<u8>) -> Self {
+        WebSocket {
+            stream,
+            pending: Cursor::new(pending),
+            client,
+            max_message_size: MAX_MESSAGE_SIZE,
+            fragment_size: 0,
+            partial: None,
+            close_sent: false,
+            close_received: false,
+        }
+    }
+
+    /// 设置允许接收的最大消息长度，默认16MB，超出时以1009关闭连接
+    pub fn set_max_message_size(&mut self, size: usize) {
+        self.max_message_size = size
+    }
+
+    /// 设置发送消息时单个分片的最大长度，默认0表示不分片
+    pub fn set_fragment_size(&mut self, size: usize) {
+        self.fragment_size = size
+    }
+
+    /// 设置读取超时，None表示一直等待
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> StarryResult<()> {
+        match self.stream.set_read_timeout(timeout) {
+            Ok(()) => Ok(()),
+            Err(err) => Err(Errs::ios("websocket set read timeout failed!", err))
+        }
+    }
+
+    /// 设置写入超时，None表示一直等待
+    pub fn set_write_timeout(&self, timeout: Option<Duration>) -> StarryResult<()> {
+        match self.stream.set_write_timeout(timeout) {
+            Ok(()) => Ok(()),
+            Err(err) => Err(Errs::ios("websocket set write timeout failed!", err))
+        }
+    }
+
+    /// 读取下一条消息
+    ///
+    /// 接收到关闭帧后返回[`Message::Close`]，此后再读取将返回错误
+    ///
+    /// [`Message::Close`]: crate::Message::Close
+    pub fn read(&mut self) -> StarryResult<Message> {
+        loop {
+            if self.close_received {
+                return Err(Errs::connection("websocket closed!"));
+            }
+            let mut reader = (&mut self.pending).chain(&mut self.stream);
+            let frame = match frame::read(&mut reader, !self.client, self.max_message_size) {
+                Ok(src) => src,
+                Err(FrameError::Io(err)) => {
+                    self.close_received = true;
+                    return Err(Errs::ios("websocket read failed!", err));
+                }
+                Err(FrameError::Protocol(code, reason)) => return Err(self.fail(code, reason)),
+            };
+            match frame.opcode {
+                OpCode::Ping => {
+                    if !self.close_sent {
+                        self.write(true, OpCode::Pong, &frame.payload)?;
+                    }
+                    return Ok(Message::Ping(frame.payload));
+                }
+                OpCode::Pong => return Ok(Message::Pong(frame.payload)),
+                OpCode::Close => return self.on_close(frame.payload),
+                OpCode::Continuation => match self.partial.take() {
+                    Some((opcode, mut data)) => {
+                        if data.len() + frame.payload.len() > self.max_message_size {
+                            return Err(self.fail(CloseFrame::TOO_BIG, format!("message exceeds {} bytes", self.max_message_size)));
+                        }
+                        data.extend_from_slice(&frame.payload);
+                        if frame.fin {
+                            return self.message(opcode, data);
+                        }
+                        self.partial = Some((opcode, data))
+                    }
+                    None => return Err(self.fail(CloseFrame::PROTOCOL_ERROR, "unexpected continuation frame".to_string()))
+                },
+                OpCode::Text | OpCode::Binary => {
+                    if self.partial.is_some() {
+                        return Err(self.fail(CloseFrame::PROTOCOL_ERROR, "expected continuation frame".to_string()));
+                    }
+                    if frame.fin {
+                        return self.message(frame.opcode, frame.payload);
+                    }
+                    self.partial = Some((frame.opcode, frame.payload))
+                }
+            }
+        }
+    }
+
+    /// 发送消息，发送[`Message::Close`]等同于调用[`close`]
+    ///
+    /// [`Message::Close`]: crate::Message::Close
+    /// [`close`]: crate::WebSocket::close
+    pub fn send(&mut self, message: Message) -> StarryResult<()> {
+        match message {
+            Message::Text(src) => self.write_message(OpCode::Text, src.as_bytes()),
+            Message::Binary(src) => self.write_message(OpCode::Binary, &src),
+            Message::Ping(src) => self.write_control(OpCode::Ping, &src),
+            Message::Pong(src) => self.write_control(OpCode::Pong, &src),
+            Message::Close(frame) => {
+                if self.close_sent {
+                    return Err(Errs::str("websocket close frame already sent!"));
+                }
+                let payload = match frame {
+                    Some(src) => {
+                        let mut payload = src.code.to_be_bytes().to_vec();
+                        payload.extend_from_slice(src.reason.as_bytes());
+                        payload
+                    }
+                    None => vec![]
+                };
+                self.write_control(OpCode::Close, &payload)?;
+                self.close_sent = true;
+                Ok(())
+            }
+        }
+    }
+
+    /// 发送文本消息
+    pub fn send_text(&mut self, text: &str) -> StarryResult<()> {
+        self.write_message(OpCode::Text, text.as_bytes())
+    }
+
+    /// 发送二进制消息
+    pub fn send_binary(&mut self, data: Vec<u8>) -> StarryResult<()> {
+        self.write_message(OpCode::Binary, &data)
+    }
+
+    /// 发送ping，载荷不超过125字节
+    pub fn ping(&mut self, payload: Vec<u8>) -> StarryResult<()> {
+        self.write_control(OpCode::Ping, &payload)
+    }
+
+    /// 发送关闭帧，此后不能再发送消息，但仍可继续读取，直到接收到对端回复的关闭帧
+    ///
+    /// * code 关闭码，如[`CloseFrame::NORMAL`]
+    /// * reason 关闭原因，与关闭码合计不超过125字节
+    ///
+    /// [`CloseFrame::NORMAL`]: crate::CloseFrame::NORMAL
+    pub fn close(&mut self, code: u16, reason: &str) -> StarryResult<()> {
+        self.send(Message::Close(Some(CloseFrame::new(code, reason))))
+    }
+
+    /// 处理接收到的关闭帧，尚未发送关闭帧时回复相同的关闭码
+    fn on_close(&mut self, payload: Vec<u8>) -> StarryResult<Message> {
+        let frame = match payload.len() {
+            0 => None,
+            1 => return Err(self.fail(CloseFrame::PROTOCOL_ERROR, "close frame payload too short".to_string())),
+            _ => {
+                let code = u16::from_be_bytes([payload[0], payload[1]]);
+                if !CloseFrame::is_valid_code(code) {
+                    return Err(self.fail(CloseFrame::PROTOCOL_ERROR, format!("invalid close code {}", code)));
+                }
+                match String::from_utf8(payload[2..].to_vec()) {
+                    Ok(reason) => Some(CloseFrame { code, reason }),
+                    Err(_) => return Err(self.fail(CloseFrame::INVALID_PAYLOAD, "close reason is not utf-8".to_string()))
+                }
+            }
+        };
+        self.close_received = true;
+        if !self.close_sent {
+            self.close_sent = true;
+            let payload = match &frame {
+                Some(src) => src.code.to_be_bytes().to_vec(),
+                None => vec![]
+            };
+            self.write(true, OpCode::Close, &payload)?;
+        }
+        Ok(Message::Close(frame))
+    }
+
+    fn message(&mut self, opcode: OpCode, data: Vec<u8>) -> StarryResult<Message> {
+        match opcode {
+            OpCode::Text => match String::from_utf8(data) {
+                Ok(src) => Ok(Message::Text(src)),
+                Err(_) => Err(self.fail(CloseFrame::INVALID_PAYLOAD, "text message is not utf-8".to_string()))
+            },
+            _ => Ok(Message::Binary(data))
+        }
+    }
+
+    /// 以指定关闭码关闭连接，并返回对应错误
+    fn fail(&mut self, code: u16, reason: String) -> Error {
+        if !self.close_sent {
+            self.close_sent = true;
+            let mut payload = code.to_be_bytes().to_vec();
+            payload.extend_from_slice(reason.as_bytes());
+            payload.truncate(MAX_CONTROL_PAYLOAD);
+            match self.write(true, OpCode::Close, &payload) {
+                Ok(()) => {}
+                Err(err) => log::debug!("websocket close with {} failed! {}", code, err)
+            }
+        }
+        self.close_received = true;
+        self.partial = None;
+        Errs::string(format!("websocket closed with {}, {}!", code, reason))
+    }
+
+    /// 发送数据消息，设置了分片长度时拆分为多个分片发送
+    fn write_message(&mut self, opcode: OpCode, payload: &[u8]) -> StarryResult<()> {
+        if self.fragment_size == 0 || payload.len() <= self.fragment_size {
+            return self.write(true, opcode, payload);
+        }
+        let count = payload.len().div_ceil(self.fragment_size);
+        for (i, chunk) in payload.chunks(self.fragment_size).enumerate() {
+            let opcode = if i == 0 { opcode } else { OpCode::Continuation };
+            self.write(i + 1 == count, opcode, chunk)?;
+        }
+        Ok(())
+    }
+
+    fn write_control(&mut self, opcode: OpCode, payload: &[u8]) -> StarryResult<()> {
+        if payload.len() > MAX_CONTROL_PAYLOAD {
+            return Err(Errs::string(format!("websocket control frame payload {} exceeds 125 bytes!", payload.len())));
+        }
+        self.write(true, opcode, payload)
+    }
+
+    fn write(&mut self, fin: bool, opcode: OpCode, payload: &[u8]) -> StarryResult<()> {
+        if self.close_sent && opcode != OpCode::Close {
+            return Err(Errs::str("websocket close frame already sent!"));
+        }
+        match frame::write(&mut self.stream, fin, opcode, payload, self.client) {
+            Ok(()) => Ok(()),
+            Err(err) => Err(Errs::ios("websocket write failed!", err))
+        }
+    }
+}